@@ -0,0 +1,98 @@
+//! Clears and redraws the whole terminal every frame.
+//!
+//! Counts the heap allocations per frame. A clear keeps the buffers
+//! of the cells as long as the size doesn't change.
+//!
+//! Run with `cargo bench --bench clear`.
+
+use rat_wgpu::Builder;
+use rat_wgpu::font::{Font, Fonts};
+use rat_wgpu::postprocessor::default::DefaultPostProcessorBuilder;
+use ratatui_core::terminal::Terminal;
+use ratatui_widgets::paragraph::{Paragraph, Wrap};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+const ROUNDS: u32 = 200;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn main() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("../tests/fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    12,
+                ))
+                .with_width_and_height(1024, 512)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let size = terminal.size().unwrap();
+    let text = "lorem ipsum dolor sit amet consectetur adipiscing elit "
+        .repeat((size.width as usize * size.height as usize) / 56 + 1);
+    let frame = |terminal: &mut Terminal<_>| {
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(
+                    Paragraph::new(text.as_str()).wrap(Wrap { trim: false }),
+                    f.area(),
+                );
+            })
+            .unwrap();
+    };
+    // fill the glyph atlas.
+    frame(&mut terminal);
+
+    let mut clear = 0;
+    let mut total = 0;
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        terminal.clear().unwrap();
+        let cleared = ALLOCATIONS.load(Ordering::Relaxed);
+        frame(&mut terminal);
+        let after = ALLOCATIONS.load(Ordering::Relaxed);
+        clear += cleared - before;
+        total += after - before;
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{}x{} cells, {ROUNDS} frames with a clear",
+        size.width, size.height
+    );
+    println!("  {:?} per frame", elapsed / ROUNDS);
+    println!(
+        "  allocations per frame: {:.1} in clear, {:.1} in total",
+        clear as f64 / ROUNDS as f64,
+        total as f64 / ROUNDS as f64,
+    );
+}
@@ -0,0 +1,65 @@
+//! Changes one cell in a wide row and flushes.
+//!
+//! Rows are shaped in segments that end after a blank. Only the
+//! segment with the changed cell is shaped, the others come from
+//! the shaping cache.
+//!
+//! Run with `cargo bench --bench shaping`.
+
+use rat_wgpu::Builder;
+use rat_wgpu::font::{Font, Fonts};
+use rat_wgpu::postprocessor::default::DefaultPostProcessorBuilder;
+use ratatui_core::backend::Backend;
+use ratatui_core::buffer::Cell;
+use ratatui_core::terminal::Terminal;
+use ratatui_widgets::paragraph::Paragraph;
+use std::time::Instant;
+
+const ROUNDS: u32 = 200;
+
+fn main() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("../tests/fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    12,
+                ))
+                .with_width_and_height(2048, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let width = terminal.size().unwrap().width;
+    let text =
+        "lorem ipsum dolor sit amet consectetur adipiscing elit ".repeat(width as usize / 56 + 1);
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new(text.as_str()), f.area());
+        })
+        .unwrap();
+    let words = text[..width as usize].split(' ').count() as u64;
+
+    let before = terminal.backend().shaping_stats();
+    let start = Instant::now();
+    for n in 0..ROUNDS {
+        let mut cell = Cell::default();
+        cell.set_char(if n % 2 == 0 { 'X' } else { 'Y' });
+        let backend = terminal.backend_mut();
+        backend.draw([(width / 2, 0, &cell)].into_iter()).unwrap();
+        backend.flush().unwrap();
+    }
+    let elapsed = start.elapsed();
+    let after = terminal.backend().shaping_stats();
+
+    println!("row of {width} cells with {words} words, {ROUNDS} flushes");
+    println!("  {:?} per flush", elapsed / ROUNDS);
+    println!(
+        "  runs per flush: {:.1} shaped, {:.1} from the cache, shaping the row would be {words} runs",
+        (after.shaped - before.shaped) as f64 / ROUNDS as f64,
+        (after.cached - before.cached) as f64 / ROUNDS as f64,
+    );
+}
@@ -43,17 +43,15 @@ impl ApplicationHandler for App<'_> {
 
         self.backend = Some(
             Terminal::new(
-                futures_lite::future::block_on(
-                    Builder::new()
-                        .with_fonts([
-                            FontData.fallback_font().expect("fallback"),
-                            FontData.fallback_emoji_font().expect("emoji"),
-                        ])
-                        .with_backends(Backends::from_comma_list("vulkan"))
-                        .with_width_and_height(size.width, size.height)
-                        .build_with_target(self.window.as_ref().unwrap().clone()),
-                )
-                .unwrap(),
+                Builder::new()
+                    .with_fonts([
+                        FontData.fallback_font().expect("fallback"),
+                        FontData.fallback_emoji_font().expect("emoji"),
+                    ])
+                    .with_backends(Backends::from_comma_list("vulkan"))
+                    .with_width_and_height(size.width, size.height)
+                    .build_with_target_blocking(self.window.as_ref().unwrap().clone())
+                    .unwrap(),
             )
             .unwrap(),
         );
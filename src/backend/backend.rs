@@ -1,41 +1,58 @@
-use crate::backend::builder::{build_img_bindings, build_wgpu_state};
+use crate::backend::builder::{build_img_bindings, build_rotation_blit, build_wgpu_state};
 use crate::backend::plan_cache::PlanCache;
+use crate::backend::shape_cache::{ShapeCache, Shaped, ShapingStats};
 use crate::backend::surface::RenderSurface;
 use crate::backend::{
-    ImageInfo, ImgVertexMember, NULL_CELL, RenderInfo, Rendered, TextBgVertexMember,
+    ImageInfo, ImgVertexMember, NULL_CELL, RenderInfo, RenderStats, Rendered, TextBgVertexMember,
     TextVertexMember, TuiSurface, WgpuAtlas, WgpuBase, WgpuImage, WgpuImages, WgpuPipeline,
     WgpuVertices,
 };
 use crate::colors::{ColorTable, Rgb};
 use crate::cursor::{Blinking, CursorStyle};
-use crate::font::rasterize::rasterize_glyph;
-use crate::font::{Font, Fonts};
+use crate::font::box_drawing::{BOX_DRAWING_FONT_ID, is_box_char, rasterize_box_char};
+use crate::font::rasterize::{GlyphRaster, rasterize_glyph};
+use crate::font::{Font, FontWarning, Fonts, RasterSettings};
 use crate::image::ImageHandle;
 use crate::image::{ImageCell, ImageFrame};
 use crate::postprocessor::{PostProcessor, PostProcessorBuilder};
-use crate::text_atlas::Key;
-use crate::util::clip_uv;
-use crate::{CellBox, Error};
+use crate::text_atlas::{AtlasStats, CacheRect, GlyphKey};
+use crate::util::{clip_uv, unpremultiply};
+use crate::{
+    BaseDirection, CellBox, ControlCharDisplay, Error, Inset, LineScale, ModifierRemap, Rotation,
+};
 use bitvec::slice::BitSlice;
+use bitvec::vec::BitVec;
+use log::{debug, warn};
 use ratatui_core::backend::{Backend, ClearType, WindowSize};
-use ratatui_core::buffer::Cell;
-use ratatui_core::style::Modifier;
+use ratatui_core::buffer::{Cell, CellDiffOption, CellWidth};
+use ratatui_core::style::{Modifier, Style};
+use ratatui_core::text::Span;
 use rustybuzz::ttf_parser::GlyphId;
-use rustybuzz::{GlyphBuffer, UnicodeBuffer, shape_with_plan};
+use rustybuzz::{UnicodeBuffer, shape_with_plan};
 use std::mem;
 use std::num::NonZeroU64;
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
-use unicode_bidi::ParagraphBidiInfo;
+use std::time::Instant;
+use unicode_bidi::{Level, ParagraphBidiInfo};
 use unicode_properties::{
     GeneralCategory, GeneralCategoryGroup, UnicodeEmoji, UnicodeGeneralCategory,
 };
 use unicode_width::UnicodeWidthChar;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{
-    Buffer, BufferUsages, BufferView, CommandEncoderDescriptor, Device, Extent3d, IndexFormat,
-    LoadOp, Operations, Origin3d, PollType, Queue, RenderPass, RenderPassColorAttachment,
-    RenderPassDescriptor, StoreOp, TextureAspect, TextureDescriptor, TextureDimension,
-    TextureFormat, TextureUsages, TextureViewDescriptor,
+    AdapterInfo, Buffer, BufferDescriptor, BufferUsages, BufferView, CommandEncoder,
+    CommandEncoderDescriptor, Device, Extent3d, IndexFormat, LoadOp, Operations, Origin3d,
+    PollType, Queue, RenderPass, RenderPassColorAttachment, RenderPassDescriptor, StoreOp,
+    SurfaceError, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    TextureView, TextureViewDescriptor,
+};
+
+// With this cell size the post-processor maps pixels of the text layer.
+const PIXEL_BOX: CellBox = CellBox {
+    width: 1,
+    height: 1,
+    ascender: 0,
 };
 
 /// A ratatui backend leveraging wgpu for rendering.
@@ -53,6 +70,13 @@ use wgpu::{
 pub struct WgpuBackend<'f, 's> {
     // active fonts
     pub(super) fonts: Fonts<'f>,
+    // font size before applying the scale factor.
+    pub(super) font_size: u32,
+    // font size in points and the dpi, if it was given that way.
+    pub(super) font_size_pt: Option<(f32, f32)>,
+    // fixed cell width before applying the scale factor.
+    pub(super) cell_width: Option<u32>,
+    pub(super) scale_factor: f32,
 
     // ratatui state
     pub(super) tui_surface: TuiSurface,
@@ -62,6 +86,7 @@ pub struct WgpuBackend<'f, 's> {
 
     // temporaries for shaping
     pub(super) tmp_plan_cache: PlanCache,
+    pub(super) tmp_shape_cache: ShapeCache,
     pub(super) tmp_rowbuf: String,
     pub(super) tmp_rowbuf_to_cell: Vec<u16>,
     pub(super) tmp_buffer: UnicodeBuffer,
@@ -73,6 +98,13 @@ pub struct WgpuBackend<'f, 's> {
     pub(super) wgpu_images: WgpuImages,
     pub(super) wgpu_post_process: Box<dyn PostProcessor + 'static>,
     pub(super) wgpu_pipeline: WgpuPipeline,
+
+    // result of the last render.
+    pub(super) last_render_error: Option<Error>,
+    // the last render presented a frame.
+    pub(super) last_presented: bool,
+    // counters without the glyphs, these are in wgpu_atlas.
+    pub(super) render_stats: RenderStats,
 }
 
 impl<'s> Backend for WgpuBackend<'_, 's> {
@@ -94,12 +126,18 @@ impl<'s> Backend for WgpuBackend<'_, 's> {
     }
 
     fn hide_cursor(&mut self) -> std::io::Result<()> {
-        self.tui_surface.cursor_visible = false;
+        if self.tui_surface.cursor_visible {
+            self.tui_surface.cursor_visible = false;
+            self.mark_cursor_dirty();
+        }
         Ok(())
     }
 
     fn show_cursor(&mut self) -> std::io::Result<()> {
-        self.tui_surface.cursor_visible = true;
+        if !self.tui_surface.cursor_visible {
+            self.tui_surface.cursor_visible = true;
+            self.mark_cursor_dirty();
+        }
         Ok(())
     }
 
@@ -118,35 +156,28 @@ impl<'s> Backend for WgpuBackend<'_, 's> {
         let pos = position.into();
 
         // old cursor
-        self.tui_surface
-            .dirty_rows
-            .set(self.tui_surface.cursor.1 as usize, true);
-        self.tui_surface.dirty_cells.set(
-            self.tui_surface.cursor.1 as usize * bounds.width as usize
-                + self.tui_surface.cursor.0 as usize,
-            true,
-        );
+        self.mark_cursor_dirty();
 
-        self.tui_surface.cursor = (pos.x.min(bounds.width - 1), pos.y.min(bounds.height - 1));
-        self.tui_surface
-            .dirty_rows
-            .set(self.tui_surface.cursor.1 as usize, true);
-        self.tui_surface.dirty_cells.set(
-            self.tui_surface.cursor.1 as usize * bounds.width as usize
-                + self.tui_surface.cursor.0 as usize,
-            true,
-        );
+        let (x, y) = (pos.x.min(bounds.width - 1), pos.y.min(bounds.height - 1));
+        self.tui_surface.cursor = (snap_to_lead_cell(&self.tui_surface, bounds, x, y), y);
+        self.mark_cursor_dirty();
 
         Ok(())
     }
 
     fn clear(&mut self) -> std::io::Result<()> {
-        self.tui_surface.cells.clear();
-        self.tui_surface.cell_font.clear();
-        self.tui_surface.dirty_rows.clear();
-        self.rendered.clear();
-        self.tui_surface.fast_blinking.clear();
-        self.tui_surface.slow_blinking.clear();
+        let bounds = self.size()?;
+        if self.tui_surface.cells.len() == bounds.width as usize * bounds.height as usize {
+            // same size, keep the allocations.
+            clear_cells(&mut self.tui_surface, bounds.width);
+        } else {
+            self.tui_surface.cells.clear();
+            self.tui_surface.cell_font.clear();
+            self.tui_surface.dirty_rows.clear();
+            self.rendered.clear();
+            self.tui_surface.fast_blinking.clear();
+            self.tui_surface.slow_blinking.clear();
+        }
         self.tui_surface.cursor = (0, 0);
 
         Ok(())
@@ -172,9 +203,9 @@ impl<'s> Backend for WgpuBackend<'_, 's> {
                 Ok(())
             }
             ClearType::BeforeCursor => {
-                self.tui_surface.cells[..idx].fill(Cell::EMPTY);
+                self.tui_surface.cells[..idx].fill(self.tui_surface.empty_cell.clone());
                 self.tui_surface.cell_font[..idx].fill(0);
-                self.tui_surface.cell_remap[..idx].fill(0);
+                reset_cell_remap(&mut self.tui_surface, 0..idx, bounds.width);
                 self.tui_surface.dirty_rows[..=self.tui_surface.cursor.1 as usize].fill(true);
                 self.tui_surface.dirty_cells[..idx].fill(true);
                 self.tui_surface.fast_blinking[..idx].fill(false);
@@ -183,9 +214,13 @@ impl<'s> Backend for WgpuBackend<'_, 's> {
             }
             ClearType::CurrentLine => {
                 self.tui_surface.cells[line_start..line_start + bounds.width as usize]
-                    .fill(Cell::EMPTY);
+                    .fill(self.tui_surface.empty_cell.clone());
                 self.tui_surface.cell_font[line_start..line_start + bounds.width as usize].fill(0);
-                self.tui_surface.cell_remap[line_start..line_start + bounds.width as usize].fill(0);
+                reset_cell_remap(
+                    &mut self.tui_surface,
+                    line_start..line_start + bounds.width as usize,
+                    bounds.width,
+                );
                 self.tui_surface
                     .dirty_rows
                     .set(self.tui_surface.cursor.1 as usize, true);
@@ -199,9 +234,9 @@ impl<'s> Backend for WgpuBackend<'_, 's> {
             }
             ClearType::UntilNewLine => {
                 let remain = (bounds.width - self.tui_surface.cursor.0) as usize;
-                self.tui_surface.cells[idx..idx + remain].fill(Cell::EMPTY);
+                self.tui_surface.cells[idx..idx + remain].fill(self.tui_surface.empty_cell.clone());
                 self.tui_surface.cell_font[idx..idx + remain].fill(0);
-                self.tui_surface.cell_remap[idx..idx + remain].fill(0);
+                reset_cell_remap(&mut self.tui_surface, idx..idx + remain, bounds.width);
                 self.tui_surface
                     .dirty_rows
                     .set(self.tui_surface.cursor.1 as usize, true);
@@ -215,74 +250,158 @@ impl<'s> Backend for WgpuBackend<'_, 's> {
 
     fn size(&self) -> std::io::Result<ratatui_core::layout::Size> {
         let font_box = self.fonts.cell_box();
-        let width = self.wgpu_base.surface_config.width;
-        let height = self.wgpu_base.surface_config.height;
+        let width = self.wgpu_base.post_config().width;
+        let height = self.wgpu_base.post_config().height;
+        let (cols, rows) = self.tui_surface.inset.grid(width, height, font_box);
 
         Ok(ratatui_core::layout::Size {
-            width: (width / font_box.width) as u16,
-            height: (height / font_box.height) as u16,
+            width: cols as u16,
+            height: rows as u16,
         })
     }
 
     fn window_size(&mut self) -> std::io::Result<WindowSize> {
         let font_box = self.fonts.cell_box();
-        let width = self.wgpu_base.surface_config.width;
-        let height = self.wgpu_base.surface_config.height;
+        let inset = self.tui_surface.inset;
+        let width = self.wgpu_base.post_config().width;
+        let height = self.wgpu_base.post_config().height;
+        let (cols, rows) = inset.grid(width, height, font_box);
 
+        // the inset is not part of the terminal.
         Ok(WindowSize {
             columns_rows: ratatui_core::layout::Size {
-                width: (width / font_box.width) as u16,
-                height: (height / font_box.height) as u16,
+                width: cols as u16,
+                height: rows as u16,
             },
             pixels: ratatui_core::layout::Size {
-                width: width as u16,
-                height: height as u16,
+                width: width.saturating_sub(inset.left + inset.right) as u16,
+                height: height.saturating_sub(inset.top + inset.bottom) as u16,
             },
         })
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
+        let start = Instant::now();
         let bounds = self.size()?;
 
+        self.update_text_layer(bounds);
+        self.tui_surface.rebuilt = false;
+
+        let result = render(
+            self.window_size().expect("window_size"),
+            self.fonts.cell_box(),
+            self.tui_surface.inset,
+            self.tui_surface
+                .margin_color
+                .unwrap_or(self.tui_surface.reset_bg),
+            self.tui_surface.window_opacity,
+            &mut self.wgpu_base,
+            &self.wgpu_images,
+            &self.wgpu_pipeline,
+            self.wgpu_post_process.as_mut(),
+            &mut self.wgpu_vertices,
+            &mut self.render_stats,
+        );
+        self.last_presented = matches!(result, Ok(true));
+        self.last_render_error = result.err();
+        if self.last_render_error.is_some() {
+            // the text layer missed this update.
+            mark_all_dirty(&mut self.tui_surface);
+        }
+
+        self.wgpu_vertices.clear();
+        drop_images(&mut self.tui_surface, &mut self.wgpu_images);
+
+        self.render_stats.last_flush_micros = start.elapsed().as_micros() as u64;
+
+        Ok(())
+    }
+}
+
+impl<'f, 's> WgpuBackend<'f, 's> {
+    // shape the changed cells and collect the vertices.
+    fn update_text_layer(&mut self, bounds: ratatui_core::layout::Size) {
         flush_tui(
             bounds,
-            &self.fonts,
             &mut self.tui_surface,
             &mut self.rendered,
-            &mut self.wgpu_atlas,
-            &self.wgpu_base.queue,
-            &mut self.tmp_plan_cache,
             &mut self.tmp_rowbuf,
             &mut self.tmp_rowbuf_to_cell,
-            &mut self.tmp_buffer,
+            &mut Shaper {
+                fonts: &self.fonts,
+                plan_cache: &mut self.tmp_plan_cache,
+                shape_cache: &mut self.tmp_shape_cache,
+                buffer: &mut self.tmp_buffer,
+                wgpu_atlas: &mut self.wgpu_atlas,
+                queue: &self.wgpu_base.queue,
+            },
         );
 
+        if self.wgpu_base.text_layer_load != LoadOp::Load {
+            mark_all_repaint(&mut self.tui_surface);
+        }
+        self.render_stats.dirty_rows += self.tui_surface.dirty_rows.count_ones() as u64;
+
         append_dirty_rows(
+            bounds,
+            self.fonts.cell_box(),
             &mut self.tui_surface,
             self.wgpu_post_process.as_ref(),
             &self.rendered,
             &mut self.wgpu_vertices,
         );
+    }
+
+    /// Draw the terminal into a render target of the host application.
+    ///
+    /// This updates the text layer with the changes since the last
+    /// flush and records the text passes and the post-processor into
+    /// `encoder`. Nothing is submitted or presented, and the surface
+    /// of the backend is not touched. Submit the encoder to
+    /// [queue](Self::queue) yourself, the uploads for this frame are
+    /// already written to it.
+    ///
+    /// `target` must have the [surface_format](Self::surface_format)
+    /// and the size the backend was built or resized with.
+    ///
+    /// __Info__
+    ///
+    /// [Terminal::draw](ratatui_core::terminal::Terminal::draw) still
+    /// flushes to the surface of the backend. Build the backend with
+    /// [build_headless_texture_only](crate::Builder::build_headless_texture_only)
+    /// and the device of the host, and call this once per host frame.
+    /// Without changes this only runs the post-processor again.
+    pub fn render_into(&mut self, encoder: &mut CommandEncoder, target: &TextureView) {
+        let bounds = self.size().expect("size");
+        self.update_text_layer(bounds);
+        self.tui_surface.rebuilt = false;
 
-        render(
+        render_text(
+            encoder,
             self.window_size().expect("window_size"),
             self.fonts.cell_box(),
-            self.tui_surface.reset_bg,
+            self.tui_surface.inset,
             &self.wgpu_base,
             &self.wgpu_images,
             &self.wgpu_pipeline,
+            &mut self.wgpu_vertices,
+            &mut self.render_stats,
+        );
+        post_process_into(
+            encoder,
+            target,
+            self.tui_surface
+                .margin_color
+                .unwrap_or(self.tui_surface.reset_bg),
+            self.tui_surface.window_opacity,
+            &self.wgpu_base,
             self.wgpu_post_process.as_mut(),
-            &self.wgpu_vertices,
         );
 
         self.wgpu_vertices.clear();
         drop_images(&mut self.tui_surface, &mut self.wgpu_images);
-
-        Ok(())
     }
-}
 
-impl<'f, 's> WgpuBackend<'f, 's> {
     /// Returns the ImageFrame.
     ///
     /// This will be used by the application to queue images for rendering.
@@ -299,11 +418,103 @@ impl<'f, 's> WgpuBackend<'f, 's> {
 
     /// Background color or Color::Reset.
     ///
-    /// This will also fill the unclaimed area at the right/bottom.
+    /// This will also fill the unclaimed area at the right/bottom,
+    /// unless a margin color is set.
     pub fn set_bg_color(&mut self, color: ratatui_core::style::Color) {
         self.tui_surface.reset_bg = self.tui_surface.colors.c2c(color, [0; 3]);
     }
 
+    /// Color for the unclaimed area at the right/bottom.
+    ///
+    /// Color::Reset uses the background color, which is the default.
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_margin_color(&mut self, color: ratatui_core::style::Color) {
+        self.invalidate();
+        self.tui_surface.margin_color = match color {
+            ratatui_core::style::Color::Reset => None,
+            color => Some(self.tui_surface.colors.c2c(color, [0; 3])),
+        };
+    }
+
+    /// Margins in px around the cell grid.
+    ///
+    /// The inset is cut from the surface, [size](Backend::size) shrinks
+    /// accordingly. It's filled with the margin color, see
+    /// [set_margin_color](Self::set_margin_color).
+    pub fn set_inset(&mut self, inset: Inset) {
+        if inset == self.tui_surface.inset {
+            return;
+        }
+        self.tui_surface.inset = inset;
+
+        rebuild_surface(
+            self.fonts.cell_box(),
+            &mut self.tui_surface,
+            &mut self.rendered,
+            &mut self.wgpu_base,
+            &mut self.wgpu_atlas,
+            self.wgpu_post_process.as_mut(),
+        );
+    }
+
+    /// Margins in px around the cell grid.
+    pub fn inset(&self) -> Inset {
+        self.tui_surface.inset
+    }
+
+    /// Rotate the whole terminal clockwise.
+    ///
+    /// The cell grid is laid out for the rotated orientation, for
+    /// 90 and 270 degrees [size](Backend::size) swaps columns and rows.
+    /// [pos_to_cell](Self::pos_to_cell) maps the positions on the
+    /// surface accordingly. The inset is applied to the rotated
+    /// terminal.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        if rotation == self.wgpu_base.rotation {
+            return;
+        }
+        self.wgpu_base.rotation = rotation;
+
+        rebuild_surface(
+            self.fonts.cell_box(),
+            &mut self.tui_surface,
+            &mut self.rendered,
+            &mut self.wgpu_base,
+            &mut self.wgpu_atlas,
+            self.wgpu_post_process.as_mut(),
+        );
+    }
+
+    /// Rotation of the terminal.
+    pub fn rotation(&self) -> Rotation {
+        self.wgpu_base.rotation
+    }
+
+    /// Opacity of the whole window, from 0.0 to 1.0.
+    ///
+    /// The post-processor multiplies the alpha of its output with this
+    /// value, including the margin. This only shows if the surface was
+    /// built with an alpha-capable [`wgpu::CompositeAlphaMode`], see
+    /// [`Builder::with_alpha_mode`](crate::Builder::with_alpha_mode).
+    /// Some platforms and compositors ignore the alpha of the surface
+    /// altogether, there the window stays opaque.
+    ///
+    /// Custom post-processors get the opacity too, but may ignore it.
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_window_opacity(&mut self, opacity: f32) {
+        self.invalidate();
+        self.tui_surface.window_opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    /// Opacity of the whole window.
+    pub fn window_opacity(&self) -> f32 {
+        self.tui_surface.window_opacity
+    }
+
     /// Foreground color for Color::Reset.
     pub fn set_fg_color(&mut self, color: ratatui_core::style::Color) {
         self.tui_surface.reset_fg = self.tui_surface.colors.c2c(color, [255; 3]);
@@ -319,6 +530,21 @@ impl<'f, 's> WgpuBackend<'f, 's> {
         self.tui_surface.cursor_style
     }
 
+    /// Set the image for [CursorStyle::Image].
+    ///
+    /// The image must be added with [add_image](Self::add_image)
+    /// first. The backend holds a clone of the handle, which keeps
+    /// the image alive until the cursor image is replaced.
+    pub fn set_cursor_image(&mut self, image: Option<ImageHandle>) {
+        self.tui_surface.cursor_image = image;
+        self.mark_cursor_dirty();
+    }
+
+    /// Current image for [CursorStyle::Image].
+    pub fn cursor_image(&self) -> Option<&ImageHandle> {
+        self.tui_surface.cursor_image.as_ref()
+    }
+
     /// Set the cursor color.
     pub fn set_cursor_color(&mut self, color: ratatui_core::style::Color) {
         self.tui_surface.cursor_color = color;
@@ -329,6 +555,41 @@ impl<'f, 's> WgpuBackend<'f, 's> {
         self.tui_surface.cursor_color
     }
 
+    /// Size of a cell in px.
+    pub fn cell_box(&self) -> CellBox {
+        self.fonts.cell_box()
+    }
+
+    /// The adapter that was selected when the backend was built.
+    ///
+    /// This tells which graphics API (Vulkan, Metal, DX12, GL)
+    /// and which device is used.
+    pub fn adapter_info(&self) -> &AdapterInfo {
+        &self.wgpu_base.adapter_info
+    }
+
+    /// The device used for rendering.
+    ///
+    /// Use it to create buffers, textures and pipelines for your own
+    /// render passes.
+    ///
+    /// __Info__
+    ///
+    /// The backend submits its own work during [flush](Self::flush).
+    /// Ordering your submissions relative to that is up to you.
+    pub fn device(&self) -> &Device {
+        &self.wgpu_base.device
+    }
+
+    /// The queue used for rendering.
+    ///
+    /// Work submitted before [flush](Self::flush) runs before the
+    /// frame of the terminal, work submitted after it runs after.
+    /// Interleaving submissions is the responsibility of the caller.
+    pub fn queue(&self) -> &Queue {
+        &self.wgpu_base.queue
+    }
+
     /// Map a physical cursor position to a col/row position.
     pub fn pos_to_cell(&self, pos: (i32, i32)) -> (u16, u16) {
         let font_box = self.fonts.cell_box();
@@ -337,9 +598,19 @@ impl<'f, 's> WgpuBackend<'f, 's> {
             return (0, 0);
         }
 
-        let (cell_x, cell_y) =
-            self.wgpu_post_process
-                .map_to_cell(pos.0, pos.1, self.fonts.cell_box());
+        let (x, y) = self.wgpu_base.rotation.unrotate(
+            pos.0,
+            pos.1,
+            self.wgpu_base.surface_config.width,
+            self.wgpu_base.surface_config.height,
+        );
+        // the post-processor maps the whole text layer, the inset included.
+        let (x, y) = self.wgpu_post_process.map_to_cell(x, y, PIXEL_BOX);
+        let inset = self.tui_surface.inset;
+        let cell_x = (x as i32 - inset.left as i32).max(0) as u32 / font_box.width;
+        let cell_y = (y as i32 - inset.top as i32).max(0) as u32 / font_box.height;
+        let (cell_x, cell_y) = (cell_x as u16, cell_y as u16);
+        let cell_x = cell_x / self.line_scale(cell_y).scale_x() as u16;
 
         let bounds = self.size().unwrap();
         let offset = (cell_y * bounds.width) as usize;
@@ -356,6 +627,80 @@ impl<'f, 's> WgpuBackend<'f, 's> {
         (cell_x, cell_y)
     }
 
+    /// Map a col/row position to the physical position of the cell.
+    ///
+    /// This is the inverse of [pos_to_cell](Self::pos_to_cell) and
+    /// returns the top-left corner of the cell, as it ends up on the
+    /// surface after bidi reordering, line scaling, the post-processor
+    /// and the rotation. Use it to anchor native popups like menus or
+    /// the candidate window of an input method to a cell.
+    ///
+    /// With a rotation the corner is the one that is top-left on the
+    /// surface.
+    pub fn cell_to_pos(&self, cell: (u16, u16)) -> (i32, i32) {
+        let (col, row) = cell;
+
+        let bounds = self.size().unwrap();
+        let offset = row as usize * bounds.width as usize;
+        let col = if col < bounds.width {
+            self.tui_surface
+                .cell_remap
+                .get(offset + col as usize)
+                .copied()
+                .unwrap_or(col)
+        } else {
+            col
+        };
+        let scale_x = self.line_scale(row).scale_x() as u32;
+
+        let font_box = self.fonts.cell_box();
+        let inset = self.tui_surface.inset;
+        // the post-processor maps the whole text layer, the inset included.
+        let corner = |col: u32, row: u32| {
+            self.wgpu_post_process.map_from_cell(
+                inset.left + col * font_box.width,
+                inset.top + row * font_box.height,
+            )
+        };
+        let (x0, y0) = corner(col as u32 * scale_x, row as u32);
+        let (x1, y1) = corner((col as u32 + 1) * scale_x, row as u32 + 1);
+
+        // the top-left pixel of the cell after the rotation.
+        let rotation = self.wgpu_base.rotation;
+        let (width, height) = (
+            self.wgpu_base.surface_config.width,
+            self.wgpu_base.surface_config.height,
+        );
+        let (ax, ay) = rotation.rotate(x0, y0, width, height);
+        let (bx, by) = rotation.rotate(x1 - 1, y1 - 1, width, height);
+        (ax.min(bx), ay.min(by))
+    }
+
+    /// The mapping of cells to visual positions for one row.
+    ///
+    /// The index is the cell-index as given to draw, the value is the
+    /// visual cell-index where it is rendered. This differs only if bidi
+    /// reordering took place. Runs of right-to-left text are mirrored.
+    ///
+    /// Returns None if the row is out of range or nothing has
+    /// been rendered yet.
+    pub fn visual_remap(&self, row: u16) -> Option<Vec<u16>> {
+        let bounds = self.size().unwrap();
+        if row >= bounds.height {
+            return None;
+        }
+        let offset = row as usize * bounds.width as usize;
+        self.tui_surface
+            .cell_remap
+            .get(offset..offset + bounds.width as usize)
+            .map(|v| v.to_vec())
+    }
+
+    /// The texture format of the render surface.
+    pub fn surface_format(&self) -> TextureFormat {
+        self.wgpu_base.surface_config.format
+    }
+
     /// Get the [`PostProcessor`] associated with this backend.
     pub fn post_processor(&self) -> &dyn PostProcessor {
         self.wgpu_post_process.as_ref()
@@ -372,7 +717,7 @@ impl<'f, 's> WgpuBackend<'f, 's> {
         let post_process = builder.compile(
             &self.wgpu_base.device,
             &self.wgpu_base.text_dest_view,
-            &self.wgpu_base.surface_config,
+            self.wgpu_base.post_config(),
         );
         self.wgpu_post_process = Box::new(post_process);
     }
@@ -380,7 +725,22 @@ impl<'f, 's> WgpuBackend<'f, 's> {
     /// Resize the rendering surface.
     ///
     /// This must be called to keep the backend in sync with your window size.
+    ///
+    /// __Info__
+    ///
+    /// This only changes the pixel dimensions of the surface.
+    /// The font size is not touched, use [set_scale_factor](Self::set_scale_factor)
+    /// for that.
+    ///
+    /// A backend created with [build_with_texture](crate::Builder::build_with_texture)
+    /// can't resize the texture of the caller. This does nothing
+    /// there, use [set_target](Self::set_target) with a new texture.
     pub fn resize(&mut self, width: u32, height: u32) {
+        if matches!(self.wgpu_base.surface, RenderSurface::Texture(_)) {
+            warn!("resize ignored, the target texture is owned by the caller. use set_target.");
+            return;
+        }
+
         let limits = self.wgpu_base.device.limits();
         let width = width.min(limits.max_texture_dimension_2d);
         let height = height.min(limits.max_texture_dimension_2d);
@@ -406,6 +766,66 @@ impl<'f, 's> WgpuBackend<'f, 's> {
         );
     }
 
+    /// Resize the rendering surface to an exact number of cells.
+    ///
+    /// The pixel size is calculated from the current cell_box and the
+    /// inset, so there will be no margin and [size](Backend::size)
+    /// returns exactly `(cols, rows)` afterward.
+    ///
+    /// __Info__
+    ///
+    /// The size is still limited by the maximum texture size of the device.
+    /// A change of the font-size will not change the pixel-size, the
+    /// number of cells will change instead.
+    pub fn resize_cells(&mut self, cols: u16, rows: u16) {
+        let cell_box = self.fonts.cell_box();
+        let inset = self.tui_surface.inset;
+        // rotating twice gives back the size of the surface.
+        let (width, height) = self.wgpu_base.rotation.rotated_size(
+            inset.left + cols as u32 * cell_box.width + inset.right,
+            inset.top + rows as u32 * cell_box.height + inset.bottom,
+        );
+        self.resize(width, height);
+    }
+
+    /// Switch to a new target texture.
+    ///
+    /// This replaces [resize](Self::resize) for a backend created with
+    /// [build_with_texture](crate::Builder::build_with_texture). The
+    /// same requirements for the texture apply.
+    ///
+    /// __Panic__
+    ///
+    /// Panics if the backend doesn't render to a texture.
+    pub fn set_target(&mut self, view: TextureView, width: u32, height: u32) {
+        let RenderSurface::Texture(target) = &mut self.wgpu_base.surface else {
+            panic!("can only be called when initialized with a texture.");
+        };
+        target.view = view;
+        target.width = width;
+        target.height = height;
+
+        if width == self.wgpu_base.surface_config.width
+            && height == self.wgpu_base.surface_config.height
+        {
+            // the new texture has none of the old content.
+            mark_all_dirty(&mut self.tui_surface);
+            return;
+        }
+
+        self.wgpu_base.surface_config.width = width;
+        self.wgpu_base.surface_config.height = height;
+
+        rebuild_surface(
+            self.fonts.cell_box(),
+            &mut self.tui_surface,
+            &mut self.rendered,
+            &mut self.wgpu_base,
+            &mut self.wgpu_atlas,
+            self.wgpu_post_process.as_mut(),
+        );
+    }
+
     /// Get the text currently displayed on the screen.
     pub fn get_text(&self) -> String {
         let bounds = self.size().unwrap();
@@ -422,112 +842,1051 @@ impl<'f, 's> WgpuBackend<'f, 's> {
         )
     }
 
-    /// Update the color-table used for rendering. This will cause a full
-    /// repaint of the screen the next time [`WgpuBackend::flush`] is
-    /// called.
-    pub fn update_color_table(&mut self, new_colors: ColorTable) {
-        self.tui_surface.dirty_rows.clear();
-        self.tui_surface.dirty_cells.clear();
-        self.tui_surface.colors = new_colors;
+    /// Get the text currently displayed on the screen in visual order.
+    ///
+    /// Unlike [get_text](Self::get_text) the cells of each row are
+    /// ordered as they are shown after bidi reordering, runs of
+    /// right-to-left text come out mirrored. This matches what is
+    /// visible, e.g. for copying to the clipboard.
+    ///
+    /// Rows that have not been rendered yet are in logical order.
+    pub fn get_visual_text(&self) -> String {
+        let bounds = self.size().unwrap();
+        let mut dest = String::with_capacity((bounds.width + 1) as usize * bounds.height as usize);
+        if bounds.width == 0 {
+            return dest;
+        }
+
+        let mut visual = Vec::with_capacity(bounds.width as usize);
+        for (row_idx, row) in self
+            .tui_surface
+            .cells
+            .chunks(bounds.width as usize)
+            .enumerate()
+        {
+            let offset = row_idx * bounds.width as usize;
+            let remap = self
+                .tui_surface
+                .cell_remap
+                .get(offset..offset + bounds.width as usize);
+
+            visual.clear();
+            visual.extend(
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, cell)| **cell != NULL_CELL)
+                    .map(|(idx, cell)| {
+                        let visual_idx = remap.map_or(idx as u16, |remap| remap[idx]);
+                        (visual_idx, cell.symbol())
+                    }),
+            );
+            // stable, cells at the same position keep their order.
+            visual.sort_by_key(|(visual_idx, _)| *visual_idx);
+
+            for (_, symbol) in &visual {
+                dest.push_str(symbol);
+            }
+            dest.push('\n');
+        }
+        dest
     }
 
-    /// Update the fonts used for rendering. This will cause a full repaint of
-    /// the screen the next time [`WgpuBackend::flush`] is called. A call to
-    /// [ratatui_core::terminal::Terminal::draw] will do this.
+    /// Get the text currently displayed on the screen as styled runs.
     ///
-    /// This will also change the number of cells if the font has a different
-    /// aspect ratio for its glyphs.
-    pub fn update_fonts(&mut self, new_fonts: Fonts<'f>) {
-        self.fonts = new_fonts;
+    /// Returns one entry per row, each with runs of consecutive cells
+    /// that share the same style. The cells following a wide character
+    /// are skipped.
+    ///
+    /// __Info__
+    ///
+    /// This reflects the logical order of the cells as given to draw,
+    /// not the visual order after bidi reordering. Use
+    /// [visual_remap](Self::visual_remap) if you need the latter.
+    pub fn get_styled_text(&self) -> Vec<Vec<(String, Style)>> {
+        let bounds = self.size().unwrap();
+        if bounds.width == 0 {
+            return Vec::new();
+        }
 
-        rebuild_surface(
-            self.fonts.cell_box(),
-            &mut self.tui_surface,
-            &mut self.rendered,
-            &mut self.wgpu_base,
-            &mut self.wgpu_atlas,
-            self.wgpu_post_process.as_mut(),
-        );
+        self.tui_surface
+            .cells
+            .chunks(bounds.width as usize)
+            .map(|row| {
+                let mut runs: Vec<(String, Style)> = Vec::new();
+                for cell in row {
+                    if *cell == NULL_CELL {
+                        continue;
+                    }
+                    let style = cell.style();
+                    match runs.last_mut() {
+                        Some((text, last)) if *last == style => text.push_str(cell.symbol()),
+                        _ => runs.push((cell.symbol().to_string(), style)),
+                    }
+                }
+                runs
+            })
+            .collect()
     }
 
-    /// Replace the fonts used for rendering. This will keep the fallback fonts.
-    /// If you want to replace those too, use [update_fonts].
+    /// Get the text currently displayed on the screen.
+    /// Trailing whitespace is removed from each line.
+    pub fn get_text_trimmed(&self) -> String {
+        let mut dest = String::new();
+        for line in self.get_text().lines() {
+            dest.push_str(line.trim_end());
+            dest.push('\n');
+        }
+        dest
+    }
+
+    /// Get the text currently displayed in the given area.
     ///
-    /// This will cause a full repaint of the screen the next
-    /// time [`WgpuBackend::flush`] is called.
-    /// A call to [ratatui_core::terminal::Terminal::draw] will do this.
-    pub fn update_font_vec(&mut self, new_fonts: Vec<Font<'f>>) {
-        self.fonts.clear_fonts();
-        self.fonts.add_fonts(new_fonts);
+    /// The area is clipped to the screen. Wide characters that
+    /// are only partially inside the area are replaced with spaces, so
+    /// each cell of the area still maps to one column of the result.
+    pub fn get_text_region(&self, area: ratatui_core::layout::Rect) -> String {
+        let bounds = self.size().unwrap();
+        let area = area.intersection(ratatui_core::layout::Rect::new(
+            0,
+            0,
+            bounds.width,
+            bounds.height,
+        ));
 
-        rebuild_surface(
-            self.fonts.cell_box(),
-            &mut self.tui_surface,
-            &mut self.rendered,
-            &mut self.wgpu_base,
-            &mut self.wgpu_atlas,
-            self.wgpu_post_process.as_mut(),
-        );
+        let mut dest = String::with_capacity((area.width + 1) as usize * area.height as usize);
+        for y in area.top()..area.bottom() {
+            let offset = y as usize * bounds.width as usize;
+            let Some(row) = self
+                .tui_surface
+                .cells
+                .get(offset..offset + bounds.width as usize)
+            else {
+                break;
+            };
+
+            // a glyph started inside the area.
+            let mut lead = false;
+            for x in area.left()..area.right() {
+                let cell = &row[x as usize];
+                if *cell == NULL_CELL {
+                    // continuation of a wide glyph.
+                    if !lead {
+                        dest.push(' ');
+                    }
+                } else if x as usize + symbol_width(cell.symbol()) > area.right() as usize {
+                    dest.push(' ');
+                } else {
+                    dest.push_str(cell.symbol());
+                    lead = true;
+                }
+            }
+            dest.push('\n');
+        }
+        dest
     }
 
-    /// Update the font-size used for rendering.
+    /// Measure the size of a string in cells, as it would be rendered.
     ///
-    /// This will cause a full repaint of
-    /// the screen the next time [`WgpuBackend::flush`] is called.
+    /// Returns (columns, rows) with one row for each line. This uses the
+    /// same width rules as rendering, which differ from plain unicode-width
+    /// for some clusters. Wide glyphs take 2 columns, everything else 1.
+    /// Control characters are dropped like ratatui does.
+    ///
+    /// The style doesn't change the result at the moment, all fonts
+    /// share the same cell grid.
+    pub fn measure(&self, line: &str, style: Style) -> (u16, u16) {
+        let mut cols = 0;
+        let mut rows = 0;
+        for text in line.lines() {
+            let span = Span::styled(text, style);
+            let width = span
+                .styled_graphemes(Style::default())
+                .map(|grapheme| symbol_width(grapheme.symbol).clamp(1, 2))
+                .sum::<usize>();
+            cols = cols.max(width);
+            rows += 1;
+        }
+        (cols.min(u16::MAX as usize) as u16, rows)
+    }
+
+    /// Rasterize the glyphs for the text ahead of time.
+    ///
+    /// Shapes the text once for each of the given modifiers and
+    /// uploads the glyphs to the atlas. Nothing is rendered. This moves
+    /// the cost of rasterizing many glyphs from the first frames
+    /// to startup. Use [Modifier::empty] for plain text.
+    ///
+    /// __Info__
+    ///
+    /// This uses the current fonts. [update_fonts](WgpuBackend::update_fonts),
+    /// [update_font_size](WgpuBackend::update_font_size) and the like
+    /// clear the atlas, call it again afterwards.
+    pub fn prewarm(&mut self, text: &str, styles: &[Modifier]) {
+        // no remapped modifiers and no cursor.
+        let settings = ShapeSettings {
+            modifier_remap: ModifierRemap::default(),
+            cursor_visible: false,
+            ..ShapeSettings::new(&self.fonts, &self.tui_surface)
+        };
+
+        for line in text.lines() {
+            let (width, _) = self.measure(line, Style::default());
+            if width == 0 {
+                continue;
+            }
+
+            let mut dirty_cells = BitVec::new();
+            dirty_cells.resize(width as usize, true);
+            let cell_remap = (0..width).collect::<Vec<_>>();
+            let mut rendered = Vec::new();
+            rendered.resize_with(width as usize, Rendered::default);
+
+            for modifier in styles {
+                let mut buffer = ratatui_core::buffer::Buffer::empty(
+                    ratatui_core::layout::Rect::new(0, 0, width, 1),
+                );
+                buffer.set_string(0, 0, line, Style::default().add_modifier(*modifier));
+                let cell_font = buffer
+                    .content
+                    .iter()
+                    .map(|cell| self.fonts.font_for_cell(cell))
+                    .collect::<Vec<_>>();
+
+                row_text(
+                    &buffer.content,
+                    settings.control_chars,
+                    &mut self.tmp_rowbuf,
+                    &mut self.tmp_rowbuf_to_cell,
+                );
+                if self.tmp_rowbuf.is_empty() {
+                    continue;
+                }
+
+                // the same segments as a flush without bidi.
+                // the glyphs are in the atlas, the result is not needed.
+                let runs = std::iter::once(0..self.tmp_rowbuf.len()).collect::<Vec<_>>();
+                shape_segments(
+                    &ShapeRow {
+                        row_idx: 0,
+                        cells: &buffer.content,
+                        dirty_cells: &dirty_cells,
+                        cell_remap: &cell_remap,
+                        cell_font: &cell_font,
+                        line_scale: LineScale::Normal,
+                        buf_str: &self.tmp_rowbuf,
+                        buf_to_cell: &self.tmp_rowbuf_to_cell,
+                    },
+                    &settings,
+                    &[Level::ltr()],
+                    &runs,
+                    &mut rendered,
+                    &mut Shaper {
+                        fonts: &self.fonts,
+                        plan_cache: &mut self.tmp_plan_cache,
+                        shape_cache: &mut self.tmp_shape_cache,
+                        buffer: &mut self.tmp_buffer,
+                        wgpu_atlas: &mut self.wgpu_atlas,
+                        queue: &self.wgpu_base.queue,
+                    },
+                );
+                rendered.iter_mut().for_each(|v| v.clear());
+            }
+        }
+    }
+
+    /// Usage of the glyph atlas.
+    ///
+    /// When the atlas is full the least recently used glyphs are
+    /// replaced. If the visible text needs more glyphs than fit,
+    /// some of them will be missing.
+    pub fn atlas_stats(&self) -> AtlasStats {
+        self.wgpu_atlas.cached.stats()
+    }
+
+    /// How many text runs have been shaped, and how many
+    /// came from the shaping cache.
+    ///
+    /// Rows are shaped in runs that end at font changes, bidi
+    /// levels and blanks. Runs without changed cells are not
+    /// shaped at all.
+    pub fn shaping_stats(&self) -> ShapingStats {
+        self.tmp_shape_cache.stats()
+    }
+
+    /// Counters for profiling.
+    ///
+    /// Glyphs rasterized or found in the atlas, quads and images
+    /// drawn, rows repainted and the duration of the last flush.
+    pub fn render_stats(&self) -> RenderStats {
+        RenderStats {
+            glyphs_rasterized: self.wgpu_atlas.rasterized,
+            glyphs_cached_hits: self.wgpu_atlas.cache_hits,
+            ..self.render_stats
+        }
+    }
+
+    /// All glyphs currently in the atlas.
+    pub fn cached_glyphs(&self) -> impl Iterator<Item = GlyphKey> + '_ {
+        self.wgpu_atlas.cached.keys()
+    }
+
+    /// Remove a glyph from the atlas. Its space is reused for the
+    /// next new glyph. If it is still visible it will be rasterized
+    /// again when its cell is rendered.
+    ///
+    /// Returns false if the glyph was not cached.
+    pub fn evict_glyph(&mut self, key: &GlyphKey) -> bool {
+        self.wgpu_atlas.cached.evict(key)
+    }
+
+    /// Update the color-table used for rendering. This will cause a full
+    /// repaint of the screen the next time [`WgpuBackend::flush`] is
+    /// called.
+    pub fn update_color_table(&mut self, new_colors: ColorTable) {
+        self.invalidate();
+        self.tui_surface.colors = new_colors;
+    }
+
+    /// Invert all colors. This swaps fg and bg for all cells,
+    /// on top of any [Modifier::REVERSED].
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_invert_colors(&mut self, invert: bool) {
+        self.invalidate();
+        self.tui_surface.invert_colors = invert;
+    }
+
+    /// Set the base direction for bidi text.
+    ///
+    /// With [BaseDirection::Auto] each row detects its direction from
+    /// the first strong character. Rtl forces right-to-left for all rows,
+    /// which aligns rows with only neutral characters, like digits,
+    /// to the right too.
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_base_direction(&mut self, direction: BaseDirection) {
+        self.invalidate();
+        self.tui_surface.base_direction = direction;
+    }
+
+    /// Base direction for bidi text.
+    pub fn base_direction(&self) -> BaseDirection {
+        self.tui_surface.base_direction
+    }
+
+    /// Run the bidi algorithm for each row.
+    ///
+    /// When disabled each row is rendered left-to-right as is,
+    /// and the base direction is ignored. This saves some time
+    /// for apps that only show left-to-right text.
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_bidi_enabled(&mut self, bidi: bool) {
+        self.invalidate();
+        self.tui_surface.bidi = bidi;
+    }
+
+    /// The bidi algorithm is used.
+    pub fn bidi_enabled(&self) -> bool {
+        self.tui_surface.bidi
+    }
+
+    /// Draw box-drawing and block-element chars (U+2500..=U+259F)
+    /// and Braille patterns (U+2800..=U+28FF) instead of using the
+    /// glyphs of the font.
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_builtin_box_drawing(&mut self, builtin: bool) {
+        self.invalidate();
+        self.tui_surface.builtin_box_drawing = builtin;
+    }
+
+    /// Box-drawing, block-element and Braille chars are drawn by
+    /// the backend.
+    pub fn builtin_box_drawing(&self) -> bool {
+        self.tui_surface.builtin_box_drawing
+    }
+
+    /// How control chars in a cell are shown.
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_control_char_display(&mut self, display: ControlCharDisplay) {
+        self.invalidate();
+        self.tui_surface.control_chars = display;
+    }
+
+    /// How control chars in a cell are shown.
+    pub fn control_char_display(&self) -> ControlCharDisplay {
+        self.tui_surface.control_chars
+    }
+
+    /// Draw an outline around the glyphs, which keeps text legible
+    /// over busy images. The outline is width_px wide, 0 switches it
+    /// off. [Color::Reset](ratatui_core::style::Color::Reset) is the
+    /// background color.
+    ///
+    /// Color glyphs like emojis don't get an outline. The outline is
+    /// clipped at the edges of the cell.
+    ///
+    /// This clears the glyph cache and causes a full repaint of the
+    /// screen the next time [`WgpuBackend::flush`] is called.
+    pub fn set_text_outline(&mut self, width_px: f32, color: ratatui_core::style::Color) {
+        self.tui_surface.text_outline = (width_px.max(0.0), color);
+        self.wgpu_atlas.cached.clear();
+        self.invalidate();
+    }
+
+    /// Width and color of the outline around the glyphs.
+    pub fn text_outline(&self) -> (f32, ratatui_core::style::Color) {
+        self.tui_surface.text_outline
+    }
+
+    /// Fill for cells that were never written or are cleared.
+    ///
+    /// The symbol must be one column wide, otherwise the cell is
+    /// ignored. Cells that are already blank keep the old fill until
+    /// they are cleared, e.g. with [Terminal::clear](ratatui_core::terminal::Terminal::clear).
+    pub fn set_empty_cell(&mut self, cell: Cell) {
+        if let Some(cell) = valid_empty_cell(cell) {
+            self.tui_surface.empty_cell = cell;
+        }
+    }
+
+    /// Fill for cells that were never written or are cleared.
+    pub fn empty_cell(&self) -> &Cell {
+        &self.tui_surface.empty_cell
+    }
+
+    /// Render one row with double width or double height.
+    ///
+    /// Each cell of the row is rendered twice as wide, so only the
+    /// cells in the left half of the row are visible. For a double-height
+    /// line the same content must be drawn to two consecutive rows,
+    /// one set to [LineScale::DoubleHeightTop] and the next to
+    /// [LineScale::DoubleHeightBottom].
+    ///
+    /// [pos_to_cell](Self::pos_to_cell) accounts for the scaling.
+    pub fn set_line_scale(&mut self, row: u16, scale: LineScale) {
+        let bounds = self.size().unwrap();
+        if row >= bounds.height {
+            return;
+        }
+        self.tui_surface
+            .line_scale
+            .resize(bounds.height as usize, LineScale::Normal);
+        if self.tui_surface.line_scale[row as usize] == scale {
+            return;
+        }
+        self.tui_surface.line_scale[row as usize] = scale;
+
+        if (row as usize) < self.tui_surface.dirty_rows.len() {
+            let offset = row as usize * bounds.width as usize;
+            self.tui_surface.dirty_rows.set(row as usize, true);
+            self.tui_surface.dirty_cells[offset..offset + bounds.width as usize].fill(true);
+        }
+    }
+
+    /// Scaling of the given row.
+    pub fn line_scale(&self, row: u16) -> LineScale {
+        self.tui_surface
+            .line_scale
+            .get(row as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Colors are inverted.
+    pub fn invert_colors(&self) -> bool {
+        self.tui_surface.invert_colors
+    }
+
+    /// High contrast mode. All colors are snapped to black or white
+    /// depending on their luminance.
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_high_contrast(&mut self, high_contrast: bool) {
+        self.invalidate();
+        self.tui_surface.high_contrast = high_contrast;
+    }
+
+    /// High contrast mode is active.
+    pub fn high_contrast(&self) -> bool {
+        self.tui_surface.high_contrast
+    }
+
+    /// Render modifiers with decorations ratatui has no modifier for.
+    /// The default maps nothing.
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_modifier_remap(&mut self, remap: ModifierRemap) {
+        self.invalidate();
+        self.tui_surface.modifier_remap = remap;
+    }
+
+    /// Modifiers that are rendered with extra decorations.
+    pub fn modifier_remap(&self) -> ModifierRemap {
+        self.tui_surface.modifier_remap
+    }
+
+    /// Highlight a selection.
+    ///
+    /// Each range goes from the start position up to and including
+    /// the end position in reading order, wrapping at the end of
+    /// each row. The positions are visual positions on the screen,
+    /// with bidi text the selection covers what is shown there.
+    ///
+    /// The color is blended over the background of the selected
+    /// cells, the cells of the application stay as they are.
+    /// An empty slice removes the selection.
+    ///
+    /// This will cause a full repaint of the screen the next time
+    /// [`WgpuBackend::flush`] is called.
+    pub fn set_selection(
+        &mut self,
+        ranges: &[(
+            ratatui_core::layout::Position,
+            ratatui_core::layout::Position,
+        )],
+        color: ratatui_core::style::Color,
+    ) {
+        self.invalidate();
+        self.tui_surface.selection = ranges.to_vec();
+        self.tui_surface.selection_color = color;
+    }
+
+    /// Current selection.
+    pub fn selection(
+        &self,
+    ) -> &[(
+        ratatui_core::layout::Position,
+        ratatui_core::layout::Position,
+    )] {
+        &self.tui_surface.selection
+    }
+
+    /// Fonts used for rendering.
+    ///
+    /// Changes go through [update_fonts](Self::update_fonts) and the
+    /// other update methods, which rebuild the surface.
+    pub fn fonts(&self) -> &Fonts<'f> {
+        &self.fonts
+    }
+
+    /// Problems found while adding the fonts, see [Fonts::warnings].
+    pub fn font_warnings(&self) -> &[FontWarning] {
+        self.fonts.warnings()
+    }
+
+    /// Update the fonts used for rendering. This will cause a full repaint of
+    /// the screen the next time [`WgpuBackend::flush`] is called. A call to
+    /// [ratatui_core::terminal::Terminal::draw] will do this.
+    ///
+    /// This will also change the number of cells if the font has a different
+    /// aspect ratio for its glyphs.
+    ///
+    /// The size of the new fonts becomes the base font-size, which is
+    /// multiplied with the current scale factor.
+    pub fn update_fonts(&mut self, new_fonts: Fonts<'f>) {
+        self.fonts = new_fonts;
+        self.font_size = self.fonts.height_px();
+        self.font_size_pt = None;
+        self.cell_width = self.fonts.cell_width_px();
+        self.scale_fonts();
+        // font ids and fake styles may have changed.
+        self.wgpu_atlas.cached.clear();
+        self.tmp_plan_cache.clear();
+        self.tmp_shape_cache.clear();
+
+        rebuild_surface(
+            self.fonts.cell_box(),
+            &mut self.tui_surface,
+            &mut self.rendered,
+            &mut self.wgpu_base,
+            &mut self.wgpu_atlas,
+            self.wgpu_post_process.as_mut(),
+        );
+    }
+
+    /// Replace the fonts used for rendering. This will keep the fallback fonts.
+    /// If you want to replace those too, use [update_fonts].
+    ///
+    /// This will cause a full repaint of the screen the next
+    /// time [`WgpuBackend::flush`] is called.
     /// A call to [ratatui_core::terminal::Terminal::draw] will do this.
+    pub fn update_font_vec(&mut self, new_fonts: Vec<Font<'f>>) {
+        self.fonts.clear_fonts();
+        self.fonts.add_fonts(new_fonts);
+        // the new fonts get new ids, the entries of the old fonts are
+        // never used again. and the new fonts may have other features.
+        self.tmp_plan_cache.clear();
+        self.tmp_shape_cache.clear();
+
+        rebuild_surface(
+            self.fonts.cell_box(),
+            &mut self.tui_surface,
+            &mut self.rendered,
+            &mut self.wgpu_base,
+            &mut self.wgpu_atlas,
+            self.wgpu_post_process.as_mut(),
+        );
+    }
+
+    /// Remove a single font, see [Fonts::remove_font].
+    ///
+    /// This will cause a full repaint of the screen the next
+    /// time [`WgpuBackend::flush`] is called.
+    pub fn remove_font(&mut self, id: u64) -> bool {
+        if !self.fonts.remove_font(id) {
+            return false;
+        }
+        // cells may have used the removed font.
+        self.tmp_plan_cache.clear();
+        self.tmp_shape_cache.clear();
+
+        rebuild_surface(
+            self.fonts.cell_box(),
+            &mut self.tui_surface,
+            &mut self.rendered,
+            &mut self.wgpu_base,
+            &mut self.wgpu_atlas,
+            self.wgpu_post_process.as_mut(),
+        );
+        true
+    }
+
+    /// Update the font-size used for rendering.
+    ///
+    /// This will cause a full repaint of
+    /// the screen the next time [`WgpuBackend::flush`] is called.
+    /// A call to [ratatui_core::terminal::Terminal::draw] will do this.
+    ///
+    /// The font-size will be multiplied with the current scale factor.
     pub fn update_font_size(&mut self, new_font_size: u32) {
-        self.fonts.set_height_px(new_font_size);
+        self.font_size_pt = None;
+        self.font_size = new_font_size;
+        self.scale_fonts();
 
         rebuild_surface(
             self.fonts.cell_box(),
             &mut self.tui_surface,
             &mut self.rendered,
-            &mut self.wgpu_base,
-            &mut self.wgpu_atlas,
-            self.wgpu_post_process.as_mut(),
+            &mut self.wgpu_base,
+            &mut self.wgpu_atlas,
+            self.wgpu_post_process.as_mut(),
+        );
+    }
+
+    /// Update the font-size in points for the given dpi.
+    ///
+    /// This is `points * dpi / 72` pixels, otherwise it works like
+    /// [update_font_size](Self::update_font_size).
+    ///
+    /// Use the logical dpi (96 on most systems). The scale factor of
+    /// the window is applied on top, a later change of the scale
+    /// factor recomputes the pixels.
+    ///
+    /// Points or dpi that don't give a valid size are ignored.
+    pub fn set_font_size_pt(&mut self, points: f32, dpi: f32) {
+        if let Some(size) = pt_to_px(points, dpi) {
+            self.update_font_size(size);
+            self.font_size_pt = Some((points, dpi));
+        }
+    }
+
+    /// Font-size in points and the dpi, if it was given that way.
+    ///
+    /// This is None after the font-size has been set in pixels or
+    /// with new fonts.
+    pub fn font_size_pt(&self) -> Option<(f32, f32)> {
+        self.font_size_pt
+    }
+
+    /// Use a fixed cell width in pixels instead of the advance of
+    /// the fonts. None goes back to the width of the fonts.
+    ///
+    /// The width is multiplied with the current scale factor, like
+    /// the font-size.
+    ///
+    /// Glyphs keep their size, glyphs wider than the cell may be
+    /// clipped.
+    ///
+    /// This will change the number of cells and cause a full repaint
+    /// of the screen the next time [`WgpuBackend::flush`] is called.
+    pub fn set_cell_width_px(&mut self, width_px: Option<u32>) {
+        self.cell_width = width_px.filter(|w| *w > 0);
+        self.scale_fonts();
+
+        rebuild_surface(
+            self.fonts.cell_box(),
+            &mut self.tui_surface,
+            &mut self.rendered,
+            &mut self.wgpu_base,
+            &mut self.wgpu_atlas,
+            self.wgpu_post_process.as_mut(),
+        );
+    }
+
+    /// Set the scale factor of the window. This multiplies the base
+    /// font-size given with [update_font_size](Self::update_font_size)
+    /// or the Builder, and a fixed cell width given with
+    /// [set_cell_width_px](Self::set_cell_width_px).
+    ///
+    /// Call this when winit sends a `ScaleFactorChanged` event.
+    ///
+    /// __Info__
+    ///
+    /// This only changes the font-size. The pixel dimensions of the surface
+    /// are still set with [resize](Self::resize). When the window moves
+    /// to another monitor you usually get both, a resize and a new scale
+    /// factor. The order in which you apply them doesn't matter.
+    ///
+    /// This will cause a full repaint of
+    /// the screen the next time [`WgpuBackend::flush`] is called.
+    pub fn set_scale_factor(&mut self, factor: f32) {
+        if !factor.is_finite() || factor <= 0.0 {
+            return;
+        }
+        self.scale_factor = factor;
+        self.scale_fonts();
+
+        rebuild_surface(
+            self.fonts.cell_box(),
+            &mut self.tui_surface,
+            &mut self.rendered,
+            &mut self.wgpu_base,
+            &mut self.wgpu_atlas,
+            self.wgpu_post_process.as_mut(),
+        );
+    }
+
+    /// Current scale factor.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    // apply the scale factor to the font-size and the cell width.
+    fn scale_fonts(&mut self) {
+        self.fonts.set_cell_width_px(
+            self.cell_width
+                .map(|width| scaled_font_size(width, self.scale_factor)),
+        );
+        self.fonts
+            .set_height_px(scaled_font_size(self.font_size, self.scale_factor));
+    }
+
+    /// Toggle blinking.
+    ///
+    /// This will increase the internal blink-counter and render all
+    /// cells marked as 'blink'. It will also make the cursor blink.
+    ///
+    /// The timing for calling blink is up to the application.
+    /// This will give the base-rate for all blink effects. The
+    /// actually rate is determined by the divisor for each effect.
+    /// You can set the divisors when creating the backend with the
+    /// [Builder](crate::Builder) or later with
+    /// [set_cursor_blink](Self::set_cursor_blink),
+    /// [set_fast_blink](Self::set_fast_blink) and
+    /// [set_slow_blink](Self::set_slow_blink).
+    pub fn blink(&mut self, blinking: Blinking) {
+        let bounds = self.size().expect("size");
+
+        advance_blink(&mut self.tui_surface);
+        if self.wgpu_base.text_layer_load != LoadOp::Load {
+            // the text layer starts empty, blinking cells are not enough.
+            mark_all_repaint(&mut self.tui_surface);
+            append_dirty_rows(
+                bounds,
+                self.fonts.cell_box(),
+                &mut self.tui_surface,
+                self.wgpu_post_process.as_ref(),
+                &self.rendered,
+                &mut self.wgpu_vertices,
+            );
+        } else {
+            flush_blink(
+                blinking,
+                bounds,
+                self.fonts.cell_box(),
+                &mut self.tui_surface,
+                &self.rendered,
+                &mut self.wgpu_vertices,
+            );
+        }
+
+        let result = render(
+            self.window_size().expect("window_size"),
+            self.fonts.cell_box(),
+            self.tui_surface.inset,
+            self.tui_surface
+                .margin_color
+                .unwrap_or(self.tui_surface.reset_bg),
+            self.tui_surface.window_opacity,
+            &mut self.wgpu_base,
+            &self.wgpu_images,
+            &self.wgpu_pipeline,
+            self.wgpu_post_process.as_mut(),
+            &mut self.wgpu_vertices,
+            &mut self.render_stats,
+        );
+        self.last_presented = matches!(result, Ok(true));
+        self.last_render_error = result.err();
+        if self.last_render_error.is_some() {
+            // the text layer missed this update.
+            mark_all_dirty(&mut self.tui_surface);
+        }
+
+        self.wgpu_vertices.clear();
+    }
+
+    /// Change the divisor for the cursor blink.
+    ///
+    /// The cursor switches every time the blink counter is divisible
+    /// by this value, see [blink](Self::blink). A divisor of 0 means
+    /// no blinking.
+    pub fn set_cursor_blink(&mut self, divisor: u8) {
+        self.tui_surface.cursor_divisor = divisor;
+        if divisor == 0 && !self.tui_surface.cursor_showing {
+            self.tui_surface.cursor_showing = true;
+            self.mark_cursor_dirty();
+        }
+    }
+
+    /// Divisor for the cursor blink.
+    pub fn cursor_blink_divisor(&self) -> u8 {
+        self.tui_surface.cursor_divisor
+    }
+
+    /// Change the divisor for [Modifier::RAPID_BLINK].
+    ///
+    /// The text switches every time the blink counter is divisible
+    /// by this value, see [blink](Self::blink). A divisor of 0 means
+    /// no blinking.
+    pub fn set_fast_blink(&mut self, divisor: u8) {
+        self.tui_surface.fast_blink_divisor = divisor;
+        if divisor == 0 && !self.tui_surface.fast_blink_showing {
+            self.tui_surface.fast_blink_showing = true;
+            mark_all_dirty(&mut self.tui_surface);
+        }
+    }
+
+    /// Divisor for [Modifier::RAPID_BLINK].
+    pub fn fast_blink_divisor(&self) -> u8 {
+        self.tui_surface.fast_blink_divisor
+    }
+
+    /// Change the divisor for [Modifier::SLOW_BLINK].
+    ///
+    /// The text switches every time the blink counter is divisible
+    /// by this value, see [blink](Self::blink). A divisor of 0 means
+    /// no blinking.
+    pub fn set_slow_blink(&mut self, divisor: u8) {
+        self.tui_surface.slow_blink_divisor = divisor;
+        if divisor == 0 && !self.tui_surface.slow_blink_showing {
+            self.tui_surface.slow_blink_showing = true;
+            mark_all_dirty(&mut self.tui_surface);
+        }
+    }
+
+    /// Divisor for [Modifier::SLOW_BLINK].
+    pub fn slow_blink_divisor(&self) -> u8 {
+        self.tui_surface.slow_blink_divisor
+    }
+
+    /// Would a flush change anything visible?
+    ///
+    /// This is true if there are changes from draw that have not been
+    /// flushed, or if the post-processor wants to update anyway.
+    /// A render loop can skip the flush if this is false.
+    ///
+    /// Calling draw multiple times before a flush is cheap, the changes
+    /// accumulate and shaping happens only once during flush.
+    ///
+    /// __Info__
+    ///
+    /// Changes that rebuild the surface, like resize or new fonts,
+    /// are pending too. They clear all cells, so draw everything
+    /// again before the flush.
+    pub fn has_pending_changes(&self) -> bool {
+        self.tui_surface.rebuilt
+            || self.tui_surface.dirty_rows.any()
+            || !self.tui_surface.dirty_img.is_empty()
+            || self.wgpu_post_process.needs_update()
+    }
+
+    /// The error of the last render, if any.
+    ///
+    /// Flush always returns Ok, even if the frame could not be
+    /// presented, e.g. when the surface is lost. This is updated with
+    /// every flush, blink or redraw.
+    ///
+    /// A lost or outdated surface is reconfigured and the frame is
+    /// retried once. If that fails too, the frame is dropped and the
+    /// next flush repaints everything.
+    pub fn last_render_error(&self) -> Option<&Error> {
+        self.last_render_error.as_ref()
+    }
+
+    /// The last flush presented a frame.
+    ///
+    /// A flush without any changes, and without a post-processor
+    /// that [needs an update](PostProcessor::needs_update), does no
+    /// GPU work at all. Render loops can use this to avoid waking
+    /// the display. This is updated with every flush, blink or redraw,
+    /// and is false if the render failed.
+    pub fn last_flush_presented(&self) -> bool {
+        self.last_presented
+    }
+
+    /// Repaint all cells with the next flush.
+    ///
+    /// Use this when something changed that the backend can't see
+    /// in the cells. This doesn't clear any caches, and the number
+    /// of cells stays the same.
+    pub fn invalidate(&mut self) {
+        mark_all_dirty(&mut self.tui_surface);
+    }
+
+    /// Draw all cells of a buffer.
+    ///
+    /// This is the same as [draw](Backend::draw) with every cell of
+    /// the buffer, at the positions given by the area of the buffer.
+    /// Cells marked as skip and the cells covered by a wide char are
+    /// left alone, as are cells outside of [size](Backend::size).
+    ///
+    /// Call [flush](Backend::flush) to render the result.
+    pub fn draw_buffer(&mut self, buffer: &ratatui_core::buffer::Buffer) {
+        let bounds = self.size().expect("size");
+
+        let mut covered = 0;
+        let mut content = buffer.content.iter().enumerate().filter_map(|(idx, cell)| {
+            if covered > 0 {
+                covered -= 1;
+                return None;
+            }
+            // the same as the diff of ratatui.
+            #[allow(deprecated)]
+            let skip = cell.diff_option == CellDiffOption::Skip
+                || cell.skip && cell.diff_option == CellDiffOption::None;
+            if skip {
+                return None;
+            }
+            covered = cell.cell_width().saturating_sub(1);
+
+            let (x, y) = buffer.pos_of(idx);
+            (x < bounds.width && y < bounds.height).then_some((x, y, cell))
+        });
+
+        draw_tui(
+            bounds,
+            &self.fonts,
+            &mut content,
+            &mut self.tui_surface,
+            &mut self.rendered,
         );
     }
 
-    /// Toggle blinking.
+    // The cursor cell is repainted with the next flush.
+    // Before the first flush and after a clear with a changed size
+    // there is nothing to mark.
+    fn mark_cursor_dirty(&mut self) {
+        let bounds = self.size().expect("size");
+        let (x, y) = self.tui_surface.cursor;
+        let idx = y as usize * bounds.width as usize + x as usize;
+        if idx < self.tui_surface.dirty_cells.len()
+            && (y as usize) < self.tui_surface.dirty_rows.len()
+        {
+            self.tui_surface.dirty_rows.set(y as usize, true);
+            self.tui_surface.dirty_cells.set(idx, true);
+        }
+    }
+
+    /// Clear the window to the background color and present it.
     ///
-    /// This will increase the internal blink-counter and render all
-    /// cells marked as 'blink'. It will also make the cursor blink.
+    /// Until the first flush nothing is rendered, and a new window
+    /// may show uninitialized content for a moment. The builder
+    /// already does this once after creating the backend, so you
+    /// only need it if you want to blank the window yourself.
     ///
-    /// The timing for calling blink is up to the application.
-    /// This will give the base-rate for all blink effects. The
-    /// actually rate is determined by the divisor for each effect.
-    /// You can set the divisors when creating the backend with the
-    /// [Builder](crate::Builder).
-    pub fn blink(&mut self, blinking: Blinking) {
-        let bounds = self.size().expect("size");
+    /// The next flush repaints all cells.
+    pub fn clear_surface(&mut self) {
+        self.last_render_error = clear_surface(
+            self.tui_surface
+                .margin_color
+                .unwrap_or(self.tui_surface.reset_bg),
+            self.tui_surface.window_opacity,
+            self.tui_surface.reset_bg,
+            &mut self.wgpu_base,
+            self.wgpu_post_process.as_mut(),
+        )
+        .err();
+        self.last_presented = self.last_render_error.is_none();
+        mark_all_dirty(&mut self.tui_surface);
+    }
 
-        flush_blink(
-            blinking,
-            bounds,
-            self.fonts.cell_box(),
-            &mut self.tui_surface,
-            &self.rendered,
-            &mut self.wgpu_vertices,
-        );
+    /// Render and present without re-shaping the text.
+    ///
+    /// If there are pending changes from a draw this is the same
+    /// as [`WgpuBackend::flush`] and returns its result. Otherwise,
+    /// only the post-processor runs and the result is presented. Use
+    /// this to drive a post-processor that animates (`needs_update`
+    /// returns true) when the text didn't change.
+    /// With a static post-processor this does nothing.
+    ///
+    /// __Info__
+    ///
+    /// Unlike flush, this doesn't reset the blink state. The
+    /// cursor stays in its current blink phase. [blink](WgpuBackend::blink)
+    /// runs the post-processor too, so you don't need to call redraw
+    /// for a frame where you already called blink.
+    pub fn redraw(&mut self) -> std::io::Result<()> {
+        if self.tui_surface.rebuilt
+            || self.tui_surface.dirty_rows.any()
+            || !self.tui_surface.dirty_img.is_empty()
+            || self.wgpu_base.text_layer_load != LoadOp::Load
+        {
+            return self.flush();
+        }
 
-        render(
+        let result = render(
             self.window_size().expect("window_size"),
             self.fonts.cell_box(),
-            self.tui_surface.reset_bg,
-            &self.wgpu_base,
+            self.tui_surface.inset,
+            self.tui_surface
+                .margin_color
+                .unwrap_or(self.tui_surface.reset_bg),
+            self.tui_surface.window_opacity,
+            &mut self.wgpu_base,
             &self.wgpu_images,
             &self.wgpu_pipeline,
             self.wgpu_post_process.as_mut(),
-            &self.wgpu_vertices,
+            &mut self.wgpu_vertices,
+            &mut self.render_stats,
         );
+        self.last_presented = matches!(result, Ok(true));
+        self.last_render_error = result.err();
+        if self.last_render_error.is_some() {
+            // the text layer missed this update.
+            mark_all_dirty(&mut self.tui_surface);
+        }
 
-        self.wgpu_vertices.clear();
+        drop_images(&mut self.tui_surface, &mut self.wgpu_images);
+        Ok(())
     }
 
-    /// Add an image as raw RGBA data.
+    /// Add an image as raw RGBA data with straight alpha.
     ///
     /// This will return an ImageHandle.
     ///
+    /// Use [add_image_premultiplied](Self::add_image_premultiplied)
+    /// if the colors are premultiplied with the alpha.
+    ///
     /// Freeing the image-texture occurs when you drop the last clone of
     /// the ImageHandle. The texture will be dropped after the next render.
     pub fn add_image(&mut self, image: &[u8], width: u32, height: u32) -> ImageHandle {
@@ -584,15 +1943,86 @@ impl<'f, 's> WgpuBackend<'f, 's> {
         handle
     }
 
+    /// Add an image as raw RGBA data with premultiplied alpha.
+    ///
+    /// The colors are converted to straight alpha before the upload,
+    /// otherwise semi-transparent pixels would come out too dark.
+    /// See [add_image](Self::add_image).
+    pub fn add_image_premultiplied(
+        &mut self,
+        image: &[u8],
+        width: u32,
+        height: u32,
+    ) -> ImageHandle {
+        self.add_image(&unpremultiply(image), width, height)
+    }
+
+    /// Remove all images at once.
+    ///
+    /// This drops the textures of all images immediately, and the
+    /// areas covered by images are repainted with the next flush.
+    /// Existing [ImageHandle]s become invalid, rendering them is
+    /// a no-op.
+    pub fn clear_images(&mut self) {
+        let bounds = self.size().unwrap();
+        let cell_box = self.fonts.cell_box();
+
+        // any cell covered by an image is marked as dirty.
+        if self.tui_surface.dirty_cells.len() == bounds.width as usize * bounds.height as usize {
+            for img_info in self
+                .tui_surface
+                .images
+                .iter()
+                .chain(self.tui_surface.dirty_img.iter())
+            {
+                let img_pos = cell_box.cell_pos(img_info.view_rect.0, img_info.view_rect.1, bounds);
+                let img_pos2 = cell_box.cell_pos(
+                    img_info.view_rect.0 + img_info.view_rect.2 as i32,
+                    img_info.view_rect.1 + img_info.view_rect.3 as i32,
+                    bounds,
+                );
+                for y in img_pos.y..=img_pos2.y {
+                    for x in img_pos.x..=img_pos2.x {
+                        self.tui_surface
+                            .dirty_cells
+                            .set((y * bounds.width + x) as usize, true);
+                    }
+                    self.tui_surface.dirty_rows.set(y as usize, true);
+                }
+            }
+        }
+
+        self.tui_surface.images.clear();
+        self.tui_surface.dirty_img.clear();
+        {
+            let image_buffer = self.tui_surface.image_frame.buffer();
+            let mut image_buffer = image_buffer.lock().expect("lock");
+            image_buffer.images.clear();
+            image_buffer.image_size.clear();
+        }
+        self.wgpu_images.handles.clear();
+        self.wgpu_images.img.clear();
+    }
+
     /// Returns a BufferView for the current rendered result.
     ///
+    /// The buffer has the size of the surface, after a
+    /// [resize](Self::resize) too. Each row is padded to a multiple
+    /// of 256 bytes, see [headless_bytes_per_row](Self::headless_bytes_per_row).
+    ///
     /// __Info__
     ///
     /// You need to call [unmap_headless_buffer] to release the mapping.
+    ///
+    /// Fails with [Error::HeadlessReadbackDisabled] when built with
+    /// [build_headless_texture_only](crate::Builder::build_headless_texture_only).
     pub fn map_headless_buffer(&self) -> Result<BufferView, Error> {
         let RenderSurface::Headless(surface) = &self.wgpu_base.surface else {
             panic!("can only be called when initialized as headless.");
         };
+        let Some(buffer) = surface.buffer.as_ref() else {
+            return Err(Error::HeadlessReadbackDisabled);
+        };
 
         let mut encoder = self
             .wgpu_base
@@ -605,7 +2035,7 @@ impl<'f, 's> WgpuBackend<'f, 's> {
                 .expect("headless texture")
                 .as_image_copy(),
             wgpu::TexelCopyBufferInfo {
-                buffer: surface.buffer.as_ref().expect("headless buffer"),
+                buffer,
                 layout: wgpu::TexelCopyBufferLayout {
                     offset: 0,
                     bytes_per_row: Some(surface.buffer_width),
@@ -620,7 +2050,7 @@ impl<'f, 's> WgpuBackend<'f, 's> {
         );
         self.wgpu_base.queue.submit(Some(encoder.finish()));
 
-        let buffer = surface.buffer.as_ref().expect("headless buffer").slice(..);
+        let buffer = buffer.slice(..);
         let data = Arc::new(Mutex::new(None));
         let data_copy = data.clone();
         buffer.map_async(wgpu::MapMode::Read, move |data| {
@@ -643,16 +2073,285 @@ impl<'f, 's> WgpuBackend<'f, 's> {
         Ok(buffer.get_mapped_range())
     }
 
+    /// Copy the text layer before post-processing.
+    ///
+    /// Returns width, height and the RGBA pixels. This is the
+    /// composited text and images as the post-processor sees them,
+    /// useful to tell compositor bugs from post-processor bugs.
+    ///
+    /// The pixels use the format of the text layer, 8 bits per
+    /// channel, or f16 per channel with
+    /// [with_hdr_text_layer](crate::Builder::with_hdr_text_layer).
+    ///
+    /// It contains the state after the last flush.
+    pub fn capture_text_layer(&self) -> Result<(u32, u32, Vec<u8>), Error> {
+        let texture = self.wgpu_base.text_dest_view.texture();
+        let width = texture.width();
+        let height = texture.height();
+        let bytes_per_pixel = texture.format().block_copy_size(None).unwrap_or(4);
+        let bytes_per_row =
+            (width * bytes_per_pixel).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = self.wgpu_base.device.create_buffer(&BufferDescriptor {
+            label: Some("Text Layer Capture"),
+            size: bytes_per_row as u64 * height as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .wgpu_base
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.wgpu_base.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let data = Arc::new(Mutex::new(None));
+        let data_copy = data.clone();
+        slice.map_async(wgpu::MapMode::Read, move |data| {
+            let mut guard = data_copy.lock().expect("lock");
+            *guard = Some(data);
+        });
+        self.wgpu_base
+            .device
+            .poll(PollType::Wait {
+                submission_index: None,
+                timeout: None,
+            })
+            .map_err(|e| Error::PollError(Box::new(e)))?;
+        let guard = data.lock().expect("lock");
+        match guard.as_ref().expect("data") {
+            Ok(_) => {}
+            Err(e) => return Err(Error::BufferAsyncError(e.to_string())),
+        };
+
+        // strip the row padding.
+        let mapped = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((width * height * bytes_per_pixel) as usize);
+        for row in mapped.chunks(bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..(width * bytes_per_pixel) as usize]);
+        }
+        drop(mapped);
+        buffer.unmap();
+
+        Ok((width, height, pixels))
+    }
+
+    /// Length of one row in the buffer returned by
+    /// [map_headless_buffer](Self::map_headless_buffer).
+    ///
+    /// This is the width times the bytes per pixel, rounded up to
+    /// a multiple of 256.
+    pub fn headless_bytes_per_row(&self) -> u32 {
+        let RenderSurface::Headless(surface) = &self.wgpu_base.surface else {
+            panic!("can only be called when initialized as headless.");
+        };
+
+        surface.buffer_width
+    }
+
     /// Releases the mapping of the headless buffer.
     pub fn unmap_headless_buffer(&self) {
         let RenderSurface::Headless(surface) = &self.wgpu_base.surface else {
             panic!("can only be called when initialized as headless.");
         };
 
-        surface.buffer.as_ref().expect("headless buffer").unmap();
+        if let Some(buffer) = &surface.buffer {
+            buffer.unmap();
+        }
+    }
+
+    /// The texture of the headless surface.
+    ///
+    /// It holds the frame after the last flush. With
+    /// [build_headless_texture_only](crate::Builder::build_headless_texture_only)
+    /// it can be sampled by other render passes on the same device.
+    ///
+    /// __Info__
+    ///
+    /// A [resize](Self::resize) creates a new texture, get it again
+    /// afterwards.
+    pub fn headless_texture(&self) -> &wgpu::Texture {
+        let RenderSurface::Headless(surface) = &self.wgpu_base.surface else {
+            panic!("can only be called when initialized as headless.");
+        };
+
+        surface.texture.as_ref().expect("headless texture")
+    }
+
+    /// Start capturing every presented frame.
+    ///
+    /// Each frame is copied to a readback buffer together with its
+    /// rendering, and [poll_capture](Self::poll_capture) picks up the
+    /// finished frames without waiting for the GPU. Up to three
+    /// frames can be on their way, the next frame waits for the
+    /// oldest one.
+    ///
+    /// A frame is presented when a flush has changes, and with
+    /// [redraw](Self::redraw) or [blink](Self::blink). Finished
+    /// frames are kept until they are picked up, so call poll_capture
+    /// regularly.
+    pub fn begin_capture(&mut self) {
+        let RenderSurface::Headless(surface) = &mut self.wgpu_base.surface else {
+            panic!("can only be called when initialized as headless.");
+        };
+
+        surface.capture.get_or_insert_with(Default::default).active = true;
+    }
+
+    /// Stop capturing.
+    ///
+    /// This waits for the frames that are still on their way.
+    /// [poll_capture](Self::poll_capture) returns them afterwards.
+    pub fn end_capture(&mut self) -> Result<(), Error> {
+        let RenderSurface::Headless(surface) = &mut self.wgpu_base.surface else {
+            panic!("can only be called when initialized as headless.");
+        };
+
+        if let Some(ring) = &mut surface.capture {
+            ring.active = false;
+            if ring.is_pending() {
+                self.wgpu_base
+                    .device
+                    .poll(PollType::Wait {
+                        submission_index: None,
+                        timeout: None,
+                    })
+                    .map_err(|e| Error::PollError(Box::new(e)))?;
+                ring.collect();
+            }
+        }
+        Ok(())
+    }
+
+    /// The oldest finished frame since [begin_capture](Self::begin_capture).
+    ///
+    /// Returns the pixels in the format of the headless surface,
+    /// without the row padding, at the size the surface had when
+    /// the frame was presented. None if no frame is finished yet,
+    /// this never blocks.
+    pub fn poll_capture(&mut self) -> Option<Vec<u8>> {
+        let RenderSurface::Headless(surface) = &mut self.wgpu_base.surface else {
+            panic!("can only be called when initialized as headless.");
+        };
+
+        let ring = surface.capture.as_mut()?;
+        if ring.is_pending() {
+            // only runs the callbacks of finished mappings.
+            if let Err(e) = self.wgpu_base.device.poll(PollType::Poll) {
+                warn!("capture poll failed: {e}");
+            }
+            ring.collect();
+        }
+        ring.next_frame()
+    }
+}
+
+// Font size with the scale factor applied.
+fn scaled_font_size(font_size: u32, scale_factor: f32) -> u32 {
+    ((font_size as f32 * scale_factor).round() as u32).max(1)
+}
+
+// Font size in px for the points at the given dpi.
+pub(super) fn pt_to_px(points: f32, dpi: f32) -> Option<u32> {
+    let px = (points * dpi / 72.0).round();
+    if px.is_finite() && px >= 1.0 {
+        Some(px as u32)
+    } else {
+        None
+    }
+}
+
+// Repaint everything with the next flush, without a full rebuild.
+fn mark_all_dirty(tui_surface: &mut TuiSurface) {
+    tui_surface.dirty_rows.fill(true);
+    tui_surface.dirty_cells.fill(true);
+}
+
+// Paint every cell and image again, the text layer is cleared
+// before each frame.
+fn mark_all_repaint(tui_surface: &mut TuiSurface) {
+    mark_all_dirty(tui_surface);
+    tui_surface.dirty_img.clone_from(&tui_surface.images);
+}
+
+// Blank all cells and repaint everything with the next flush.
+// The buffers keep their size, the glyphs of the cells are
+// rebuilt anyway when the cells are dirty.
+fn clear_cells(tui_surface: &mut TuiSurface, width: u16) {
+    tui_surface.cells.fill(tui_surface.empty_cell.clone());
+    tui_surface.cell_font.fill(0);
+    reset_cell_remap(tui_surface, 0..tui_surface.cell_remap.len(), width);
+    tui_surface.fast_blinking.fill(false);
+    tui_surface.slow_blinking.fill(false);
+    mark_all_dirty(tui_surface);
+}
+
+// Empty cells are shown in their own column until the next flush
+// computes the remap of the row.
+fn reset_cell_remap(tui_surface: &mut TuiSurface, cells: Range<usize>, width: u16) {
+    for idx in cells {
+        tui_surface.cell_remap[idx] = (idx % width.max(1) as usize) as u16;
+    }
+}
+
+// The fill for empty cells must be one column wide.
+pub(super) fn valid_empty_cell(cell: Cell) -> Option<Cell> {
+    if symbol_width(cell.symbol()) == 1 {
+        Some(cell)
+    } else {
+        warn!("empty cell {:?} is not one column wide.", cell.symbol());
+        None
     }
 }
 
+// Display width of a cell symbol.
+fn symbol_width(symbol: &str) -> usize {
+    symbol
+        .chars()
+        .find(|c| c.general_category() != GeneralCategory::Format)
+        .unwrap_or(' ')
+        .width()
+        .unwrap_or(1)
+}
+
+// A cursor on the trailing half of a wide glyph moves left to the
+// cell that holds the glyph, like a terminal would do.
+fn snap_to_lead_cell(
+    tui_surface: &TuiSurface,
+    bounds: ratatui_core::layout::Size,
+    x: u16,
+    y: u16,
+) -> u16 {
+    let row_offset = y as usize * bounds.width as usize;
+    let mut x = x;
+    while x > 0
+        && tui_surface
+            .cells
+            .get(row_offset + x as usize)
+            .is_some_and(|cell| *cell == NULL_CELL)
+    {
+        x -= 1;
+    }
+    x
+}
+
 // Resize the rendering surface. This should be called e.g. to keep the
 // backend in sync with your window size.
 fn rebuild_surface(
@@ -663,14 +2362,18 @@ fn rebuild_surface(
     wgpu_atlas: &mut WgpuAtlas,
     wgpu_post_process: &mut dyn PostProcessor,
 ) {
-    let width = wgpu_base.surface_config.width;
-    let height = wgpu_base.surface_config.height;
     wgpu_base
         .surface
         .configure(&wgpu_base.device, &wgpu_base.surface_config);
+    wgpu_base.rotation_blit = build_rotation_blit(
+        &wgpu_base.device,
+        &wgpu_base.surface_config,
+        wgpu_base.rotation,
+    );
+    let width = wgpu_base.post_config().width;
+    let height = wgpu_base.post_config().height;
 
-    let chars_wide = width / cell_box.width;
-    let chars_high = height / cell_box.height;
+    let (chars_wide, chars_high) = tui_surface.inset.grid(width, height, cell_box);
 
     wgpu_atlas.cached.update_font_box(cell_box);
 
@@ -686,6 +2389,7 @@ fn rebuild_surface(
     tui_surface.dirty_rows.clear();
     tui_surface.dirty_cells.clear();
     tui_surface.dirty_img.clear();
+    tui_surface.rebuilt = true;
 
     let image_buffer = tui_surface.image_frame.buffer();
     let mut image_buffer = image_buffer.lock().expect("lock");
@@ -694,16 +2398,19 @@ fn rebuild_surface(
 
     rendered.clear();
 
+    // the text layer starts at the top-left of the surface, the right
+    // and bottom inset are left to the post-processor.
     wgpu_base.text_dest_view = build_wgpu_state(
         &wgpu_base.device,
-        chars_wide * cell_box.width,
-        chars_high * cell_box.height,
+        wgpu_base.text_dest_view.texture().format(),
+        tui_surface.inset.left + chars_wide * cell_box.width,
+        tui_surface.inset.top + chars_high * cell_box.height,
     );
 
     wgpu_post_process.resize(
         &wgpu_base.device,
         &wgpu_base.text_dest_view,
-        &wgpu_base.surface_config,
+        wgpu_base.post_config(),
     );
 }
 
@@ -728,15 +2435,18 @@ fn drop_images(tui_surface: &mut TuiSurface, wgpu_images: &mut WgpuImages) {
 fn render(
     bounds: WindowSize,
     cell_box: CellBox,
-    reset_bg: Rgb,
-    base: &WgpuBase,
+    inset: Inset,
+    margin_color: Rgb,
+    opacity: f32,
+    base: &mut WgpuBase,
     images: &WgpuImages,
     pipeline: &WgpuPipeline,
     post_process: &mut dyn PostProcessor,
-    vertices: &WgpuVertices,
-) {
+    vertices: &mut WgpuVertices,
+    stats: &mut RenderStats,
+) -> Result<bool, Error> {
     if vertices.is_empty() && !post_process.needs_update() {
-        return;
+        return Ok(false);
     }
 
     let mut encoder = base
@@ -745,6 +2455,40 @@ fn render(
             label: Some("Draw Encoder"),
         });
 
+    render_text(
+        &mut encoder,
+        bounds,
+        cell_box,
+        inset,
+        base,
+        images,
+        pipeline,
+        vertices,
+        stats,
+    );
+
+    present(encoder, margin_color, opacity, base, post_process)?;
+    Ok(true)
+}
+
+// record the text layer passes.
+fn render_text(
+    encoder: &mut CommandEncoder,
+    bounds: WindowSize,
+    cell_box: CellBox,
+    inset: Inset,
+    base: &WgpuBase,
+    images: &WgpuImages,
+    pipeline: &WgpuPipeline,
+    vertices: &mut WgpuVertices,
+    stats: &mut RenderStats,
+) {
+    vertices.sort_images();
+
+    stats.bg_quads += (vertices.bg_vertices.len() + vertices.line_vertices.len()) as u64 / 4;
+    stats.fg_quads += vertices.text_vertices.len() as u64 / 4;
+    stats.images_drawn += vertices.img_render.len() as u64;
+
     {
         let mut uniforms = base
             .queue
@@ -754,11 +2498,12 @@ fn render(
                 NonZeroU64::new(size_of::<[f32; 4]>() as u64).unwrap(),
             )
             .unwrap();
+        // size of the text layer and the offset of the cell grid.
         uniforms.copy_from_slice(bytemuck::cast_slice(&[
-            bounds.columns_rows.width as f32 * cell_box.width as f32,
-            bounds.columns_rows.height as f32 * cell_box.height as f32,
-            0.0,
-            0.0,
+            inset.left as f32 + bounds.columns_rows.width as f32 * cell_box.width as f32,
+            inset.top as f32 + bounds.columns_rows.height as f32 * cell_box.height as f32,
+            inset.left as f32,
+            inset.top as f32,
         ]));
     }
 
@@ -780,6 +2525,18 @@ fn render(
         usage: BufferUsages::INDEX,
     });
 
+    let line_vertices = base.device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Line Vertices"),
+        contents: bytemuck::cast_slice(&vertices.line_vertices),
+        usage: BufferUsages::VERTEX,
+    });
+
+    let line_indices = base.device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Line Indices"),
+        contents: bytemuck::cast_slice(&vertices.line_indices),
+        usage: BufferUsages::INDEX,
+    });
+
     let img_vertices = base.device.create_buffer_init(&BufferInitDescriptor {
         label: Some("Image Vertices"),
         contents: bytemuck::cast_slice(&vertices.img_vertices),
@@ -799,7 +2556,7 @@ fn render(
                 view: &base.text_dest_view,
                 resolve_target: None,
                 ops: Operations {
-                    load: LoadOp::Load,
+                    load: base.text_layer_load,
                     store: StoreOp::Store,
                 },
                 depth_slice: None,
@@ -824,10 +2581,18 @@ fn render(
                 images,
                 &img_indices,
                 &img_vertices,
-                &vertices.img_render,
+                vertices,
             );
         }
 
+        if !vertices.line_indices.is_empty() {
+            text_render_pass.set_index_buffer(line_indices.slice(..), IndexFormat::Uint32);
+            text_render_pass.set_pipeline(&pipeline.text_bg_compositor.pipeline);
+            text_render_pass.set_bind_group(0, &pipeline.text_bg_compositor.fs_uniforms, &[]);
+            text_render_pass.set_vertex_buffer(0, line_vertices.slice(..));
+            text_render_pass.draw_indexed(0..vertices.line_indices.len() as u32 * 6, 0, 0..1);
+        }
+
         if !vertices.text_indices.is_empty() {
             text_render_pass.set_index_buffer(txt_indices.slice(..), IndexFormat::Uint32);
             text_render_pass.set_pipeline(&pipeline.text_fg_compositor.pipeline);
@@ -850,29 +2615,134 @@ fn render(
                 images,
                 &img_indices,
                 &img_vertices,
-                &vertices.img_render,
+                vertices,
             );
         }
     }
+}
 
-    let Some(texture) = base.surface.get_current_texture() else {
-        return;
-    };
+// Clears the text layer to the background color and presents it.
+fn clear_surface(
+    margin_color: Rgb,
+    opacity: f32,
+    bg_color: Rgb,
+    base: &mut WgpuBase,
+    post_process: &mut dyn PostProcessor,
+) -> Result<(), Error> {
+    let mut encoder = base
+        .device
+        .create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Clear Encoder"),
+        });
 
-    let bg_color_u32 = u32::from_le_bytes([reset_bg[0], reset_bg[1], reset_bg[2], 255]);
+    encoder.begin_render_pass(&RenderPassDescriptor {
+        label: Some("Text Clear Pass"),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view: &base.text_dest_view,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Clear(wgpu::Color {
+                    r: bg_color[0] as f64 / 255.0,
+                    g: bg_color[1] as f64 / 255.0,
+                    b: bg_color[2] as f64 / 255.0,
+                    a: 1.0,
+                }),
+                store: StoreOp::Store,
+            },
+            depth_slice: None,
+        })],
+        ..Default::default()
+    });
 
-    post_process.process(
-        bg_color_u32,
+    present(encoder, margin_color, opacity, base, post_process)
+}
+
+// Runs the post-processor over the text layer and presents the frame.
+fn present(
+    mut encoder: CommandEncoder,
+    margin_color: Rgb,
+    opacity: f32,
+    base: &mut WgpuBase,
+    post_process: &mut dyn PostProcessor,
+) -> Result<(), Error> {
+    let texture = match base.surface.get_current_texture() {
+        Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+            // reconfigure and retry once.
+            base.surface.configure(&base.device, &base.surface_config);
+            base.surface.get_current_texture()
+        }
+        texture => texture,
+    };
+    let texture = match texture {
+        Ok(texture) => texture,
+        Err(err) => {
+            warn!("frame dropped: {err}");
+            return Err(Error::SurfaceTextureFailed(err));
+        }
+    };
+
+    post_process_into(
         &mut encoder,
-        &base.queue,
-        &base.text_dest_view,
-        &base.surface_config,
         texture.get_view(),
+        margin_color,
+        opacity,
+        base,
+        post_process,
     );
+    base.surface.capture(&base.device, &mut encoder);
 
-    base.queue.submit(Some(encoder.finish()));
+    let submission = base.queue.submit(Some(encoder.finish()));
+    base.surface.map_capture(submission);
 
     texture.present();
+
+    Ok(())
+}
+
+// Runs the post-processor over the text layer into the target.
+fn post_process_into(
+    encoder: &mut CommandEncoder,
+    target: &TextureView,
+    margin_color: Rgb,
+    opacity: f32,
+    base: &WgpuBase,
+    post_process: &mut dyn PostProcessor,
+) {
+    let margin_color_u32 =
+        u32::from_le_bytes([margin_color[0], margin_color[1], margin_color[2], 255]);
+
+    // with a rotation the post-processor renders to an intermediate
+    // texture, that is rotated onto the target.
+    post_process.set_window_opacity(opacity);
+    post_process.process(
+        margin_color_u32,
+        encoder,
+        &base.queue,
+        &base.text_dest_view,
+        base.post_config(),
+        match &base.rotation_blit {
+            Some(blit) => &blit.view,
+            None => target,
+        },
+    );
+    if let Some(blit) = &base.rotation_blit {
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Rotation Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            ..Default::default()
+        });
+        pass.set_pipeline(&blit.pipeline);
+        pass.set_bind_group(0, &blit.bindings, &[]);
+        pass.draw(0..3, 0..1);
+    }
 }
 
 fn render_img(
@@ -883,14 +2753,16 @@ fn render_img(
     images: &WgpuImages,
     img_indices: &Buffer,
     img_vertices: &Buffer,
-    img_render: &[ImageInfo],
+    vertices: &WgpuVertices,
 ) {
     text_render_pass.set_index_buffer(img_indices.slice(..), IndexFormat::Uint32);
 
     text_render_pass.set_pipeline(&pipeline.img_compositor.pipeline);
     text_render_pass.set_bind_group(0, &pipeline.img_compositor.fs_uniforms, &[]);
     text_render_pass.set_vertex_buffer(0, img_vertices.slice(..));
-    for (n, img_info) in img_render.iter().enumerate() {
+
+    for &n in &vertices.img_order {
+        let img_info = &vertices.img_render[n];
         let n = n as u32;
 
         if img_info.below_text != below_text {
@@ -919,11 +2791,15 @@ fn render_img(
             contents: bytemuck::cast_slice(&uv),
         });
 
-        let img_texture = images.img.get(&img_info.image_id).expect("image");
+        let Some(img_texture) = images.img.get(&img_info.image_id) else {
+            // the handle was dropped since the last draw.
+            debug!("image {}: texture gone, not rendered.", img_info.image_id);
+            continue;
+        };
         let img_bindings = build_img_bindings(
             &pipeline.img_compositor,
             &device,
-            &pipeline.sampler,
+            &pipeline.img_sampler,
             &img_texture.texture,
             &uv_transform_buffer,
             &uv_clip_buffer,
@@ -943,15 +2819,22 @@ fn draw_tui(
     tui_surface: &mut TuiSurface,
     rendered: &mut Vec<Rendered>,
 ) {
-    tui_surface
-        .cells
-        .resize(bounds.height as usize * bounds.width as usize, Cell::EMPTY);
+    tui_surface.cells.resize(
+        bounds.height as usize * bounds.width as usize,
+        tui_surface.empty_cell.clone(),
+    );
     tui_surface
         .cell_font
         .resize(bounds.height as usize * bounds.width as usize, 0);
+    let remap_len = tui_surface.cell_remap.len();
     tui_surface
         .cell_remap
         .resize(bounds.height as usize * bounds.width as usize, 0);
+    reset_cell_remap(
+        tui_surface,
+        remap_len..tui_surface.cell_remap.len(),
+        bounds.width,
+    );
     tui_surface
         .fast_blinking
         .resize(bounds.height as usize * bounds.width as usize, false);
@@ -962,6 +2845,9 @@ fn draw_tui(
     tui_surface
         .dirty_cells
         .resize(bounds.height as usize * bounds.width as usize, true);
+    tui_surface
+        .line_scale
+        .resize(bounds.height as usize, LineScale::Normal);
 
     let cell_box = fonts.cell_box();
 
@@ -1004,14 +2890,7 @@ fn draw_tui(
         tui_surface.cell_font[index] = fonts.font_for_cell(cell);
         tui_surface.dirty_cells.set(index, true);
 
-        let new_symbol_width = tui_surface.cells[index]
-            .symbol()
-            .chars()
-            .filter(|c| c.general_category() != GeneralCategory::Format)
-            .next()
-            .unwrap_or(' ')
-            .width()
-            .unwrap_or(1);
+        let new_symbol_width = symbol_width(tui_surface.cells[index].symbol());
         if index + 1 < index + new_symbol_width {
             tui_surface.cells[index + 1..index + new_symbol_width].fill(NULL_CELL);
             tui_surface.dirty_cells[index + 1..index + new_symbol_width].fill(true);
@@ -1029,14 +2908,21 @@ fn draw_tui(
             view_rect,
             view_clip,
             below_text,
+            z,
             tr,
         } in image_buffer.images.iter()
         {
+            if !image_buffer.image_size.contains_key(image_id) {
+                // stale handle
+                continue;
+            }
+
             let img_info = ImageInfo {
                 image_id: *image_id,
                 view_rect: *view_rect,
                 view_clip: *view_clip,
                 below_text: *below_text,
+                z: *z,
                 uv_transform: *tr,
             };
 
@@ -1051,6 +2937,7 @@ fn draw_tui(
                 let test = tui_surface.images[pos];
 
                 if test.below_text != img_info.below_text
+                    || test.z != img_info.z
                     || test.uv_transform != img_info.uv_transform
                 {
                     // existing image differs in render parameters.
@@ -1109,16 +2996,11 @@ fn draw_tui(
 
 fn flush_tui(
     bounds: ratatui_core::layout::Size,
-    fonts: &Fonts<'_>,
     tui_surface: &mut TuiSurface,
     rendered: &mut Vec<Rendered>,
-    wgpu_atlas: &mut WgpuAtlas,
-    queue: &Queue,
-    //
-    tmp_plan_cache: &mut PlanCache,
     tmp_rowbuf: &mut String,
     tmp_rowbuf_to_cell: &mut Vec<u16>,
-    tmp_buffer: &mut UnicodeBuffer,
+    shaper: &mut Shaper<'_, '_>,
 ) {
     // always show cursor on flush.
     tui_surface.cursor_showing = true;
@@ -1129,6 +3011,8 @@ fn flush_tui(
         return;
     }
 
+    let settings = ShapeSettings::new(shaper.fonts, tui_surface);
+
     for (row_idx, row_cells) in tui_surface.cells.chunks(bounds.width as usize).enumerate() {
         if !tui_surface.dirty_rows[row_idx] {
             continue;
@@ -1137,27 +3021,35 @@ fn flush_tui(
         let row_offset = row_idx * bounds.width as usize;
 
         // This block concatenates the strings for the row into one string for bidi
-        // resolution, then maps bytes for the string to their associated cell index. It
-        // also maps the row's cell index to the font that can source all glyphs for
-        // that cell.
-        tmp_rowbuf.clear();
-        tmp_rowbuf_to_cell.clear();
-
-        for (cell_idx, cell) in row_cells.iter().enumerate() {
-            if !cell.skip {
-                tmp_rowbuf.push_str(cell.symbol());
-                tmp_rowbuf_to_cell.resize(
-                    tmp_rowbuf_to_cell.len() + cell.symbol().len(),
-                    cell_idx as u16,
-                );
-            }
-
+        // resolution, then maps bytes for the string to their associated cell index.
+        row_text(
+            row_cells,
+            tui_surface.control_chars,
+            tmp_rowbuf,
+            tmp_rowbuf_to_cell,
+        );
+        for cell_idx in 0..row_cells.len() {
             tui_surface.cell_remap[row_offset + cell_idx] = cell_idx as u16;
         }
 
         // run text shaping
-        let bidi = ParagraphBidiInfo::new(&tmp_rowbuf, None);
-        let (levels, runs) = bidi.visual_runs(0..bidi.levels.len());
+        let (levels, runs) = if tui_surface.bidi {
+            let base_level = match tui_surface.base_direction {
+                BaseDirection::Auto => None,
+                BaseDirection::Ltr => Some(Level::ltr()),
+                BaseDirection::Rtl => Some(Level::rtl()),
+            };
+            let bidi = ParagraphBidiInfo::new(&tmp_rowbuf, base_level);
+            bidi.visual_runs(0..bidi.levels.len())
+        } else if tmp_rowbuf.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            // one ltr run. levels are only looked up at the start of a run.
+            (
+                vec![Level::ltr()],
+                std::iter::once(0..tmp_rowbuf.len()).collect(),
+            )
+        };
 
         // when bidi kicks in dirty_cell ceases to work...
         if runs.len() > 1 {
@@ -1173,12 +3065,12 @@ fn flush_tui(
             }
         }
 
-        let mut current_font_id = None;
-        let mut current_level = None;
+        // map each cell to its visible cell.
         let mut current_cell_idx = -1;
-        for (level, range) in runs.into_iter().map(|run| (levels[run.start], run)) {
+        for range in runs.iter().cloned() {
+            let level = levels[range.start];
             let bidi_run_chars = &tmp_rowbuf[range.clone()];
-            let bidi_run_cells = &tmp_rowbuf_to_cell[range.clone()];
+            let bidi_run_cells = &tmp_rowbuf_to_cell[range];
             let min_cell_idx = *bidi_run_cells.first().expect("first") as usize;
             let max_cell_idx = *bidi_run_cells.last().expect("last") as usize;
             let mut start_cell_idx = None;
@@ -1191,49 +3083,11 @@ fn flush_tui(
 
                 let cell_idx = bidi_run_cells[ch_idx] as usize;
 
-                let font_id = tui_surface.cell_font[row_offset + cell_idx];
-                if let (Some(current_font_id), Some(current_level)) =
-                    (current_font_id, current_level)
-                    && (font_id != current_font_id || level != current_level)
-                {
-                    let mut buffer = mem::take(tmp_buffer);
-                    let current_font = fonts.get_by_id(current_font_id);
-
-                    *tmp_buffer = shape(
-                        row_idx,
-                        row_cells,
-                        &tui_surface.dirty_cells[row_offset..row_offset + bounds.width as usize],
-                        &tui_surface.cell_remap[row_offset..row_offset + bounds.width as usize],
-                        &tmp_rowbuf,
-                        &tmp_rowbuf_to_cell,
-                        shape_with_plan(
-                            current_font.face(),
-                            tmp_plan_cache.get(current_font_id, current_font, &mut buffer),
-                            buffer,
-                        ),
-                        current_font_id,
-                        fonts.cell_box(),
-                        current_font,
-                        tui_surface.cursor_visible,
-                        tui_surface.cursor,
-                        &mut rendered[row_offset..row_offset + bounds.width as usize],
-                        wgpu_atlas,
-                        queue,
-                    );
-                }
-
                 if current_cell_idx == -1 {
                     current_cell_idx += 1;
                 } else if current_cell_idx != cell_idx as i32 {
-                    let symbol_width = row_cells[current_cell_idx as usize]
-                        .symbol()
-                        .chars()
-                        .filter(|c| c.general_category() != GeneralCategory::Format)
-                        .next()
-                        .unwrap_or(' ')
-                        .width()
-                        .unwrap_or(1);
-                    current_cell_idx = current_cell_idx + symbol_width as i32;
+                    let width = symbol_width(row_cells[current_cell_idx as usize].symbol());
+                    current_cell_idx = current_cell_idx + width as i32;
                 }
 
                 if start_cell_idx.is_none() {
@@ -1258,45 +3112,255 @@ fn flush_tui(
                     }
                     tui_surface.cell_remap[row_offset + cell_idx] = current_cell_idx as u16;
                 }
-
-                tmp_buffer.add(ch, (range.start + ch_idx) as u32);
-
-                current_font_id = Some(font_id);
-                current_level = Some(level);
             }
         }
 
-        if let Some(current_font_id) = current_font_id {
-            let mut buffer = mem::take(tmp_buffer);
-            let current_font = fonts.get_by_id(current_font_id);
-            *tmp_buffer = shape(
+        shape_segments(
+            &ShapeRow::new(
+                tui_surface,
                 row_idx,
-                row_cells,
-                &tui_surface.dirty_cells[row_offset..row_offset + bounds.width as usize],
-                &tui_surface.cell_remap[row_offset..row_offset + bounds.width as usize],
-                &tmp_rowbuf,
+                bounds.width as usize,
+                tmp_rowbuf,
                 tmp_rowbuf_to_cell,
-                shape_with_plan(
-                    current_font.face(),
-                    tmp_plan_cache.get(current_font_id, current_font, &mut buffer),
-                    buffer,
-                ),
-                current_font_id,
-                fonts.cell_box(),
-                current_font,
-                tui_surface.cursor_visible,
-                tui_surface.cursor,
-                &mut rendered[row_offset..row_offset + bounds.width as usize],
-                wgpu_atlas,
-                queue,
-            );
+            ),
+            &settings,
+            &levels,
+            &runs,
+            &mut rendered[row_offset..row_offset + bounds.width as usize],
+            shaper,
+        );
+    }
+}
+
+// concatenate the symbols of a row, and map each byte of the
+// string to its cell.
+fn row_text(
+    row_cells: &[Cell],
+    control_chars: ControlCharDisplay,
+    buf_str: &mut String,
+    buf_to_cell: &mut Vec<u16>,
+) {
+    buf_str.clear();
+    buf_to_cell.clear();
+    for (cell_idx, cell) in row_cells.iter().enumerate() {
+        if !cell.skip {
+            control_chars.push_symbol(buf_str, cell.symbol());
+            buf_to_cell.resize(buf_str.len(), cell_idx as u16);
+        }
+    }
+}
+
+// the caches and scratch space for shaping, and the atlas
+// for the rasterized glyphs.
+struct Shaper<'a, 'f> {
+    fonts: &'a Fonts<'f>,
+    plan_cache: &'a mut PlanCache,
+    shape_cache: &'a mut ShapeCache,
+    buffer: &'a mut UnicodeBuffer,
+    wgpu_atlas: &'a mut WgpuAtlas,
+    queue: &'a Queue,
+}
+
+// one row as far as shaping is concerned.
+struct ShapeRow<'a> {
+    row_idx: usize,
+    cells: &'a [Cell],
+    dirty_cells: &'a BitSlice,
+    // cell index to visible cell index.
+    cell_remap: &'a [u16],
+    cell_font: &'a [u64],
+    line_scale: LineScale,
+    // the concatenated symbols of the row.
+    buf_str: &'a str,
+    // byte index in buf_str to cell index.
+    buf_to_cell: &'a [u16],
+}
+
+impl<'a> ShapeRow<'a> {
+    fn new(
+        tui_surface: &'a TuiSurface,
+        row_idx: usize,
+        width: usize,
+        buf_str: &'a str,
+        buf_to_cell: &'a [u16],
+    ) -> Self {
+        let row_range = row_idx * width..(row_idx + 1) * width;
+        Self {
+            row_idx,
+            cells: &tui_surface.cells[row_range.clone()],
+            dirty_cells: &tui_surface.dirty_cells[row_range.clone()],
+            cell_remap: &tui_surface.cell_remap[row_range.clone()],
+            cell_font: &tui_surface.cell_font[row_range],
+            line_scale: tui_surface.line_scale[row_idx],
+            buf_str,
+            buf_to_cell,
+        }
+    }
+}
+
+// the glyphs of one segment of a row.
+#[derive(Clone, Copy)]
+struct ShapedSegment<'a, 'f> {
+    glyphs: &'a Shaped,
+    // the clusters of the glyphs are relative to this.
+    cluster_offset: usize,
+    font: &'a Font<'f>,
+}
+
+// the settings for shaping that are the same for all rows.
+#[derive(Debug, Clone, Copy)]
+struct ShapeSettings {
+    cell_box: CellBox,
+    strikeout_metrics: (u32, u32),
+    modifier_remap: ModifierRemap,
+    builtin_box_drawing: bool,
+    control_chars: ControlCharDisplay,
+    text_outline: f32,
+    raster: RasterSettings,
+    cursor_visible: bool,
+    cursor: (u16, u16),
+}
+
+impl ShapeSettings {
+    fn new(fonts: &Fonts<'_>, tui_surface: &TuiSurface) -> Self {
+        Self {
+            cell_box: fonts.cell_box(),
+            strikeout_metrics: fonts.strikeout_metrics(),
+            modifier_remap: tui_surface.modifier_remap,
+            builtin_box_drawing: tui_surface.builtin_box_drawing,
+            control_chars: tui_surface.control_chars,
+            text_outline: tui_surface.text_outline.0,
+            raster: fonts.raster_settings(),
+            cursor_visible: tui_surface.cursor_visible,
+            cursor: tui_surface.cursor,
+        }
+    }
+}
+
+// shape the bidi runs of a row in segments.
+//
+// a segment ends when the font or the bidi level changes. it
+// also ends after a blank, this keeps changes in one word from
+// re-shaping the whole row.
+fn shape_segments(
+    row: &ShapeRow<'_>,
+    settings: &ShapeSettings,
+    levels: &[Level],
+    runs: &[Range<usize>],
+    rendered: &mut [Rendered],
+    shaper: &mut Shaper<'_, '_>,
+) {
+    let mut current_font_id = None;
+    let mut current_level = None;
+    let mut segment_start = None;
+    let mut segment_end = 0;
+    let mut last_char = None;
+    let mut last_char_cell = 0;
+    for range in runs.iter().cloned() {
+        let level = levels[range.start];
+        for (ch_idx, ch) in row.buf_str[range.clone()].char_indices() {
+            if ch.general_category() == GeneralCategory::Format {
+                // skip Format, no longer needed after bidi.
+                continue;
+            }
+
+            let cell_idx = row.buf_to_cell[range.start + ch_idx] as usize;
+            let font_id = row.cell_font[cell_idx];
+            let after_blank = last_char == Some(' ') && ch != ' ' && last_char_cell != cell_idx;
+            if let (Some(current_font_id), Some(current_level), Some(start)) =
+                (current_font_id, current_level, segment_start)
+                && (font_id != current_font_id || level != current_level || after_blank)
+            {
+                shape_segment(
+                    row,
+                    settings,
+                    start..segment_end,
+                    current_font_id,
+                    rendered,
+                    shaper,
+                );
+                segment_start = None;
+            }
+
+            shaper.buffer.add(ch, (range.start + ch_idx) as u32);
+            segment_start.get_or_insert(range.start + ch_idx);
+            segment_end = range.start + ch_idx + ch.len_utf8();
+            last_char = Some(ch);
+            last_char_cell = cell_idx;
+
+            current_font_id = Some(font_id);
+            current_level = Some(level);
         }
     }
+
+    if let (Some(current_font_id), Some(start)) = (current_font_id, segment_start) {
+        shape_segment(
+            row,
+            settings,
+            start..segment_end,
+            current_font_id,
+            rendered,
+            shaper,
+        );
+    }
+}
+
+// shape one segment of a row.
+//
+// the chars of the segment have already been added to the buffer.
+// segments without dirty cells are skipped, the others use the
+// shaping cache before running rustybuzz.
+fn shape_segment(
+    row: &ShapeRow<'_>,
+    settings: &ShapeSettings,
+    segment: Range<usize>,
+    font_id: u64,
+    rendered: &mut [Rendered],
+    shaper: &mut Shaper<'_, '_>,
+) {
+    if !row.buf_to_cell[segment.clone()]
+        .iter()
+        .any(|cell_idx| row.dirty_cells[*cell_idx as usize])
+    {
+        shaper.buffer.clear();
+        return;
+    }
+
+    let font = shaper.fonts.get_by_id(font_id);
+    let glyphs = shaper
+        .shape_cache
+        .get_or_shape(font_id, &row.buf_str[segment.clone()], || {
+            let mut buffer = mem::take(shaper.buffer);
+            let glyphs = shape_with_plan(
+                font.face(),
+                shaper.plan_cache.get(font_id, font, &mut buffer),
+                buffer,
+            );
+            let shaped = Shaped::new(&glyphs, segment.start);
+            *shaper.buffer = glyphs.clear();
+            shaped
+        });
+    // a cache hit leaves the chars in the buffer.
+    shaper.buffer.clear();
+
+    shape(
+        row,
+        settings,
+        &ShapedSegment {
+            glyphs,
+            cluster_offset: segment.start,
+            font,
+        },
+        rendered,
+        shaper.wgpu_atlas,
+        shaper.queue,
+    );
 }
 
 // shape a part of one row.
 //
-// the glyphs come as a GlyphBuffer provided by the bidi algorithm.
+// the glyphs come shaped for one segment of a bidi run. their clusters
+// are relative to the cluster_offset.
 // each glyph is mapped to a cell, which in turn might be mapped to a
 // visible cell if there is any reordering during bidi.
 //
@@ -1305,63 +3369,93 @@ fn flush_tui(
 //
 // Positioning of glyphs always restarts with each new cell.
 // This ensures that the output is mostly cell-aligned and makes
-// the final result more predictable.
+// the final result more predictable. Kerning between cells is lost.
+// Glyphs after the first in a cell are moved back to the start of
+// the cell too, unless the font preserves the cluster kerning.
 fn shape(
-    row_idx: usize,
-    row: &[Cell],
-    dirty_cells: &BitSlice,
-    cell_remap: &[u16],
-    buf_str: &str,
-    buf_to_cell: &[u16],
-    buffer: GlyphBuffer,
-    font_id: u64,
-    cell_box: CellBox,
-    font: &Font<'_>,
-    cursor_visible: bool,
-    cursor: (u16, u16),
+    row: &ShapeRow<'_>,
+    settings: &ShapeSettings,
+    segment: &ShapedSegment<'_, '_>,
     rendered: &mut [Rendered],
     wgpu_atlas: &mut WgpuAtlas,
     queue: &Queue,
-) -> UnicodeBuffer {
+) {
+    let ShapedSegment {
+        glyphs,
+        cluster_offset,
+        font,
+    } = *segment;
+    let ShapeRow {
+        row_idx,
+        cells: row,
+        dirty_cells,
+        cell_remap,
+        line_scale,
+        buf_str,
+        buf_to_cell,
+        ..
+    } = *row;
+    let ShapeSettings {
+        cell_box,
+        strikeout_metrics,
+        modifier_remap,
+        builtin_box_drawing,
+        control_chars,
+        text_outline,
+        raster,
+        cursor_visible,
+        cursor,
+    } = *settings;
+
     let metrics = font.face();
+    let phases = raster.subpixel_phases.max(1) as i32;
+
+    // positions are truncated to whole pixels without sub-pixel phases.
+    let px = |v: f32| if phases > 1 { v } else { v.trunc() };
 
-    let mut x = 0;
+    let mut x = 0.0;
     let mut default_chars_wide = 1;
     #[allow(unused_assignments)]
     let mut chars_wide = 1;
     let mut last_cell_idx: Option<usize> = None;
-    let mut last_advance = 0;
-    for (info, position) in buffer
-        .glyph_infos()
-        .iter()
-        .zip(buffer.glyph_positions().iter())
-    {
-        let cell_idx = buf_to_cell[info.cluster as usize] as usize;
+    let mut last_advance = 0.0;
+    for (info, position) in glyphs.infos.iter().zip(glyphs.positions.iter()) {
+        let cluster = info.cluster as usize + cluster_offset;
+        let cell_idx = buf_to_cell[cluster] as usize;
 
         if !dirty_cells[cell_idx] {
             continue;
         }
 
         let cell = &row[cell_idx];
-        let ch = buf_str[info.cluster as usize..]
-            .chars()
-            .next()
-            .unwrap_or_default();
+        let ch = buf_str[cluster..].chars().next().unwrap_or_default();
+        // caret notation squeezes two glyphs into the cell.
+        let condensed = control_chars.is_caret(cell.symbol());
+        // all glyphs of a grapheme share the cluster of the base char,
+        // enclosing marks are found by their glyph.
+        let enclosing = last_cell_idx == Some(cell_idx)
+            && cell.symbol().chars().any(|c| {
+                c.general_category() == GeneralCategory::EnclosingMark
+                    && metrics.glyph_index(c).map(|g| g.0 as u32) == Some(info.glyph_id)
+            });
 
         // Every cell has it's defined position on the grid.
         // This position is used as a starting point from which
         // every glyph in the cell is positioned.
         let mut first_glyph = false;
         if last_cell_idx != Some(cell_idx) {
-            x = cell_remap[cell_idx] as i32 * cell_box.width as i32;
+            x = cell_remap[cell_idx] as f32 * cell_box.width as f32;
             // zero width are still 1 cell wide.
             // there is KHMER SIGN BEYYAL with width 3.
             // we ignore that one completely.
             default_chars_wide = ch.width().unwrap_or(1).max(1).min(2);
             chars_wide = default_chars_wide;
             assert_ne!(chars_wide, 0);
-            last_advance = 0;
+            last_advance = 0.0;
             first_glyph = true;
+        } else if enclosing {
+            // enclosing marks cover the whole base char.
+            chars_wide = default_chars_wide;
         } else {
             // zero width are still 1 cell wide.
             // there is KHMER SIGN BEYYAL with width 3.
@@ -1378,19 +3472,32 @@ fn shape(
         }
 
         let block_char = (ch as u32) >= 0x2500 && (ch as u32) <= 0x259F;
+        let builtin = builtin_box_drawing && is_box_char(ch);
         let advance_scale = font.scale_x(info.glyph_id as u16, block_char, chars_wide as u32);
+        let advance_scale = if condensed {
+            advance_scale / 2.0
+        } else {
+            advance_scale
+        };
         let advance_scale_y = font.scale_y(info.glyph_id as u16, block_char);
 
-        let basey = row_idx as i32 * cell_box.height as i32
-            + (position.y_offset as f32 * advance_scale_y) as i32;
+        let basey = if enclosing || builtin {
+            row_idx as i32 * cell_box.height as i32
+        } else {
+            row_idx as i32 * cell_box.height as i32
+                + (position.y_offset as f32 * advance_scale_y) as i32
+        };
 
-        let glyph_advance = (position.x_advance as f32 * advance_scale) as i32;
-        let glyph_offset = (position.x_offset as f32 * advance_scale) as i32;
+        let glyph_advance = px(position.x_advance as f32 * advance_scale);
+        let glyph_offset = px(position.x_offset as f32 * advance_scale);
 
         // combining glyph
         let basex;
-        if last_cell_idx == Some(cell_idx) {
-            if glyph_offset < 0 {
+        if enclosing {
+            // scaled and centered over the cell by the rasterizer.
+            basex = cell_remap[cell_idx] as f32 * cell_box.width as f32;
+        } else if last_cell_idx == Some(cell_idx) {
+            if glyph_offset < 0.0 || raster.preserve_cluster_kerning {
                 basex = x + glyph_offset;
                 last_advance += glyph_advance;
                 x += glyph_advance;
@@ -1407,13 +3514,48 @@ fn shape(
 
         last_cell_idx = Some(cell_idx);
 
-        let key = Key {
-            style: cell
-                .modifier
-                .intersection(Modifier::BOLD | Modifier::ITALIC),
-            glyph: info.glyph_id,
-            width: chars_wide as u8,
-            font: font_id,
+        // builtin box chars fill the cell.
+        // condensed glyphs take one half of the cell each.
+        let basex = if builtin {
+            cell_remap[cell_idx] as f32 * cell_box.width as f32
+        } else if condensed {
+            let half = if first_glyph { 0.0 } else { 0.5 };
+            (cell_remap[cell_idx] as f32 + half) * cell_box.width as f32
+        } else {
+            basex
+        };
+
+        // split into whole pixels and the nearest phase.
+        let steps = (basex * phases as f32).round() as i32;
+        let phase = steps.rem_euclid(phases) as u8;
+        let basex = steps.div_euclid(phases);
+
+        // scaled rows stretch everything from the left edge.
+        let basex = basex * line_scale.scale_x();
+
+        let key = if builtin {
+            // the same for all fonts.
+            GlyphKey {
+                style: Modifier::empty(),
+                glyph: ch as u32,
+                width: chars_wide as u8,
+                font: BOX_DRAWING_FONT_ID,
+                phase: 0,
+                condensed: false,
+                enclosing: false,
+            }
+        } else {
+            GlyphKey {
+                style: cell
+                    .modifier
+                    .intersection(Modifier::BOLD | Modifier::ITALIC),
+                glyph: info.glyph_id,
+                width: chars_wide as u8,
+                font: font.id(),
+                phase,
+                condensed,
+                enclosing,
+            }
         };
 
         let cached =
@@ -1434,18 +3576,26 @@ fn shape(
                 (0, 0)
             };
 
-        let underline_pos = if view_modifier.contains(Modifier::UNDERLINED) {
+        let underline_pos = if view_modifier.contains(Modifier::UNDERLINED)
+            || (first_glyph && view_modifier.intersects(modifier_remap.double_underline))
+        {
             font.underline_metrics(cell_box.ascender, cached.height)
         } else {
             (0, 0)
         };
         let strikeout_pos = if view_modifier.contains(Modifier::CROSSED_OUT) {
-            font.strikeout_metrics(cell_box.ascender)
+            strikeout_metrics
+        } else {
+            (0, 0)
+        };
+        let overline_pos = if first_glyph && view_modifier.intersects(modifier_remap.overline) {
+            font.overline_metrics(cached.height)
         } else {
             (0, 0)
         };
 
         if cached.cached() {
+            wgpu_atlas.cache_hits += 1;
             rendered[cell_idx].push((
                 basex,
                 basey,
@@ -1459,8 +3609,11 @@ fn shape(
                     underline_pos_max: underline_pos.1 as u16,
                     strikeout_pos_min: strikeout_pos.0 as u16,
                     strikeout_pos_max: strikeout_pos.1 as u16,
+                    overline_pos_min: overline_pos.0 as u16,
+                    overline_pos_max: overline_pos.1 as u16,
                     cursor_pos_min: cursor_pos.0 as u16,
                     cursor_pos_max: cursor_pos.1 as u16,
+                    line_scale,
                 },
             ));
 
@@ -1470,23 +3623,42 @@ fn shape(
         let is_emoji =
             ch.is_emoji_char() && ch.general_category_group() != GeneralCategoryGroup::Number;
 
-        let (cached, image) = rasterize_glyph(
-            cached,
-            metrics,
-            info,
-            view_modifier.contains(Modifier::BOLD),
-            view_modifier.contains(Modifier::ITALIC),
-            advance_scale,
-            advance_scale_y,
-            cell_box.ascender,
-            is_emoji,
-            block_char,
-            ch.general_category(),
-            font.is_fallback(),
-        );
+        let (cached, image) = if builtin {
+            (
+                CacheRect {
+                    color: false,
+                    ..*cached
+                },
+                rasterize_box_char(ch, cached.width, cached.height),
+            )
+        } else {
+            rasterize_glyph(
+                cached,
+                metrics,
+                info,
+                &GlyphRaster {
+                    bold: view_modifier.contains(Modifier::BOLD),
+                    italic: view_modifier.contains(Modifier::ITALIC),
+                    settings: raster,
+                    aliased: cell_box.height < raster.aa_min_height_px,
+                    text_outline,
+                    subpixel_x: phase as f32 / phases as f32,
+                    advance_scale,
+                    advance_scale_y,
+                    ascender: cell_box.ascender,
+                    emoji: is_emoji,
+                    block_char,
+                    enclosing,
+                    condensed,
+                    category: ch.general_category(),
+                    is_fallback: font.is_fallback(),
+                },
+            )
+        };
 
         // remember colored flag for the glyph.
         wgpu_atlas.cached.update_colored(&key, cached.color);
+        wgpu_atlas.rasterized += 1;
 
         rendered[cell_idx].push((
             basex,
@@ -1501,8 +3673,11 @@ fn shape(
                 underline_pos_max: underline_pos.1 as u16,
                 strikeout_pos_min: strikeout_pos.0 as u16,
                 strikeout_pos_max: strikeout_pos.1 as u16,
+                overline_pos_min: overline_pos.0 as u16,
+                overline_pos_max: overline_pos.1 as u16,
                 cursor_pos_min: cursor_pos.0 as u16,
                 cursor_pos_max: cursor_pos.1 as u16,
+                line_scale,
             },
         ));
 
@@ -1530,20 +3705,10 @@ fn shape(
             },
         );
     }
-
-    buffer.clear()
 }
 
-fn flush_blink(
-    blinking: Blinking,
-    bounds: ratatui_core::layout::Size,
-    cell_box: CellBox,
-    tui_surface: &mut TuiSurface,
-    rendered: &Vec<Rendered>,
-    wgpu_vertices: &mut WgpuVertices,
-) {
-    wgpu_vertices.clear();
-
+// count the blink and switch the blinking text and cursor.
+fn advance_blink(tui_surface: &mut TuiSurface) {
     tui_surface.blink = tui_surface.blink.wrapping_add(1);
     if tui_surface.fast_blink_divisor != 0
         && tui_surface.blink % tui_surface.fast_blink_divisor == 0
@@ -1561,6 +3726,18 @@ fn flush_blink(
     {
         tui_surface.cursor_showing = !tui_surface.cursor_showing;
     }
+}
+
+// collect the vertices for the blinking cells.
+fn flush_blink(
+    blinking: Blinking,
+    bounds: ratatui_core::layout::Size,
+    cell_box: CellBox,
+    tui_surface: &mut TuiSurface,
+    rendered: &Vec<Rendered>,
+    wgpu_vertices: &mut WgpuVertices,
+) {
+    wgpu_vertices.clear();
 
     let mut cell_indexes = if blinking & Blinking::TEXT {
         tui_surface
@@ -1580,7 +3757,15 @@ fn flush_blink(
     let mut index_offset = 0;
     for index in cell_indexes.iter() {
         if let Some(to_render) = rendered.get(*index) {
-            append_rendered(&tui_surface, to_render, &mut index_offset, wgpu_vertices);
+            let selected = is_selected(tui_surface, bounds, *index);
+            append_rendered(
+                &tui_surface,
+                to_render,
+                cell_pos(tui_surface, bounds, cell_box, *index),
+                selected,
+                &mut index_offset,
+                wgpu_vertices,
+            );
         }
     }
 
@@ -1622,9 +3807,22 @@ fn flush_blink(
             tui_surface.dirty_rows.set(y as usize, true);
         }
     }
+
+    if tui_surface.cursor_visible && blinking & Blinking::CURSOR {
+        append_cursor_image(
+            bounds,
+            cell_box,
+            tui_surface,
+            rendered,
+            &mut index_offset,
+            wgpu_vertices,
+        );
+    }
 }
 
 fn append_dirty_rows(
+    bounds: ratatui_core::layout::Size,
+    cell_box: CellBox,
     tui_surface: &mut TuiSurface,
     wgpu_post_process: &dyn PostProcessor,
     rendered: &Vec<Rendered>,
@@ -1639,14 +3837,42 @@ fn append_dirty_rows(
         let mut index_offset = 0;
         for cell_idx in tui_surface.dirty_cells.iter_ones() {
             let to_render = &rendered[cell_idx];
-            append_rendered(tui_surface, to_render, &mut index_offset, wgpu_vertices);
+            let selected = is_selected(tui_surface, bounds, cell_idx);
+            append_rendered(
+                tui_surface,
+                to_render,
+                cell_pos(tui_surface, bounds, cell_box, cell_idx),
+                selected,
+                &mut index_offset,
+                wgpu_vertices,
+            );
         }
+        append_inset(
+            bounds,
+            cell_box,
+            tui_surface,
+            &mut index_offset,
+            wgpu_vertices,
+        );
 
         let mut index_offset = 0;
         for img_info in tui_surface.dirty_img.iter() {
             append_rendered_image(img_info, &mut index_offset, wgpu_vertices);
         }
 
+        let cursor_idx =
+            tui_surface.cursor.1 as usize * bounds.width as usize + tui_surface.cursor.0 as usize;
+        if tui_surface.dirty_cells.get(cursor_idx).is_some_and(|v| *v) {
+            append_cursor_image(
+                bounds,
+                cell_box,
+                tui_surface,
+                rendered,
+                &mut index_offset,
+                wgpu_vertices,
+            );
+        }
+
         tui_surface
             .dirty_rows
             .iter_mut()
@@ -1659,6 +3885,109 @@ fn append_dirty_rows(
     }
 }
 
+// The image of CursorStyle::Image above the cursor cell. This must
+// come with the cursor cell, which paints over it when the cursor
+// hides again.
+fn append_cursor_image(
+    bounds: ratatui_core::layout::Size,
+    cell_box: CellBox,
+    tui_surface: &TuiSurface,
+    rendered: &[Rendered],
+    index_offset: &mut u32,
+    vertices: &mut WgpuVertices,
+) {
+    if tui_surface.cursor_style != CursorStyle::Image {
+        return;
+    }
+    let Some(handle) = &tui_surface.cursor_image else {
+        return;
+    };
+    if !tui_surface.cursor_visible || !tui_surface.cursor_showing {
+        return;
+    }
+
+    let (cursor_x, cursor_y) = tui_surface.cursor;
+    if cursor_x >= bounds.width || cursor_y >= bounds.height {
+        return;
+    }
+    let cell_idx = cursor_y as usize * bounds.width as usize + cursor_x as usize;
+    // the cell tells the width and line scale of the cursor.
+    let Some((_, _, _, info)) = rendered.get(cell_idx).and_then(|v| v.first()) else {
+        return;
+    };
+
+    let visual_x = tui_surface
+        .cell_remap
+        .get(cell_idx)
+        .copied()
+        .unwrap_or(cursor_x);
+    let scale_x = info.line_scale.scale_x();
+    let view_rect = (
+        visual_x as i32 * cell_box.width as i32 * scale_x,
+        cursor_y as i32 * cell_box.height as i32,
+        info.cached.width * scale_x as u32,
+        cell_box.height,
+    );
+
+    append_rendered_image(
+        &ImageInfo {
+            image_id: handle.id(),
+            view_rect,
+            view_clip: view_rect,
+            below_text: false,
+            // always on top.
+            z: i32::MAX,
+            uv_transform: Default::default(),
+        },
+        index_offset,
+        vertices,
+    );
+}
+
+// Fills the top and left inset of the text layer with the margin
+// color. Right and bottom are filled by the post-processor.
+// These are bg-only quads and must come after all cells.
+fn append_inset(
+    bounds: ratatui_core::layout::Size,
+    cell_box: CellBox,
+    tui_surface: &TuiSurface,
+    index_offset: &mut u32,
+    vertices: &mut WgpuVertices,
+) {
+    let inset = tui_surface.inset;
+    let margin_color = tui_surface.margin_color.unwrap_or(tui_surface.reset_bg);
+    let color = u32::from_le_bytes([margin_color[0], margin_color[1], margin_color[2], 255]);
+
+    let left = inset.left as f32;
+    let top = inset.top as f32;
+    let width = bounds.width as f32 * cell_box.width as f32;
+    let height = bounds.height as f32 * cell_box.height as f32;
+
+    // relative to the cell grid.
+    let strips = [
+        (top > 0.0).then_some((-left, -top, left + width, top)),
+        (left > 0.0).then_some((-left, 0.0, left, height)),
+    ];
+    for (x, y, w, h) in strips.into_iter().flatten() {
+        vertices.text_indices.push([
+            *index_offset,     // x, y
+            *index_offset + 1, // x + w, y
+            *index_offset + 2, // x, y + h
+            *index_offset + 2, // x, y + h
+            *index_offset + 3, // x + w, y + h
+            *index_offset + 1, // x + w, y
+        ]);
+        *index_offset += 4;
+
+        for vertex in [[x, y], [x + w, y], [x, y + h], [x + w, y + h]] {
+            vertices.bg_vertices.push(TextBgVertexMember {
+                vertex,
+                bg_color: color,
+            });
+        }
+    }
+}
+
 fn append_rendered_image(
     to_render: &ImageInfo,
     index_offset: &mut u32,
@@ -1701,9 +4030,47 @@ fn append_rendered_image(
     });
 }
 
+// The selection uses visual positions, bidi may have moved
+// the cell elsewhere.
+fn is_selected(
+    tui_surface: &TuiSurface,
+    bounds: ratatui_core::layout::Size,
+    cell_idx: usize,
+) -> bool {
+    if tui_surface.selection.is_empty() {
+        return false;
+    }
+
+    let width = bounds.width as usize;
+    let row_offset = cell_idx - cell_idx % width;
+    let visual_idx = row_offset + tui_surface.cell_remap[cell_idx] as usize;
+
+    tui_surface.selection.iter().any(|(start, end)| {
+        let start = start.y as usize * width + start.x as usize;
+        let end = end.y as usize * width + end.x as usize;
+        (start.min(end)..=start.max(end)).contains(&visual_idx)
+    })
+}
+
+// Top left of the cell on the text layer, after bidi reordering.
+fn cell_pos(
+    tui_surface: &TuiSurface,
+    bounds: ratatui_core::layout::Size,
+    cell_box: CellBox,
+    cell_idx: usize,
+) -> [f32; 2] {
+    let row = cell_idx / bounds.width as usize;
+    [
+        tui_surface.cell_remap[cell_idx] as f32 * cell_box.width as f32,
+        row as f32 * cell_box.height as f32,
+    ]
+}
+
 fn append_rendered(
     tui_surface: &TuiSurface,
     to_render: &Rendered,
+    cell_pos: [f32; 2],
+    selected: bool,
     index_offset: &mut u32,
     vertices: &mut WgpuVertices,
 ) {
@@ -1720,8 +4087,11 @@ fn append_rendered(
             underline_pos_max,
             strikeout_pos_min,
             strikeout_pos_max,
+            overline_pos_min,
+            overline_pos_max,
             cursor_pos_min,
             cursor_pos_max,
+            line_scale,
         },
     ) in to_render.iter()
     {
@@ -1730,19 +4100,49 @@ fn append_rendered(
             | (modifier.contains(Modifier::SLOW_BLINK) && !tui_surface.slow_blink_showing)
         {
             0
-        } else if modifier.contains(Modifier::DIM) {
-            127
         } else {
             255
         };
 
-        let reverse = modifier.contains(Modifier::REVERSED);
-        let fg_color = if reverse {
-            tui_surface.colors.c2c(*bg, tui_surface.reset_bg)
+        // from here on fg_color and bg_color are the colors after
+        // reversing. the glyph, underline, strikeout, overline and the
+        // default cursor color all use this fg_color. the bg of a reversed
+        // cell is the opaque fg of the cell and hides images below the text.
+        let reverse = modifier.contains(Modifier::REVERSED) != tui_surface.invert_colors;
+        let (fg_color, bg_color) = if reverse {
+            (
+                tui_surface.colors.c2c(*bg, tui_surface.reset_bg),
+                tui_surface.colors.c2c(*fg, tui_surface.reset_fg),
+            )
+        } else {
+            (
+                tui_surface.colors.c2c(*fg, tui_surface.reset_fg),
+                tui_surface.colors.c2c(*bg, tui_surface.reset_bg),
+            )
+        };
+        let (fg_color, bg_color) = if tui_surface.high_contrast {
+            high_contrast(fg_color, bg_color)
+        } else {
+            (fg_color, bg_color)
+        };
+        let bg_color = if selected {
+            let selection_color = tui_surface
+                .colors
+                .c2c(tui_surface.selection_color, tui_surface.reset_fg);
+            dim(selection_color, bg_color)
+        } else {
+            bg_color
+        };
+        // blend toward the background instead of using alpha,
+        // otherwise anything below the glyph shines through.
+        let fg_color = if modifier.contains(Modifier::DIM) {
+            dim(fg_color, bg_color)
         } else {
-            tui_surface.colors.c2c(*fg, tui_surface.reset_fg)
+            fg_color
         };
+
         let fg_color_u32: u32 = u32::from_le_bytes([fg_color[0], fg_color[1], fg_color[2], alpha]);
+        let fg_line_u32: u32 = u32::from_le_bytes([fg_color[0], fg_color[1], fg_color[2], 255]);
 
         let cursor_color_u32 = if tui_surface.cursor_color != ratatui_core::style::Color::Reset {
             let cur_color = tui_surface
@@ -1753,17 +4153,20 @@ fn append_rendered(
             u32::from_le_bytes([fg_color[0], fg_color[1], fg_color[2], 99])
         };
 
-        let bg_color = if reverse {
-            tui_surface.colors.c2c(*fg, tui_surface.reset_fg)
-        } else {
-            tui_surface.colors.c2c(*bg, tui_surface.reset_bg)
-        };
         let bg_color_u32 = u32::from_le_bytes([bg_color[0], bg_color[1], bg_color[2], 255]);
 
-        let underline_pos =
-            ((*underline_pos_min as u32 + cached.y) << 16) | (*underline_pos_max as u32 + cached.y);
-        let strikeout_pos =
-            ((*strikeout_pos_min as u32 + cached.y) << 16) | (*strikeout_pos_max as u32 + cached.y);
+        // outlined glyphs have the coverage of the fill in the colors.
+        let (outline_width, outline_color) = tui_surface.text_outline;
+        let color_glyph = if cached.color {
+            1
+        } else if outline_width > 0.0 {
+            2
+        } else {
+            0
+        };
+        let outline_color = tui_surface.colors.c2c(outline_color, tui_surface.reset_bg);
+        let outline_color_u32 =
+            u32::from_le_bytes([outline_color[0], outline_color[1], outline_color[2], 255]);
 
         let mut cursor_pos = 0x0000_0000;
         if tui_surface.cursor_visible
@@ -1805,6 +4208,9 @@ fn append_rendered(
                         | cached.width << 8
                         | (cached.width.saturating_sub(cursor_width + 3))
                 }
+                CursorStyle::Image => {
+                    // rendered with the images.
+                }
             }
         }
 
@@ -1820,10 +4226,81 @@ fn append_rendered(
 
         let x = *x as f32;
         let y = *y as f32;
-        let width = cached.width as f32;
-        let height = cached.height as f32;
+        let uv_width = cached.width as f32;
+        let uv_height = cached.height as f32;
         let uvx = cached.x as f32;
         let uvy = cached.y as f32;
+        // double height rows show one half of the glyph each.
+        let (uvy, uv_height) = match line_scale {
+            LineScale::Normal | LineScale::DoubleWidth => (uvy, uv_height),
+            LineScale::DoubleHeightTop => (uvy, uv_height / 2.0),
+            LineScale::DoubleHeightBottom => (uvy + uv_height / 2.0, uv_height / 2.0),
+        };
+        let width = uv_width * line_scale.scale_x() as f32;
+        let height = cached.height as f32;
+
+        // underline, strikeout and overline span the whole cell, not
+        // only the glyph. a run of styled cells gets one unbroken line, even
+        // with spaces or glyphs that are moved by the font.
+        if alpha != 0 {
+            let line_x = cell_pos[0] * line_scale.scale_x() as f32;
+            // rows of the glyph visible in this line.
+            let v0 = uvy - cached.y as f32;
+            let v1 = v0 + uv_height;
+            let scale_y = height / uv_height;
+            // the second line of a double underline goes one thickness
+            // below the first, or above it if the cell is too short.
+            let (min, max) = (*underline_pos_min, *underline_pos_max);
+            let double_underline =
+                if min < max && modifier.intersects(tui_surface.modifier_remap.double_underline) {
+                    let thickness = max - min;
+                    if (max + 2 * thickness) as u32 <= cached.height {
+                        (max + thickness, max + 2 * thickness)
+                    } else {
+                        (
+                            min.saturating_sub(2 * thickness),
+                            min.saturating_sub(thickness),
+                        )
+                    }
+                } else {
+                    (0, 0)
+                };
+            for (min, max) in [
+                (*underline_pos_min, *underline_pos_max),
+                double_underline,
+                (*strikeout_pos_min, *strikeout_pos_max),
+                (*overline_pos_min, *overline_pos_max),
+            ] {
+                let top = (min as f32).max(v0);
+                let bottom = (max as f32).min(v1);
+                if top >= bottom {
+                    continue;
+                }
+                let top = y + (top - v0) * scale_y;
+                let bottom = y + (bottom - v0) * scale_y;
+
+                let offset = vertices.line_vertices.len() as u32;
+                vertices.line_indices.push([
+                    offset,
+                    offset + 1,
+                    offset + 2,
+                    offset + 2,
+                    offset + 3,
+                    offset + 1,
+                ]);
+                for vertex in [
+                    [line_x, top],
+                    [line_x + width, top],
+                    [line_x, bottom],
+                    [line_x + width, bottom],
+                ] {
+                    vertices.line_vertices.push(TextBgVertexMember {
+                        vertex,
+                        bg_color: fg_line_u32,
+                    });
+                }
+            }
+        }
 
         vertices.bg_vertices.push(TextBgVertexMember {
             vertex: [x, y],
@@ -1847,44 +4324,65 @@ fn append_rendered(
             uv: [uvx, uvy],
             uv_x0: uvx,
             fg_color: fg_color_u32,
-            color_glyph: cached.color as u32,
-            underline_pos,
-            strikeout_pos,
+            color_glyph,
             cursor_pos,
             cursor_color: cursor_color_u32,
+            outline_color: outline_color_u32,
         });
         vertices.text_vertices.push(TextVertexMember {
             vertex: [x + width, y],
-            uv: [uvx + width, uvy],
+            uv: [uvx + uv_width, uvy],
             uv_x0: uvx,
             fg_color: fg_color_u32,
-            color_glyph: cached.color as u32,
-            underline_pos,
-            strikeout_pos,
+            color_glyph,
             cursor_pos,
             cursor_color: cursor_color_u32,
+            outline_color: outline_color_u32,
         });
         vertices.text_vertices.push(TextVertexMember {
             vertex: [x, y + height],
-            uv: [uvx, uvy + height],
+            uv: [uvx, uvy + uv_height],
             uv_x0: uvx,
             fg_color: fg_color_u32,
-            color_glyph: cached.color as u32,
-            underline_pos,
-            strikeout_pos,
+            color_glyph,
             cursor_pos,
             cursor_color: cursor_color_u32,
+            outline_color: outline_color_u32,
         });
         vertices.text_vertices.push(TextVertexMember {
             vertex: [x + width, y + height],
-            uv: [uvx + width, uvy + height],
+            uv: [uvx + uv_width, uvy + uv_height],
             uv_x0: uvx,
             fg_color: fg_color_u32,
-            color_glyph: cached.color as u32,
-            underline_pos,
-            strikeout_pos,
+            color_glyph,
             cursor_pos,
             cursor_color: cursor_color_u32,
+            outline_color: outline_color_u32,
         });
     }
 }
+
+// Halfway between fg and bg.
+fn dim(fg: Rgb, bg: Rgb) -> Rgb {
+    [
+        ((fg[0] as u16 + bg[0] as u16) / 2) as u8,
+        ((fg[1] as u16 + bg[1] as u16) / 2) as u8,
+        ((fg[2] as u16 + bg[2] as u16) / 2) as u8,
+    ]
+}
+
+// Snap fg and bg to black/white. If both end up the same
+// the fg uses the opposite to stay readable.
+fn high_contrast(fg: Rgb, bg: Rgb) -> (Rgb, Rgb) {
+    fn snap(c: Rgb) -> Rgb {
+        let luminance = 0.2126 * c[0] as f32 + 0.7152 * c[1] as f32 + 0.0722 * c[2] as f32;
+        if luminance >= 128.0 { [255; 3] } else { [0; 3] }
+    }
+
+    let bg = snap(bg);
+    let mut fg = snap(fg);
+    if fg == bg {
+        fg = [255 - bg[0], 255 - bg[1], 255 - bg[2]];
+    }
+    (fg, bg)
+}
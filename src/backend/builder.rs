@@ -1,10 +1,11 @@
-use crate::Error;
-use crate::backend::backend::WgpuBackend;
+use crate::backend::backend::{WgpuBackend, pt_to_px, valid_empty_cell};
 use crate::backend::plan_cache::PlanCache;
+use crate::backend::shape_cache::ShapeCache;
 use crate::backend::surface::RenderSurface;
 use crate::backend::{
-    ImgPipeline, ImgVertexMember, TextBgVertexMember, TextCacheBgPipeline, TextCacheFgPipeline,
-    TextVertexMember, TuiSurface, WgpuAtlas, WgpuBase, WgpuImages, WgpuPipeline, WgpuVertices,
+    ImgPipeline, ImgVertexMember, RotationBlit, TextBgVertexMember, TextCacheBgPipeline,
+    TextCacheFgPipeline, TextVertexMember, TuiSurface, WgpuAtlas, WgpuBase, WgpuImages,
+    WgpuPipeline, WgpuVertices,
 };
 use crate::colors::ColorTable;
 use crate::cursor::CursorStyle;
@@ -13,45 +14,72 @@ use crate::image::{ImageBuffer, ImageFrame};
 use crate::postprocessor::PostProcessorBuilder;
 use crate::postprocessor::default::DefaultPostProcessorBuilder;
 use crate::text_atlas::Atlas;
-use log::info;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::util::block_on;
+use crate::{ControlCharDisplay, Error, Inset, ModifierRemap, Rotation};
+use log::{info, warn};
+use ratatui_core::buffer::Cell;
 use ratatui_core::style::Color;
 use rustybuzz::UnicodeBuffer;
 use std::num::NonZeroU64;
 use std::sync::{Arc, Mutex};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{
-    AddressMode, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry,
+    Adapter, AdapterInfo, AddressMode, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry,
     BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState,
     Buffer, BufferBindingType, BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites,
-    Device, Extent3d, FilterMode, FragmentState, Instance, InstanceDescriptor, InstanceFlags,
-    Limits, MemoryHints, MipmapFilterMode, MultisampleState, PipelineCompilationOptions,
-    PipelineLayoutDescriptor, PresentMode, PrimitiveState, PrimitiveTopology,
-    RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages,
-    Surface, SurfaceTarget, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+    CompositeAlphaMode, Device, Extent3d, FilterMode, FragmentState, Instance, InstanceDescriptor,
+    InstanceFlags, Limits, LoadOp, MemoryHints, MipmapFilterMode, MultisampleState,
+    PipelineCompilationOptions, PipelineLayoutDescriptor, PowerPreference, PresentMode,
+    PrimitiveState, PrimitiveTopology, Queue, RenderPipelineDescriptor, Sampler,
+    SamplerBindingType, SamplerDescriptor, ShaderStages, Surface, SurfaceConfiguration,
+    SurfaceTarget, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
     TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexBufferLayout,
     VertexState, VertexStepMode, include_wgsl, vertex_attr_array,
 };
 
 const CACHE_WIDTH: u32 = 1800;
 const CACHE_HEIGHT: u32 = 1200;
+// shaped runs, a few screens full.
+const SHAPE_CACHE_SIZE: usize = 4096;
 
 pub struct Builder<'a, P = DefaultPostProcessorBuilder> {
     postprocessor: P,
     fonts: Option<Fonts<'a>>,
+    font_size_pt: Option<(f32, f32)>,
     backends: Backends,
     instance: Option<Instance>,
+    device: Option<(Adapter, Device, Queue)>,
+    adapter: Option<Adapter>,
+    adapter_info: Option<AdapterInfo>,
+    power_preference: PowerPreference,
     limits: Option<Limits>,
     present_mode: Option<PresentMode>,
+    surface_format: Option<TextureFormat>,
+    alpha_mode: Option<CompositeAlphaMode>,
+    image_filter: FilterMode,
+    text_layer_format: TextureFormat,
+    text_layer_load: LoadOp<wgpu::Color>,
     width: u32,
     height: u32,
     colors: ColorTable,
     reset_fg: Color,
     reset_bg: Color,
+    margin_color: Color,
+    inset: Inset,
+    rotation: Rotation,
     fast_blink: u8,
     slow_blink: u8,
     cursor_blink: u8,
     cursor_style: CursorStyle,
     cursor_color: Color,
+    bidi: bool,
+    builtin_box_drawing: bool,
+    control_chars: ControlCharDisplay,
+    modifier_remap: ModifierRemap,
+    empty_cell: Cell,
+    text_outline: (f32, Color),
+    plan_cache_size: Option<usize>,
 }
 
 impl<'a, P> Default for Builder<'a, P>
@@ -62,20 +90,40 @@ where
         Self {
             postprocessor: Default::default(),
             fonts: Default::default(),
+            font_size_pt: None,
             backends: Default::default(),
             instance: Default::default(),
+            device: Default::default(),
+            adapter: Default::default(),
+            adapter_info: Default::default(),
+            power_preference: PowerPreference::default(),
             limits: Default::default(),
             present_mode: Default::default(),
+            surface_format: Default::default(),
+            alpha_mode: Default::default(),
+            image_filter: FilterMode::Linear,
+            text_layer_format: TextureFormat::Rgba8Unorm,
+            text_layer_load: LoadOp::Load,
             width: 100,
             height: 100,
             colors: Default::default(),
             reset_fg: Color::White,
             reset_bg: Color::Black,
+            margin_color: Color::Reset,
+            inset: Inset::default(),
+            rotation: Rotation::None,
             fast_blink: 1,
             slow_blink: 5,
             cursor_blink: 5,
             cursor_style: Default::default(),
             cursor_color: Color::Reset,
+            bidi: true,
+            builtin_box_drawing: false,
+            control_chars: ControlCharDisplay::Blank,
+            modifier_remap: ModifierRemap::default(),
+            empty_cell: Cell::EMPTY,
+            text_outline: (0.0, Color::Reset),
+            plan_cache_size: None,
         }
     }
 }
@@ -99,6 +147,8 @@ where
 
     ///
     pub fn with_fallback_fonts(mut self, fonts: Fonts<'a>) -> Self {
+        // the fonts come with their own size.
+        self.font_size_pt = None;
         self.fonts = Some(fonts);
         self
     }
@@ -136,6 +186,30 @@ where
         self.fonts = Some(Fonts::new_vec(fallback, 22));
     }
 
+    /// Use a fixed cell width in pixels instead of the advance
+    /// of the fonts. This guarantees an exact grid, but glyphs
+    /// that are wider than the cell may be clipped.
+    ///
+    /// __Note__
+    ///
+    /// Width 0 is ignored.
+    ///
+    /// __Note__
+    ///
+    /// If no fallback fonts have been set before, this will
+    /// initialize a builtin list of fallback fonts.
+    #[must_use]
+    pub fn with_cell_width_px(mut self, width: u32) -> Self {
+        if width > 0 {
+            self.init_fallback_fonts();
+            self.fonts
+                .as_mut()
+                .expect("fonts")
+                .set_cell_width_px(Some(width));
+        }
+        self
+    }
+
     /// Use the specified font size in pixels. Defaults to 24px.
     ///
     /// __Note__
@@ -149,12 +223,32 @@ where
     #[must_use]
     pub fn with_font_size_px(mut self, size: u32) -> Self {
         if size > 0 {
+            self.font_size_pt = None;
             self.init_fallback_fonts();
             self.fonts.as_mut().expect("fonts").set_height_px(size);
         }
         self
     }
 
+    /// Use the specified font size in points for the given dpi.
+    ///
+    /// This is `points * dpi / 72` pixels, see
+    /// [with_font_size_px](Self::with_font_size_px).
+    ///
+    /// __Note__
+    ///
+    /// Use the logical dpi (96 on most systems). The scale factor of
+    /// the window is applied on top, see
+    /// [set_scale_factor](WgpuBackend::set_scale_factor).
+    #[must_use]
+    pub fn with_font_size_pt(mut self, points: f32, dpi: f32) -> Self {
+        if let Some(size) = pt_to_px(points, dpi) {
+            self = self.with_font_size_px(size);
+            self.font_size_pt = Some((points, dpi));
+        }
+        self
+    }
+
     /// Use the specified list of fonts for rendering. You may call this
     /// multiple times to extend the list of fallback fonts. Note that this will
     /// automatically organize fonts by relative width in order to optimize
@@ -215,6 +309,70 @@ where
         self
     }
 
+    /// Use an existing device and queue instead of requesting a new one.
+    ///
+    /// The adapter is still needed to configure the surface. For a window
+    /// surface it must come from the same [`wgpu::Instance`], so pass that
+    /// one with [with_instance](Builder::with_instance) too.
+    ///
+    /// The build fails with [Error::DeviceLimitsNotSupported] if the device
+    /// doesn't support the [limits](Builder::with_limits).
+    #[must_use]
+    pub fn with_device_and_queue(mut self, adapter: Adapter, device: Device, queue: Queue) -> Self {
+        self.device = Some((adapter, device, queue));
+        self
+    }
+
+    /// Use the adapter with this info instead of the one wgpu picks.
+    ///
+    /// Get the info from [enumerate_adapters](Builder::enumerate_adapters).
+    /// The build fails with [Error::AdapterNotFound] if the adapter
+    /// is not available with the [backends](Builder::with_backends),
+    /// and with [Error::AdapterNotCompatible] if it can't present to
+    /// the surface.
+    ///
+    /// Ignored with [with_device_and_queue](Builder::with_device_and_queue).
+    #[must_use]
+    pub fn with_adapter_info(mut self, info: AdapterInfo) -> Self {
+        self.adapter = None;
+        self.adapter_info = Some(info);
+        self
+    }
+
+    /// Use this adapter instead of the one wgpu picks.
+    ///
+    /// For a window surface it must come from the same [`wgpu::Instance`],
+    /// so pass that one with [with_instance](Builder::with_instance) too.
+    /// The build fails with [Error::AdapterNotCompatible] if the adapter
+    /// can't present to the surface.
+    ///
+    /// Ignored with [with_device_and_queue](Builder::with_device_and_queue).
+    #[must_use]
+    pub fn with_adapter(mut self, adapter: Adapter) -> Self {
+        self.adapter_info = None;
+        self.adapter = Some(adapter);
+        self
+    }
+
+    /// Prefer a low-power or a high-performance adapter.
+    ///
+    /// Defaults to [`wgpu::PowerPreference::None`], which leaves the
+    /// choice to wgpu. Use `LowPower` to keep the discrete GPU of a
+    /// laptop asleep.
+    ///
+    /// __Info__
+    ///
+    /// This is only a preference. The adapter must still be able to
+    /// present to the surface, if only the other one can, that one is
+    /// used. Ignored with [with_adapter](Builder::with_adapter),
+    /// [with_adapter_info](Builder::with_adapter_info) and
+    /// [with_device_and_queue](Builder::with_device_and_queue).
+    #[must_use]
+    pub fn with_power_preference(mut self, preference: PowerPreference) -> Self {
+        self.power_preference = preference;
+        self
+    }
+
     /// Use the specified [`wgpu::Limits`]. Defaults to
     /// [`wgpu::Adapter::limits`].
     #[must_use]
@@ -230,6 +388,80 @@ where
         self
     }
 
+    /// Use the specified [`wgpu::TextureFormat`] for the surface.
+    ///
+    /// Defaults to the preferred format of the surface. The format
+    /// must be one of the formats supported by the surface, otherwise
+    /// building the backend fails.
+    ///
+    /// Both linear and sRGB formats are supported.
+    #[must_use]
+    pub fn with_surface_format(mut self, format: TextureFormat) -> Self {
+        self.surface_format = Some(format);
+        self
+    }
+
+    /// Use the specified [`wgpu::CompositeAlphaMode`] for the surface.
+    ///
+    /// Defaults to the mode chosen by the surface, which usually is
+    /// opaque. A transparent window needs
+    /// [`wgpu::CompositeAlphaMode::PreMultiplied`] or
+    /// [`wgpu::CompositeAlphaMode::PostMultiplied`], see
+    /// [`WgpuBackend::set_window_opacity`].
+    ///
+    /// If the surface doesn't support the mode, a warning is logged
+    /// and the default is used.
+    #[must_use]
+    pub fn with_alpha_mode(mut self, mode: CompositeAlphaMode) -> Self {
+        self.alpha_mode = Some(mode);
+        self
+    }
+
+    /// Filter used when scaling images.
+    ///
+    /// Defaults to [`wgpu::FilterMode::Linear`]. Use
+    /// [`wgpu::FilterMode::Nearest`] for a pixelated look.
+    /// Glyphs are always sampled with Nearest, they are
+    /// rasterized at cell size.
+    #[must_use]
+    pub fn with_image_filter(mut self, filter: FilterMode) -> Self {
+        self.image_filter = filter;
+        self
+    }
+
+    /// Use a 16-bit float texture for the composited text.
+    ///
+    /// The text layer defaults to [`wgpu::TextureFormat::Rgba8Unorm`].
+    /// With a wide surface format like `Rgba16Float` or `Rgb10a2Unorm`
+    /// this keeps the precision for the post-processor. The surface
+    /// itself always uses the negotiated format.
+    #[must_use]
+    pub fn with_hdr_text_layer(mut self, hdr: bool) -> Self {
+        self.text_layer_format = if hdr {
+            TextureFormat::Rgba16Float
+        } else {
+            TextureFormat::Rgba8Unorm
+        };
+        self
+    }
+
+    /// Load operation for the pass that composites the text layer.
+    ///
+    /// Defaults to [`wgpu::LoadOp::Load`]. The text layer keeps its
+    /// content between frames and only the dirty cells are painted
+    /// again.
+    ///
+    /// With [`wgpu::LoadOp::Clear`] the layer starts from the given
+    /// color every frame. The backend then ignores the dirty-cell
+    /// tracking and repaints every cell and image with each flush,
+    /// blink and redraw. This costs more per frame, but nothing of
+    /// an earlier frame can shine through.
+    #[must_use]
+    pub fn with_text_layer_load(mut self, load: LoadOp<wgpu::Color>) -> Self {
+        self.text_layer_load = load;
+        self
+    }
+
     /// Use the specified height and width when creating the surface.
     ///
     /// Defaults to 100x100.
@@ -264,6 +496,33 @@ where
         self
     }
 
+    /// Use the specified [`ratatui::style::Color`] for the unclaimed
+    /// area at the right/bottom of the cell grid.
+    /// Defaults to Color::Reset, which uses the background color.
+    #[must_use]
+    pub fn with_margin_color(mut self, color: Color) -> Self {
+        self.margin_color = color;
+        self
+    }
+
+    /// Keep a margin around the cell grid free, for rounded window
+    /// corners, a title bar drawn by the application or a notch.
+    /// The inset is filled with the margin color.
+    #[must_use]
+    pub fn with_inset(mut self, inset: Inset) -> Self {
+        self.inset = inset;
+        self
+    }
+
+    /// Rotate the whole terminal clockwise, for portrait or
+    /// upside-down mounted displays.
+    /// The cell grid is laid out for the rotated orientation.
+    #[must_use]
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
     /// Initial cursor-color.
     #[must_use]
     pub fn with_cursor_color(mut self, color: Color) -> Self {
@@ -271,6 +530,81 @@ where
         self
     }
 
+    /// Run the bidi algorithm for each row. Defaults to true.
+    ///
+    /// Apps that only show left-to-right text can switch it off
+    /// to save some time with each flush.
+    #[must_use]
+    pub fn with_bidi(mut self, bidi: bool) -> Self {
+        self.bidi = bidi;
+        self
+    }
+
+    /// Draw box-drawing and block-element chars (U+2500..=U+259F)
+    /// and Braille patterns (U+2800..=U+28FF) instead of using the
+    /// glyphs of the font. Defaults to false.
+    ///
+    /// The lines and blocks fill exact pixels of the cell, so borders
+    /// join without gaps whatever the metrics of the font. Braille
+    /// dots sit on the same grid in every cell, which helps plots.
+    #[must_use]
+    pub fn with_builtin_box_drawing(mut self, builtin: bool) -> Self {
+        self.builtin_box_drawing = builtin;
+        self
+    }
+
+    /// How control chars in a cell are shown. Defaults to
+    /// [ControlCharDisplay::Blank].
+    ///
+    /// Ratatui strips control chars from strings, but they can still
+    /// end up in a cell via [Cell::set_symbol](ratatui_core::buffer::Cell::set_symbol).
+    /// Showing them helps when debugging terminal protocols.
+    #[must_use]
+    pub fn with_control_char_display(mut self, display: ControlCharDisplay) -> Self {
+        self.control_chars = display;
+        self
+    }
+
+    /// Render some modifiers with decorations ratatui has no
+    /// modifier for, e.g. an overline or a double underline.
+    /// The default maps no modifier.
+    #[must_use]
+    pub fn with_modifier_remap(mut self, remap: ModifierRemap) -> Self {
+        self.modifier_remap = remap;
+        self
+    }
+
+    /// Fill for cells that were never written or are cleared.
+    /// Defaults to [Cell::EMPTY].
+    ///
+    /// A `·` gives a background like graph paper. The symbol must be
+    /// one column wide, otherwise the default is used.
+    #[must_use]
+    pub fn with_empty_cell(mut self, cell: Cell) -> Self {
+        self.empty_cell = cell;
+        self
+    }
+
+    /// Draw an outline around the glyphs, see
+    /// [WgpuBackend::set_text_outline]. Defaults to no outline.
+    #[must_use]
+    pub fn with_text_outline(mut self, width_px: f32, color: Color) -> Self {
+        self.text_outline = (width_px.max(0.0), color);
+        self
+    }
+
+    /// Number of shaping plans kept for reuse.
+    ///
+    /// A plan is needed for each combination of font, features,
+    /// direction and script. Defaults to the number of fonts, but at
+    /// least 2. The least recently used plan is dropped when the cache
+    /// is full.
+    #[must_use]
+    pub fn with_plan_cache_size(mut self, size: usize) -> Self {
+        self.plan_cache_size = Some(size);
+        self
+    }
+
     /// Initial cursor-style.
     #[must_use]
     pub fn with_cursor_style(mut self, style: CursorStyle) -> Self {
@@ -284,7 +618,8 @@ where
     /// is switched.
     ///
     /// So. To switch with every call to blink give a counter 1.
-    /// To blink half as fast give a counter 2.
+    /// To blink half as fast give a counter 2. A counter 0 means
+    /// no blinking.
     #[must_use]
     pub fn with_cursor_blink(mut self, counter: u8) -> Self {
         self.cursor_blink = counter;
@@ -297,7 +632,8 @@ where
     /// is switched.
     ///
     /// So. To switch with every call to blink give a counter 1.
-    /// To blink half as fast give a counter 2.
+    /// To blink half as fast give a counter 2. A counter 0 means
+    /// no blinking.
     #[must_use]
     pub fn with_rapid_blink(mut self, counter: u8) -> Self {
         self.fast_blink = counter;
@@ -310,7 +646,8 @@ where
     /// is switched.
     ///
     /// So. To switch with every call to blink give a counter 1.
-    /// To blink half as fast give a counter 2.
+    /// To blink half as fast give a counter 2. A counter 0 means
+    /// no blinking.
     #[must_use]
     pub fn with_slow_blink(mut self, counter: u8) -> Self {
         self.slow_blink = counter;
@@ -322,21 +659,56 @@ impl<'a, P> Builder<'a, P>
 where
     P: PostProcessorBuilder,
 {
-    /// Build a new backend with the provided surface target - e.g. a winit
-    /// `Window`.
-    pub async fn build_with_target<'s>(
-        mut self,
-        target: impl Into<SurfaceTarget<'s>>,
-    ) -> Result<WgpuBackend<'a, 's>, Error> {
-        let instance = self.instance.get_or_insert_with(|| {
+    /// Information about the adapters for the
+    /// [backends](Builder::with_backends) of this builder.
+    ///
+    /// For a GPU picker. Pass the chosen one to
+    /// [with_adapter_info](Builder::with_adapter_info). This uses the
+    /// [instance](Builder::with_instance) of the builder, or creates
+    /// the one the build will use.
+    pub async fn enumerate_adapters(&mut self) -> Vec<AdapterInfo> {
+        let backends = self.backends;
+        self.instance()
+            .enumerate_adapters(backends)
+            .await
+            .iter()
+            .map(|adapter| adapter.get_info())
+            .collect()
+    }
+
+    /// Same as [enumerate_adapters](Self::enumerate_adapters), but blocks
+    /// until the list is ready.
+    ///
+    /// __Info__
+    ///
+    /// Not available on wasm, the browser can't be blocked.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn enumerate_adapters_blocking(&mut self) -> Vec<AdapterInfo> {
+        block_on(self.enumerate_adapters())
+    }
+
+    // The given instance or a new one for the backends.
+    fn instance(&mut self) -> &Instance {
+        self.instance.get_or_insert_with(|| {
             Instance::new(&InstanceDescriptor {
                 backends: self.backends,
                 flags: InstanceFlags::default(),
                 ..Default::default()
             })
-        });
+        })
+    }
 
-        let surface = instance
+    /// Build a new backend with the provided surface target - e.g. a winit
+    /// `Window`.
+    ///
+    /// The window is cleared to the background color right away, so
+    /// it doesn't flash uninitialized content until the first flush.
+    pub async fn build_with_target<'s>(
+        mut self,
+        target: impl Into<SurfaceTarget<'s>>,
+    ) -> Result<WgpuBackend<'a, 's>, Error> {
+        let surface = self
+            .instance()
             .create_surface(target)
             .map_err(|e| Error::SurfaceCreationFailed(Box::new(e)))?;
 
@@ -344,6 +716,21 @@ where
             .await
     }
 
+    /// Same as [build_with_target](Self::build_with_target), but blocks
+    /// until the backend is ready. For sync contexts like the `resumed`
+    /// handler of winit.
+    ///
+    /// __Info__
+    ///
+    /// Not available on wasm, the browser can't be blocked.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn build_with_target_blocking<'s>(
+        self,
+        target: impl Into<SurfaceTarget<'s>>,
+    ) -> Result<WgpuBackend<'a, 's>, Error> {
+        block_on(self.build_with_target(target))
+    }
+
     /// Build a new backend from this builder with the supplied surface. You
     /// almost certainly want to call this with the instance you used to create
     /// the provided surface - see [`Builder::with_instance`]. If one is not
@@ -357,18 +744,79 @@ where
     }
 
     pub async fn build_headless(self) -> Result<WgpuBackend<'a, 'static>, Error> {
-        self.build_with_render_surface(RenderSurface::new_headless())
+        if let Some(format) = self.surface_format {
+            self.build_with_render_surface(RenderSurface::new_headless_with_format(format))
+                .await
+        } else {
+            self.build_with_render_surface(RenderSurface::new_headless())
+                .await
+        }
+    }
+
+    /// Same as [build_headless](Self::build_headless), but blocks
+    /// until the backend is ready.
+    ///
+    /// __Info__
+    ///
+    /// Not available on wasm, the browser can't be blocked.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn build_headless_blocking(self) -> Result<WgpuBackend<'a, 'static>, Error> {
+        block_on(self.build_headless())
+    }
+
+    /// Build a headless backend without the readback buffer.
+    ///
+    /// For rendering into a texture that is sampled on the same device,
+    /// see [headless_texture](WgpuBackend::headless_texture). This saves
+    /// the memory of the buffer, and
+    /// [map_headless_buffer](WgpuBackend::map_headless_buffer) fails with
+    /// [Error::HeadlessReadbackDisabled].
+    ///
+    /// The texture has [`wgpu::TextureUsages::TEXTURE_BINDING`] and uses
+    /// the [surface format](Builder::with_surface_format), or `Rgba8Unorm`.
+    pub async fn build_headless_texture_only(self) -> Result<WgpuBackend<'a, 'static>, Error> {
+        let format = self.surface_format.unwrap_or(TextureFormat::Rgba8Unorm);
+        self.build_with_render_surface(RenderSurface::new_headless_no_readback(format))
             .await
     }
 
     pub async fn build_headless_with_format(
-        self,
+        mut self,
         format: TextureFormat,
     ) -> Result<WgpuBackend<'a, 'static>, Error> {
+        self.surface_format = Some(format);
         self.build_with_render_surface(RenderSurface::new_headless_with_format(format))
             .await
     }
 
+    /// Build a new backend that renders into a texture owned by the caller.
+    ///
+    /// Every flush composites and post-processes the terminal into
+    /// the given view. Nothing is presented, this is left to whoever
+    /// samples the texture. Use [set_target](WgpuBackend::set_target)
+    /// to switch to a new texture, [resize](WgpuBackend::resize) is not
+    /// useful for this backend.
+    ///
+    /// __Info__
+    ///
+    /// The texture must be created with [`wgpu::TextureUsages::RENDER_ATTACHMENT`]
+    /// and the given format. It must belong to the same device as the backend,
+    /// so you will want to use [with_device_and_queue](Builder::with_device_and_queue)
+    /// too.
+    pub async fn build_with_texture(
+        mut self,
+        view: TextureView,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<WgpuBackend<'a, 'static>, Error> {
+        self.width = width;
+        self.height = height;
+        self.surface_format = Some(format);
+        self.build_with_render_surface(RenderSurface::new_texture(view, width, height, format))
+            .await
+    }
+
     async fn build_with_render_surface<'s>(
         mut self,
         mut surface: RenderSurface<'s>,
@@ -381,31 +829,56 @@ where
             })
         });
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                compatible_surface: surface.wgpu_surface(),
-                ..Default::default()
-            })
-            .await
-            .map_err(|e| Error::AdapterRequestFailed(Box::new(e)))?;
-
-        let limits = if let Some(limits) = self.limits {
+        let limits = if let Some(limits) = self.limits.take() {
             limits
         } else {
             Limits::downlevel_defaults()
         };
 
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: Some("ratatui-wgpu Device"),
-                required_features: Default::default(),
-                required_limits: limits,
-                experimental_features: Default::default(),
-                memory_hints: MemoryHints::MemoryUsage,
-                trace: Default::default(),
-            })
-            .await
-            .map_err(|e| Error::DeviceRequestFailed(Box::new(e)))?;
+        let (adapter, device, queue) = if let Some((adapter, device, queue)) = self.device.take() {
+            if !limits.check_limits(&device.limits()) {
+                return Err(Error::DeviceLimitsNotSupported);
+            }
+            (adapter, device, queue)
+        } else {
+            let adapter = if let Some(adapter) = self.adapter.take() {
+                adapter
+            } else if let Some(info) = self.adapter_info.take() {
+                instance
+                    .enumerate_adapters(self.backends)
+                    .await
+                    .into_iter()
+                    .find(|adapter| adapter.get_info() == info)
+                    .ok_or(Error::AdapterNotFound(info))?
+            } else {
+                instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: self.power_preference,
+                        compatible_surface: surface.wgpu_surface(),
+                        ..Default::default()
+                    })
+                    .await
+                    .map_err(|e| Error::AdapterRequestFailed(Box::new(e)))?
+            };
+            if let Some(wgpu_surface) = surface.wgpu_surface()
+                && !adapter.is_surface_supported(wgpu_surface)
+            {
+                return Err(Error::AdapterNotCompatible(adapter.get_info()));
+            }
+
+            let (device, queue) = adapter
+                .request_device(&wgpu::DeviceDescriptor {
+                    label: Some("ratatui-wgpu Device"),
+                    required_features: Default::default(),
+                    required_limits: limits,
+                    experimental_features: Default::default(),
+                    memory_hints: MemoryHints::MemoryUsage,
+                    trace: Default::default(),
+                })
+                .await
+                .map_err(|e| Error::DeviceRequestFailed(Box::new(e)))?;
+            (adapter, device, queue)
+        };
 
         let fonts = self.fonts.as_ref().expect("fonts");
 
@@ -420,11 +893,43 @@ where
         if let Some(mode) = self.present_mode {
             surface_config.present_mode = mode;
         }
+        if let Some(format) = self.surface_format {
+            if let Some(wgpu_surface) = surface.wgpu_surface()
+                && !wgpu_surface
+                    .get_capabilities(&adapter)
+                    .formats
+                    .contains(&format)
+            {
+                return Err(Error::SurfaceFormatNotSupported(format));
+            }
+            surface_config.format = format;
+        }
+        if let Some(mode) = self.alpha_mode {
+            if let Some(wgpu_surface) = surface.wgpu_surface()
+                && !wgpu_surface
+                    .get_capabilities(&adapter)
+                    .alpha_modes
+                    .contains(&mode)
+            {
+                warn!(
+                    "alpha mode {mode:?} not supported, using {:?}",
+                    surface_config.alpha_mode
+                );
+            } else {
+                surface_config.alpha_mode = mode;
+            }
+        }
 
         surface.configure(&device, &surface_config);
 
-        let drawable_width = surface_config.width;
-        let drawable_height = surface_config.height;
+        // the post-processor renders the unrotated terminal.
+        let rotation_blit = build_rotation_blit(&device, &surface_config, self.rotation);
+        let post_config = rotation_blit
+            .as_ref()
+            .map_or(&surface_config, |blit| &blit.config);
+
+        let drawable_width = post_config.width;
+        let drawable_height = post_config.height;
 
         info!(
             "char width x height: {}x{}",
@@ -449,7 +954,9 @@ where
 
         let text_cache_view = text_cache.create_view(&TextureViewDescriptor::default());
 
-        let sampler = device.create_sampler(&SamplerDescriptor {
+        // glyphs are rasterized at cell size, keep them pixel-perfect
+        // regardless of the image filter.
+        let text_sampler = device.create_sampler(&SamplerDescriptor {
             address_mode_u: AddressMode::ClampToEdge,
             address_mode_v: AddressMode::ClampToEdge,
             address_mode_w: AddressMode::ClampToEdge,
@@ -459,6 +966,16 @@ where
             ..Default::default()
         });
 
+        let img_sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: self.image_filter,
+            min_filter: self.image_filter,
+            mipmap_filter: MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
         let text_screen_size_buffer = device.create_buffer(&BufferDescriptor {
             label: Some("Text Uniforms Buffer"),
             size: size_of::<[f32; 4]>() as u64,
@@ -474,40 +991,57 @@ where
 
         let text_bg_compositor = build_text_bg_compositor(
             &device, //
+            self.text_layer_format,
             &text_screen_size_buffer,
         );
 
         let text_fg_compositor = build_text_fg_compositor(
             &device,
+            self.text_layer_format,
             &text_screen_size_buffer,
             &atlas_size_buffer,
             &text_cache_view,
-            &sampler,
+            &text_sampler,
         );
 
-        let img_compositor = build_img_compositor(&device, &text_screen_size_buffer);
+        let img_compositor = build_img_compositor(
+            &device, //
+            self.text_layer_format,
+            &text_screen_size_buffer,
+        );
 
+        let (grid_width, grid_height) =
+            self.inset
+                .grid(drawable_width, drawable_height, fonts.cell_box());
         let wgpu_view = build_wgpu_state(
             &device,
-            (drawable_width / fonts.width_px()) * fonts.width_px(),
-            (drawable_height / fonts.height_px()) * fonts.height_px(),
+            self.text_layer_format,
+            self.inset.left + grid_width * fonts.width_px(),
+            self.inset.top + grid_height * fonts.height_px(),
         );
 
         let reset_fg = self.colors.c2c(self.reset_fg, [255; 3]);
         let reset_bg = self.colors.c2c(self.reset_bg, [0; 3]);
+        let margin_color = match self.margin_color {
+            Color::Reset => None,
+            color => Some(self.colors.c2c(color, [0; 3])),
+        };
 
         let cell_box = fonts.cell_box();
         let font_count = fonts.count();
+        let font_size = fonts.height_px();
+        let cell_width = fonts.cell_width_px();
 
-        let chars_wide = width / cell_box.width;
-        let chars_high = height / cell_box.height;
+        let (chars_wide, chars_high) = self.inset.grid(drawable_width, drawable_height, cell_box);
 
-        let post_process = self
-            .postprocessor
-            .compile(&device, &wgpu_view, &surface_config);
+        let post_process = self.postprocessor.compile(&device, &wgpu_view, post_config);
 
-        Ok(WgpuBackend {
+        let mut backend = WgpuBackend {
             fonts: self.fonts.expect("fonts"),
+            font_size,
+            font_size_pt: self.font_size_pt,
+            cell_width,
+            scale_factor: 1.0,
             tui_surface: TuiSurface {
                 image_frame: ImageFrame {
                     buffer: Arc::new(Mutex::new(ImageBuffer {
@@ -529,14 +1063,31 @@ where
                 dirty_rows: Default::default(),
                 dirty_cells: Default::default(),
                 dirty_img: Default::default(),
+                rebuilt: false,
                 fast_blinking: Default::default(),
                 slow_blinking: Default::default(),
                 cursor: (0, 0),
                 colors: self.colors,
                 reset_fg,
                 reset_bg,
+                margin_color,
+                window_opacity: 1.0,
+                inset: self.inset,
+                base_direction: Default::default(),
+                bidi: self.bidi,
+                builtin_box_drawing: self.builtin_box_drawing,
+                control_chars: self.control_chars,
+                empty_cell: valid_empty_cell(self.empty_cell).unwrap_or(Cell::EMPTY),
+                text_outline: self.text_outline,
+                line_scale: Default::default(),
+                invert_colors: false,
+                high_contrast: false,
+                modifier_remap: self.modifier_remap,
+                selection: Vec::new(),
+                selection_color: Color::Reset,
                 cursor_color: self.cursor_color,
                 cursor_style: self.cursor_style,
+                cursor_image: None,
                 cursor_visible: true,
                 cursor_blink: 0,
                 cursor_divisor: self.cursor_blink,
@@ -549,7 +1100,10 @@ where
             },
             rendered: Default::default(),
 
-            tmp_plan_cache: PlanCache::new(font_count.max(2)),
+            tmp_plan_cache: PlanCache::new(
+                self.plan_cache_size.unwrap_or(font_count.max(2)).max(1),
+            ),
+            tmp_shape_cache: ShapeCache::new(SHAPE_CACHE_SIZE),
             tmp_buffer: UnicodeBuffer::new(),
             tmp_rowbuf: String::new(),
             tmp_rowbuf_to_cell: Default::default(),
@@ -557,21 +1111,30 @@ where
             wgpu_base: WgpuBase {
                 surface,
                 surface_config,
+                adapter_info: adapter.get_info(),
                 device,
                 queue,
                 text_dest_view: wgpu_view,
+                text_layer_load: self.text_layer_load,
+                rotation: self.rotation,
+                rotation_blit,
             },
             wgpu_vertices: WgpuVertices {
                 bg_vertices: Default::default(),
                 text_indices: Default::default(),
                 text_vertices: Default::default(),
+                line_indices: Default::default(),
+                line_vertices: Default::default(),
                 img_render: Default::default(),
+                img_order: Default::default(),
                 img_indices: Default::default(),
                 img_vertices: Default::default(),
             },
             wgpu_atlas: WgpuAtlas {
                 cached: Atlas::new(cell_box, CACHE_WIDTH, CACHE_HEIGHT),
                 text_cache,
+                rasterized: 0,
+                cache_hits: 0,
             },
             wgpu_images: WgpuImages {
                 img_id: 1,
@@ -580,18 +1143,29 @@ where
             },
             wgpu_post_process: Box::new(post_process),
             wgpu_pipeline: WgpuPipeline {
-                sampler,
+                img_sampler,
                 text_screen_size_buffer,
                 text_bg_compositor,
                 text_fg_compositor,
                 img_compositor,
             },
-        })
+
+            last_render_error: None,
+            last_presented: false,
+            render_stats: Default::default(),
+        };
+
+        // show the background right away instead of whatever
+        // the surface contains until the first flush.
+        backend.clear_surface();
+
+        Ok(backend)
     }
 }
 
 pub(super) fn build_wgpu_state(
     device: &Device,
+    format: TextureFormat,
     drawable_width: u32,
     drawable_height: u32,
 ) -> TextureView {
@@ -605,8 +1179,10 @@ pub(super) fn build_wgpu_state(
         mip_level_count: 1,
         sample_count: 1,
         dimension: TextureDimension::D2,
-        format: TextureFormat::Rgba8Unorm,
-        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+        format,
+        usage: TextureUsages::TEXTURE_BINDING
+            | TextureUsages::RENDER_ATTACHMENT
+            | TextureUsages::COPY_SRC,
         view_formats: &[],
     });
 
@@ -615,6 +1191,131 @@ pub(super) fn build_wgpu_state(
     text_dest_view
 }
 
+pub(super) fn build_rotation_blit(
+    device: &Device,
+    surface_config: &SurfaceConfiguration,
+    rotation: Rotation,
+) -> Option<RotationBlit> {
+    let rotation_idx: u32 = match rotation {
+        Rotation::None => return None,
+        Rotation::Cw90 => 1,
+        Rotation::Cw180 => 2,
+        Rotation::Cw270 => 3,
+    };
+
+    let (width, height) = rotation.rotated_size(surface_config.width, surface_config.height);
+    let mut config = surface_config.clone();
+    config.width = width;
+    config.height = height;
+
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Rotation In"),
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: surface_config.format,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+
+    let uniforms = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Rotation Uniforms"),
+        contents: bytemuck::cast_slice(&[rotation_idx, 0, 0, 0]),
+        usage: BufferUsages::UNIFORM,
+    });
+
+    let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Rotation Bindings Layout"),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(NonZeroU64::new(size_of::<[u32; 4]>() as u64).unwrap()),
+                },
+                count: None,
+            },
+        ],
+    });
+
+    let bindings = device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Rotation Bindings"),
+        layout: &layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: uniforms.as_entire_binding(),
+            },
+        ],
+    });
+
+    let shader = device.create_shader_module(include_wgsl!("rotate.wgsl"));
+
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Rotation Layout"),
+        bind_group_layouts: &[&layout],
+        immediate_size: 0,
+    });
+
+    let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Rotation Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            compilation_options: PipelineCompilationOptions::default(),
+            buffers: &[],
+        },
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            compilation_options: PipelineCompilationOptions::default(),
+            targets: &[Some(ColorTargetState {
+                format: surface_config.format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        multiview_mask: None,
+        cache: None,
+    });
+
+    Some(RotationBlit {
+        pipeline,
+        bindings,
+        view,
+        config,
+    })
+}
+
 pub(super) fn build_img_bindings(
     img_pipeline: &ImgPipeline,
     device: &Device,
@@ -647,7 +1348,11 @@ pub(super) fn build_img_bindings(
     })
 }
 
-fn build_img_compositor(device: &Device, screen_size: &Buffer) -> ImgPipeline {
+fn build_img_compositor(
+    device: &Device,
+    format: TextureFormat,
+    screen_size: &Buffer,
+) -> ImgPipeline {
     let shader = device.create_shader_module(include_wgsl!("img.wgsl"));
 
     let vertex_shader_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -747,7 +1452,7 @@ fn build_img_compositor(device: &Device, screen_size: &Buffer) -> ImgPipeline {
             entry_point: Some("fs_main"),
             compilation_options: PipelineCompilationOptions::default(),
             targets: &[Some(ColorTargetState {
-                format: TextureFormat::Rgba8Unorm,
+                format,
                 blend: Some(BlendState::ALPHA_BLENDING),
                 write_mask: ColorWrites::ALL,
             })],
@@ -763,7 +1468,11 @@ fn build_img_compositor(device: &Device, screen_size: &Buffer) -> ImgPipeline {
     }
 }
 
-fn build_text_bg_compositor(device: &Device, screen_size: &Buffer) -> TextCacheBgPipeline {
+fn build_text_bg_compositor(
+    device: &Device,
+    format: TextureFormat,
+    screen_size: &Buffer,
+) -> TextCacheBgPipeline {
     let shader = device.create_shader_module(include_wgsl!("composite_bg.wgsl"));
 
     let vertex_shader_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -819,7 +1528,7 @@ fn build_text_bg_compositor(device: &Device, screen_size: &Buffer) -> TextCacheB
             entry_point: Some("fs_main"),
             compilation_options: PipelineCompilationOptions::default(),
             targets: &[Some(ColorTargetState {
-                format: TextureFormat::Rgba8Unorm,
+                format,
                 blend: None,
                 write_mask: ColorWrites::ALL,
             })],
@@ -836,6 +1545,7 @@ fn build_text_bg_compositor(device: &Device, screen_size: &Buffer) -> TextCacheB
 
 fn build_text_fg_compositor(
     device: &Device,
+    format: TextureFormat,
     screen_size: &Buffer,
     atlas_size: &Buffer,
     cache_view: &TextureView,
@@ -933,7 +1643,7 @@ fn build_text_fg_compositor(
             buffers: &[VertexBufferLayout {
                 array_stride: size_of::<TextVertexMember>() as u64,
                 step_mode: VertexStepMode::Vertex,
-                attributes: &vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32, 3 => Uint32, 4 => Uint32, 5 => Uint32, 6 => Uint32, 7 => Uint32, 8 => Uint32 ],
+                attributes: &vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32, 3 => Uint32, 4 => Uint32, 5 => Uint32, 6 => Uint32, 7 => Uint32 ],
             }],
         },
         primitive: PrimitiveState {
@@ -947,7 +1657,7 @@ fn build_text_fg_compositor(
             entry_point: Some("fs_main"),
             compilation_options: PipelineCompilationOptions::default(),
             targets: &[Some(ColorTargetState {
-                format: TextureFormat::Rgba8Unorm,
+                format,
                 blend: Some(BlendState::ALPHA_BLENDING),
                 write_mask: ColorWrites::ALL,
             })],
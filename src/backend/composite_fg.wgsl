@@ -3,10 +3,9 @@ struct VertexOutput {
     @location(1) @interpolate(flat) UVx0: f32,
     @location(2) @interpolate(flat) FgColor: u32,
     @location(3) @interpolate(flat) ColorGlyph: u32,
-    @location(4) @interpolate(flat) UnderlinePos: u32,
-    @location(5) @interpolate(flat) StrikeoutPos: u32,
-    @location(6) @interpolate(flat) CursorPos: u32,
-    @location(7) @interpolate(flat) CursorColor: u32,
+    @location(4) @interpolate(flat) CursorPos: u32,
+    @location(5) @interpolate(flat) CursorColor: u32,
+    @location(6) @interpolate(flat) OutlineColor: u32,
     @builtin(position) gl_Position: vec4<f32>,
 }
 
@@ -20,21 +19,19 @@ fn vs_main(
     @location(2) UVx0: f32,
     @location(3) FgColor: u32,
     @location(4) ColorGlyph: u32,
-    @location(5) UnderlinePos: u32,
-    @location(6) StrikeoutPos: u32,
-    @location(7) CursorPos: u32,
-    @location(8) CursorColor: u32,
+    @location(5) CursorPos: u32,
+    @location(6) CursorColor: u32,
+    @location(7) OutlineColor: u32,
 ) -> VertexOutput {
-    let gl_Position = vec4<f32>((2.0 * VertexCoord / ScreenSize.xy - 1.0) * vec2(1.0, -1.0), 0.0, 1.0);
+    let gl_Position = vec4<f32>((2.0 * (VertexCoord + ScreenSize.zw) / ScreenSize.xy - 1.0) * vec2(1.0, -1.0), 0.0, 1.0);
 
     return VertexOutput(UV,
         UVx0,
         FgColor,
         ColorGlyph,
-        UnderlinePos,
-        StrikeoutPos,
         CursorPos,
         CursorColor,
+        OutlineColor,
         gl_Position);
 }
 
@@ -55,29 +52,28 @@ fn fs_main(
     @location(1) @interpolate(flat) UVx0: f32,
     @location(2) @interpolate(flat) FgColor: u32,
     @location(3) @interpolate(flat) ColorGlyph: u32,
-    @location(4) @interpolate(flat) UnderlinePos: u32,
-    @location(5) @interpolate(flat) StrikeoutPos: u32,
-    @location(6) @interpolate(flat) CursorPos: u32,
-    @location(7) @interpolate(flat) CursorColor: u32,
+    @location(4) @interpolate(flat) CursorPos: u32,
+    @location(5) @interpolate(flat) CursorColor: u32,
+    @location(6) @interpolate(flat) OutlineColor: u32,
 ) -> FragmentOutput {
     var cursorColorUnpacked = unpack4x8unorm(CursorColor);
     var fgColorUnpacked = unpack4x8unorm(FgColor);
     var textureColor = textureSample(Atlas, Sampler, UV / AtlasSize.xy);
 
     var fgcolorAlpha = fgColorUnpacked;
+    // outlined glyph, the colors hold the coverage of the fill.
+    // bitmap glyphs have no outline and white colors, this
+    // clamps them to a full fill.
+    if ColorGlyph == 2u && textureColor.a > 0.0 {
+        let outlineColorUnpacked = unpack4x8unorm(OutlineColor);
+        let fill = clamp(textureColor.g / textureColor.a, 0.0, 1.0);
+        fgcolorAlpha = vec4(mix(outlineColorUnpacked.rgb, fgColorUnpacked.rgb, fill), fgColorUnpacked.a);
+    }
     let alpha = textureColor.a * fgcolorAlpha.a;
     textureColor.a = alpha;
     fgcolorAlpha.a = alpha;
     var fragmentColor = select(fgcolorAlpha, textureColor, ColorGlyph == 1);
 
-    let yMax = UnderlinePos & 0xFFFFu;
-    let yMin = UnderlinePos >> 16u;
-    fragmentColor = select(fragmentColor, fgColorUnpacked, u32(UV.y) >= yMin && u32(UV.y) < yMax);
-
-    let y2Max = StrikeoutPos & 0xFFFFu;
-    let y2Min = StrikeoutPos >> 16u;
-    fragmentColor = select(fragmentColor, fgColorUnpacked, u32(UV.y) >= y2Min && u32(UV.y) < y2Max);
-
     let cur_vis = CursorPos & 0x00020000u;
     let cur_hor = CursorPos & 0x00010000u;
     let cur_min = CursorPos & 0xFFu;
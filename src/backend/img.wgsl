@@ -11,7 +11,7 @@ fn vs_main(
     @location(0) VertexCoord: vec2<f32>,
     @location(1) UV: vec2<f32>,
 ) -> VertexOutput {
-    let gl_Position = vec4<f32>((2.0 * VertexCoord / ScreenSize.xy - 1.0) * vec2(1.0, -1.0), 0.0, 1.0);
+    let gl_Position = vec4<f32>((2.0 * (VertexCoord + ScreenSize.zw) / ScreenSize.xy - 1.0) * vec2(1.0, -1.0), 0.0, 1.0);
     return VertexOutput(UV, gl_Position);
 }
 
@@ -3,6 +3,7 @@ use crate::colors::{ColorTable, Rgb};
 use crate::cursor::CursorStyle;
 use crate::image::{ImageFrame, ImageHandle};
 use crate::text_atlas::{Atlas, CacheRect};
+use crate::{BaseDirection, ControlCharDisplay, Inset, LineScale, ModifierRemap, Rotation};
 use bitvec::vec::BitVec;
 use raqote::Transform;
 use ratatui_core::buffer::Cell;
@@ -10,13 +11,14 @@ use ratatui_core::style::Modifier;
 use rustybuzz::ttf_parser::GlyphId;
 use std::collections::{HashMap, HashSet};
 use wgpu::{
-    BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPipeline, Sampler,
-    SurfaceConfiguration, Texture, TextureView,
+    AdapterInfo, BindGroup, BindGroupLayout, Buffer, Device, LoadOp, Queue, RenderPipeline,
+    Sampler, SurfaceConfiguration, Texture, TextureView,
 };
 
 pub(super) mod backend;
 pub(super) mod builder;
 mod plan_cache;
+pub(super) mod shape_cache;
 mod surface;
 
 const NULL_CELL: Cell = {
@@ -35,8 +37,11 @@ struct RenderInfo {
     underline_pos_max: u16,
     strikeout_pos_min: u16,
     strikeout_pos_max: u16,
+    overline_pos_min: u16,
+    overline_pos_max: u16,
     cursor_pos_min: u16,
     cursor_pos_max: u16,
+    line_scale: LineScale,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -45,6 +50,7 @@ struct ImageInfo {
     view_rect: (i32, i32, u32, u32),
     view_clip: (i32, i32, u32, u32),
     below_text: bool,
+    z: i32,
     uv_transform: Transform,
 }
 
@@ -73,6 +79,8 @@ struct TuiSurface {
     dirty_cells: BitVec,
     // images prepared to render.
     dirty_img: Vec<ImageInfo>,
+    // the surface was rebuilt and not flushed since.
+    rebuilt: bool,
     // blink flag for each cell
     fast_blinking: BitVec,
     // blink flag for each cell
@@ -82,6 +90,8 @@ struct TuiSurface {
     cursor: (u16, u16),
     cursor_color: ratatui_core::style::Color,
     cursor_style: CursorStyle,
+    // image for CursorStyle::Image.
+    cursor_image: Option<ImageHandle>,
     // cursor status set by the application.
     cursor_visible: bool,
     // every time blink() is called this value is increased by 1.
@@ -113,6 +123,39 @@ struct TuiSurface {
     reset_fg: Rgb,
     // BG-Color for Color::Reset
     reset_bg: Rgb,
+    // Color for the unclaimed area. None uses reset_bg.
+    margin_color: Option<Rgb>,
+    // Alpha of the output of the post-processor.
+    window_opacity: f32,
+    // Margins around the cell grid.
+    inset: Inset,
+    // Base direction for bidi.
+    base_direction: BaseDirection,
+    // Run the bidi algorithm. Otherwise, every row is one ltr run.
+    bidi: bool,
+    // Draw box-drawing and block chars instead of using the font.
+    builtin_box_drawing: bool,
+    // Replacement for control chars.
+    control_chars: ControlCharDisplay,
+    // Fill for unwritten and cleared cells.
+    empty_cell: Cell,
+    // Width and color of the outline around glyphs.
+    text_outline: (f32, ratatui_core::style::Color),
+    // Double width/height per row.
+    line_scale: Vec<LineScale>,
+    // Swap fg/bg for all cells.
+    invert_colors: bool,
+    // Snap all colors to black/white.
+    high_contrast: bool,
+    // Modifiers rendered with extra decorations.
+    modifier_remap: ModifierRemap,
+    // Selected ranges in visual positions. Start and end are inclusive.
+    selection: Vec<(
+        ratatui_core::layout::Position,
+        ratatui_core::layout::Position,
+    )>,
+    // Blended over the bg of selected cells.
+    selection_color: ratatui_core::style::Color,
 }
 
 #[repr(C)]
@@ -131,10 +174,9 @@ struct TextVertexMember {
     uv_x0: f32,
     fg_color: u32,
     color_glyph: u32,
-    underline_pos: u32,
-    strikeout_pos: u32,
     cursor_pos: u32,
     cursor_color: u32,
+    outline_color: u32,
 }
 
 #[repr(C)]
@@ -161,17 +203,70 @@ struct TextCacheFgPipeline {
     atlas_bindings: BindGroup,
 }
 
+// Rotates the output of the post-processor onto the surface.
+struct RotationBlit {
+    pipeline: RenderPipeline,
+    bindings: BindGroup,
+    // the post-processor renders here instead of the surface.
+    view: TextureView,
+    // surface config with the rotated size.
+    config: SurfaceConfiguration,
+}
+
 struct WgpuBase<'s> {
     surface: RenderSurface<'s>,
     surface_config: SurfaceConfiguration,
+    adapter_info: AdapterInfo,
     device: Device,
     queue: Queue,
     text_dest_view: TextureView,
+    // Load for the text layer. Clear forces a full repaint.
+    text_layer_load: LoadOp<wgpu::Color>,
+    rotation: Rotation,
+    // None without rotation.
+    rotation_blit: Option<RotationBlit>,
+}
+
+impl WgpuBase<'_> {
+    // The surface as seen by the post-processor.
+    fn post_config(&self) -> &SurfaceConfiguration {
+        match &self.rotation_blit {
+            Some(blit) => &blit.config,
+            None => &self.surface_config,
+        }
+    }
+}
+
+/// Counters for profiling, see
+/// [render_stats](crate::WgpuBackend::render_stats).
+///
+/// Except for the duration these are totals since the backend
+/// was built.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Glyphs rasterized into the atlas.
+    pub glyphs_rasterized: u64,
+    /// Glyphs found in the atlas.
+    pub glyphs_cached_hits: u64,
+    /// Quads drawn with the bg pipeline. These are the cell
+    /// backgrounds, the margin, underline, strikeout and overline.
+    pub bg_quads: u64,
+    /// Glyph quads drawn.
+    pub fg_quads: u64,
+    /// Images drawn, including the cursor image.
+    pub images_drawn: u64,
+    /// Rows repainted by flush.
+    pub dirty_rows: u64,
+    /// Duration of the last flush in µs.
+    pub last_flush_micros: u64,
 }
 
 struct WgpuAtlas {
     cached: Atlas,
     text_cache: Texture,
+    // glyph counts for RenderStats.
+    rasterized: u64,
+    cache_hits: u64,
 }
 
 struct WgpuImage {
@@ -188,8 +283,14 @@ struct WgpuVertices {
     text_indices: Vec<[u32; 6]>,
     bg_vertices: Vec<TextBgVertexMember>,
     text_vertices: Vec<TextVertexMember>,
+    // underline, strikeout and overline. drawn with the bg pipeline, above
+    // the bg and the images below the text.
+    line_indices: Vec<[u32; 6]>,
+    line_vertices: Vec<TextBgVertexMember>,
 
     img_render: Vec<ImageInfo>,
+    // img_render in z-order. kept to avoid the allocation per render.
+    img_order: Vec<usize>,
     img_indices: Vec<[u32; 6]>,
     img_vertices: Vec<ImgVertexMember>,
 }
@@ -203,14 +304,27 @@ impl WgpuVertices {
         self.text_indices.clear();
         self.bg_vertices.clear();
         self.text_vertices.clear();
+        self.line_indices.clear();
+        self.line_vertices.clear();
         self.img_vertices.clear();
         self.img_indices.clear();
         self.img_render.clear();
+        self.img_order.clear();
+    }
+
+    // paint in z-order, the sort is stable.
+    fn sort_images(&mut self) {
+        self.img_order.clear();
+        self.img_order.extend(0..self.img_render.len());
+        let img_render = &self.img_render;
+        self.img_order.sort_by_key(|n| img_render[*n].z);
     }
 }
 
 struct WgpuPipeline {
-    sampler: Sampler,
+    // The glyph atlas has its own Nearest sampler
+    // in text_fg_compositor.atlas_bindings.
+    img_sampler: Sampler,
 
     text_screen_size_buffer: Buffer,
 
@@ -1,11 +1,14 @@
 use crate::font::Font;
 use evictor::Lru;
 use rustybuzz::{Direction, Script, ShapePlan, UnicodeBuffer};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::num::NonZeroUsize;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Key {
     face_id: u64,
+    // hash of the features.
+    features: u64,
     direction: Direction,
     script: Script,
 }
@@ -23,8 +26,11 @@ impl PlanCache {
 
     pub(crate) fn get(&mut self, id: u64, font: &Font, buffer: &mut UnicodeBuffer) -> &ShapePlan {
         buffer.guess_segment_properties();
+        let mut hasher = DefaultHasher::new();
+        font.features().hash(&mut hasher);
         let key = Key {
             face_id: id,
+            features: hasher.finish(),
             direction: buffer.direction(),
             script: buffer.script(),
         };
@@ -35,8 +41,106 @@ impl PlanCache {
                 buffer.direction(),
                 Some(buffer.script()),
                 buffer.language().as_ref(),
-                &[],
+                font.features(),
             )
         })
     }
+
+    // Plans depend on the font. Must be cleared whenever the
+    // fonts change. Changed features get a new entry anyway.
+    pub(crate) fn clear(&mut self) {
+        self.lru.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlanCache;
+    use crate::font::Font;
+    use rustybuzz::ttf_parser::Tag;
+    use rustybuzz::{Feature, UnicodeBuffer, shape_with_plan};
+
+    fn glyphs(cache: &mut PlanCache, font: &Font, text: &str) -> Vec<u32> {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        let plan = cache.get(font.id(), font, &mut buffer);
+        shape_with_plan(font.face(), plan, buffer)
+            .glyph_infos()
+            .iter()
+            .map(|info| info.glyph_id)
+            .collect()
+    }
+
+    #[test]
+    fn features() {
+        let mut font = Font::new(include_bytes!("../font/CascadiaMono-Regular.ttf")).unwrap();
+        let mut cache = PlanCache::new(2);
+
+        let plain = glyphs(&mut cache, &font, "0");
+
+        // slashed zero.
+        font.set_features(&[Feature::new(Tag::from_bytes(b"zero"), 1, ..)]);
+        cache.clear();
+        let zero = glyphs(&mut cache, &font, "0");
+        assert_eq!(zero.len(), 1);
+        assert_ne!(zero, plain);
+
+        font.disable_ligatures();
+        cache.clear();
+        assert_eq!(glyphs(&mut cache, &font, "0"), plain);
+        assert_eq!(glyphs(&mut cache, &font, "->").len(), 2);
+    }
+
+    #[test]
+    fn ligatures() {
+        let mut font = Font::new(include_bytes!("../../tests/fonts/RatTest.ttf")).unwrap();
+        let mut cache = PlanCache::new(2);
+
+        let arrow = glyphs(&mut cache, &font, "->");
+        assert_eq!(arrow.len(), 1);
+        assert_ne!(arrow, glyphs(&mut cache, &font, "-"));
+
+        font.disable_ligatures();
+        cache.clear();
+        let plain = glyphs(&mut cache, &font, "->");
+        assert_eq!(plain.len(), 2);
+        assert!(!plain.contains(&arrow[0]));
+    }
+
+    #[test]
+    fn eviction() {
+        let data = include_bytes!("../font/CascadiaMono-Regular.ttf");
+        let mut fonts = [
+            Font::new(data).unwrap(),
+            Font::new(data).unwrap(),
+            Font::new(data).unwrap(),
+        ];
+        // slashed zero and superscript.
+        fonts[1].set_features(&[Feature::new(Tag::from_bytes(b"zero"), 1, ..)]);
+        fonts[2].set_features(&[Feature::new(Tag::from_bytes(b"sups"), 1, ..)]);
+        for (id, font) in fonts.iter_mut().enumerate() {
+            font.set_id(id as u64);
+        }
+
+        let expected = fonts
+            .iter()
+            .map(|font| glyphs(&mut PlanCache::new(1), font, "0"))
+            .collect::<Vec<_>>();
+        assert_ne!(expected[0], expected[1]);
+        assert_ne!(expected[0], expected[2]);
+        assert_ne!(expected[1], expected[2]);
+
+        // more combinations than entries.
+        let mut cache = PlanCache::new(2);
+        for n in 0..10 {
+            let idx = (n * 2) % fonts.len();
+            assert_eq!(glyphs(&mut cache, &fonts[idx], "0"), expected[idx]);
+        }
+
+        // same id, but new features.
+        let mut font = Font::new(data).unwrap();
+        assert_eq!(glyphs(&mut cache, &font, "0"), expected[0]);
+        font.set_features(fonts[1].features());
+        assert_eq!(glyphs(&mut cache, &font, "0"), expected[1]);
+    }
 }
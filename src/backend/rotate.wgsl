@@ -0,0 +1,43 @@
+struct VertexOutput {
+    @builtin(position) gl_Position: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) Index: u32) -> VertexOutput {
+    let vertex = vec2(f32((Index << 1u) & 2u), f32(Index & 2u));
+    return VertexOutput(vec4(vertex * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0));
+}
+
+struct FragmentOutput {
+    @location(0) FragColor: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var Texture: texture_2d<f32>;
+
+// clockwise, in steps of 90 degrees.
+@group(0) @binding(1)
+var<uniform> Rotation: vec4<u32>;
+
+@fragment
+fn fs_main(@builtin(position) gl_Position: vec4<f32>) -> FragmentOutput {
+    // the texture has the unrotated size.
+    let size = vec2<i32>(textureDimensions(Texture));
+    let p = vec2<i32>(gl_Position.xy);
+
+    var pos = p;
+    switch Rotation.x {
+        case 1u: {
+            pos = vec2(p.y, size.y - 1 - p.x);
+        }
+        case 2u: {
+            pos = vec2(size.x - 1 - p.x, size.y - 1 - p.y);
+        }
+        case 3u: {
+            pos = vec2(size.x - 1 - p.y, p.x);
+        }
+        default: {}
+    }
+
+    return FragmentOutput(textureLoad(Texture, pos, 0));
+}
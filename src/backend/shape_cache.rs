@@ -0,0 +1,98 @@
+use evictor::Lru;
+use rustybuzz::{GlyphBuffer, GlyphInfo, GlyphPosition};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::num::NonZeroUsize;
+
+/// Counts how the text runs were shaped during flush.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShapingStats {
+    /// Runs shaped with rustybuzz.
+    pub shaped: u64,
+    /// Runs taken from the shaping cache.
+    pub cached: u64,
+}
+
+/// Shaped glyphs of one run.
+/// The clusters are relative to the start of the run.
+#[derive(Debug)]
+pub(crate) struct Shaped {
+    pub(crate) infos: Vec<GlyphInfo>,
+    pub(crate) positions: Vec<GlyphPosition>,
+}
+
+impl Shaped {
+    pub(crate) fn new(glyphs: &GlyphBuffer, start: usize) -> Self {
+        Self {
+            infos: glyphs
+                .glyph_infos()
+                .iter()
+                .map(|info| {
+                    let mut info = *info;
+                    info.cluster -= start as u32;
+                    info
+                })
+                .collect(),
+            positions: glyphs.glyph_positions().to_vec(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    text: String,
+    shaped: Shaped,
+}
+
+/// Shaped runs by font and text.
+///
+/// Rows are shaped in runs that break at font changes, bidi levels
+/// and after blanks. A row that is repainted without changes, or with
+/// changes in some other run, doesn't need to be shaped again.
+pub(crate) struct ShapeCache {
+    lru: Lru<u64, Entry>,
+    stats: ShapingStats,
+}
+
+impl ShapeCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            lru: Lru::new(NonZeroUsize::new(capacity).expect("Capacity must be non-zero")),
+            stats: Default::default(),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> ShapingStats {
+        self.stats
+    }
+
+    pub(crate) fn get_or_shape(
+        &mut self,
+        font_id: u64,
+        text: &str,
+        shape: impl FnOnce() -> Shaped,
+    ) -> &Shaped {
+        let mut hasher = DefaultHasher::new();
+        font_id.hash(&mut hasher);
+        text.hash(&mut hasher);
+        let key = hasher.finish();
+
+        // the text is compared too, a hash collision just shapes again.
+        if self.lru.peek(&key).is_some_and(|entry| entry.text == text) {
+            self.stats.cached += 1;
+            &self.lru.get(&key).expect("entry").shaped
+        } else {
+            self.stats.shaped += 1;
+            let entry = Entry {
+                text: text.to_string(),
+                shaped: shape(),
+            };
+            &self.lru.insert(key, entry).shaped
+        }
+    }
+
+    // Shaping depends on the font and its features. Must be
+    // cleared whenever the fonts change.
+    pub(crate) fn clear(&mut self) {
+        self.lru.clear();
+    }
+}
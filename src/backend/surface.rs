@@ -1,5 +1,14 @@
-use log::error;
-use wgpu::{Adapter, Device, Extent3d, Surface, SurfaceConfiguration, SurfaceTexture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor};
+use log::warn;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use wgpu::{
+    Adapter, Buffer, BufferAsyncError, CommandEncoder, Device, Extent3d, PollType, SubmissionIndex,
+    Surface, SurfaceConfiguration, SurfaceError, SurfaceTexture, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+};
+
+// Number of frames that can be on their way back from the gpu.
+const CAPTURE_RING_SIZE: usize = 3;
 
 pub(crate) enum RenderTarget {
     Surface {
@@ -9,11 +18,15 @@ pub(crate) enum RenderTarget {
     Headless {
         view: TextureView,
     },
+    Texture {
+        view: TextureView,
+    },
 }
 
 pub(crate) enum RenderSurface<'s> {
     Surface(Surface<'s>),
     Headless(Headless),
+    Texture(TextureTarget),
 }
 
 pub(crate) struct Headless {
@@ -23,6 +36,42 @@ pub(crate) struct Headless {
     pub(crate) width: u32,
     pub(crate) height: u32,
     pub(crate) format: TextureFormat,
+    pub(crate) capture: Option<CaptureRing>,
+    // allocate the buffer for map_headless_buffer.
+    pub(crate) readback: bool,
+}
+
+// Result of map_async, once it is called.
+type MapResult = Arc<Mutex<Option<Result<(), BufferAsyncError>>>>;
+
+// One frame that is copied or mapped.
+struct PendingFrame {
+    buffer: Buffer,
+    bytes_per_row: u32,
+    // without padding.
+    row_len: u32,
+    height: u32,
+    // the submission with the copy, None until it is submitted.
+    submission: Option<SubmissionIndex>,
+    // None until the mapping is requested.
+    mapped: Option<MapResult>,
+}
+
+// Copies of the presented frames, read back without blocking.
+#[derive(Default)]
+pub(crate) struct CaptureRing {
+    // copy each presented frame.
+    pub(crate) active: bool,
+    free: Vec<Buffer>,
+    pending: VecDeque<PendingFrame>,
+    frames: VecDeque<Vec<u8>>,
+}
+
+pub(crate) struct TextureTarget {
+    pub(crate) view: TextureView,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) format: TextureFormat,
 }
 
 impl RenderTarget {
@@ -30,13 +79,14 @@ impl RenderTarget {
         match self {
             RenderTarget::Surface { view, .. } => view,
             RenderTarget::Headless { view } => view,
+            RenderTarget::Texture { view } => view,
         }
     }
 
     pub(crate) fn present(self) {
         match self {
             RenderTarget::Surface { texture, .. } => texture.present(),
-            RenderTarget::Headless { .. } => {
+            RenderTarget::Headless { .. } | RenderTarget::Texture { .. } => {
                 // noop
             }
         }
@@ -49,17 +99,19 @@ impl<'s> RenderSurface<'s> {
     }
 
     pub(crate) fn new_headless() -> Self {
-        Self::Headless(Headless {
-            texture: Default::default(),
-            buffer: Default::default(),
-            buffer_width: Default::default(),
-            width: Default::default(),
-            height: Default::default(),
-            format: TextureFormat::Rgba8Unorm,
-        })
+        Self::headless(TextureFormat::Rgba8Unorm, true)
     }
 
     pub(crate) fn new_headless_with_format(format: TextureFormat) -> Self {
+        Self::headless(format, true)
+    }
+
+    // Only the texture, for sampling on the same device.
+    pub(crate) fn new_headless_no_readback(format: TextureFormat) -> Self {
+        Self::headless(format, false)
+    }
+
+    fn headless(format: TextureFormat, readback: bool) -> Self {
         Self::Headless(Headless {
             texture: Default::default(),
             buffer: Default::default(),
@@ -67,15 +119,31 @@ impl<'s> RenderSurface<'s> {
             width: Default::default(),
             height: Default::default(),
             format,
+            capture: None,
+            readback,
+        })
+    }
+
+    pub(crate) fn new_texture(
+        view: TextureView,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Self {
+        Self::Texture(TextureTarget {
+            view,
+            width,
+            height,
+            format,
         })
     }
 
     pub(crate) fn wgpu_surface(&self) -> Option<&Surface<'s>> {
         match self {
             RenderSurface::Surface(surface) => Some(surface),
-            RenderSurface::Headless(_) => None,
+            RenderSurface::Headless(_) | RenderSurface::Texture(_) => None,
         }
-    } 
+    }
 
     pub(crate) fn get_default_config(
         &self,
@@ -95,6 +163,22 @@ impl<'s> RenderSurface<'s> {
                 alpha_mode: wgpu::CompositeAlphaMode::Auto,
                 view_formats: vec![],
             }),
+            // the size of the texture is fixed.
+            RenderSurface::Texture(TextureTarget {
+                width,
+                height,
+                format,
+                ..
+            }) => Some(SurfaceConfiguration {
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                format: *format,
+                width: *width,
+                height: *height,
+                present_mode: wgpu::PresentMode::Immediate,
+                desired_maximum_frame_latency: 2,
+                alpha_mode: wgpu::CompositeAlphaMode::Auto,
+                view_formats: vec![],
+            }),
         }
     }
 
@@ -110,6 +194,8 @@ impl<'s> RenderSurface<'s> {
                 width,
                 height,
                 format,
+                readback,
+                ..
             }) => {
                 *texture = Some(device.create_texture(&TextureDescriptor {
                     label: None,
@@ -122,48 +208,209 @@ impl<'s> RenderSurface<'s> {
                     sample_count: 1,
                     dimension: TextureDimension::D2,
                     format: *format,
-                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                    usage: if *readback {
+                        TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC
+                    } else {
+                        TextureUsages::RENDER_ATTACHMENT
+                            | TextureUsages::COPY_SRC
+                            | TextureUsages::TEXTURE_BINDING
+                    },
                     view_formats: &[],
                 }));
 
-                *buffer_width = config.width * 4;
-                *buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
-                    label: None,
-                    size: (*buffer_width * config.height) as u64,
-                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-                    mapped_at_creation: false,
-                }));
+                // copies to a buffer need rows aligned to 256 bytes.
+                *buffer_width = (config.width * format.block_copy_size(None).unwrap_or(4))
+                    .next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+                *buffer = readback.then(|| {
+                    device.create_buffer(&wgpu::BufferDescriptor {
+                        label: None,
+                        size: (*buffer_width * config.height) as u64,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    })
+                });
                 *width = config.width;
                 *height = config.height;
             }
+            RenderSurface::Texture(_) => {
+                // owned by the caller.
+            }
         }
     }
 
-    pub(crate) fn get_current_texture(&self) -> Option<RenderTarget> {
+    pub(crate) fn get_current_texture(&self) -> Result<RenderTarget, SurfaceError> {
         match self {
             RenderSurface::Surface(surface) => {
-                let output = match surface.get_current_texture() {
-                    Ok(output) => output,
-                    Err(err) => {
-                        error!("{err}");
-                        return None;
-                    }
-                };
+                let output = surface.get_current_texture()?;
 
                 let view = output
                     .texture
                     .create_view(&TextureViewDescriptor::default());
 
-                Some(RenderTarget::Surface {
+                Ok(RenderTarget::Surface {
                     texture: output,
                     view,
                 })
             }
-            RenderSurface::Headless(Headless { texture, .. }) => {
-                texture.as_ref().map(|t| RenderTarget::Headless {
+            RenderSurface::Headless(Headless { texture, .. }) => texture
+                .as_ref()
+                .map(|t| RenderTarget::Headless {
                     view: t.create_view(&TextureViewDescriptor::default()),
                 })
+                // not configured yet.
+                .ok_or(SurfaceError::Outdated),
+            RenderSurface::Texture(TextureTarget { view, .. }) => {
+                Ok(RenderTarget::Texture { view: view.clone() })
             }
         }
     }
+
+    // Copy the frame to a capture buffer, if capturing.
+    // Must be called before the encoder is submitted.
+    pub(crate) fn capture(&mut self, device: &Device, encoder: &mut CommandEncoder) {
+        if let RenderSurface::Headless(headless) = self {
+            headless.capture(device, encoder);
+        }
+    }
+
+    // Start reading back the frame copied last.
+    // Must be called after the encoder is submitted.
+    pub(crate) fn map_capture(&mut self, submission: SubmissionIndex) {
+        if let RenderSurface::Headless(Headless {
+            capture: Some(ring),
+            ..
+        }) = self
+        {
+            ring.map_last(submission);
+        }
+    }
+}
+
+impl Headless {
+    fn capture(&mut self, device: &Device, encoder: &mut CommandEncoder) {
+        let (Some(ring), Some(texture)) = (&mut self.capture, &self.texture) else {
+            return;
+        };
+        if !ring.active {
+            return;
+        }
+
+        // all buffers are in flight. wait for the oldest.
+        if ring.pending.len() >= CAPTURE_RING_SIZE {
+            let oldest = ring
+                .pending
+                .front()
+                .and_then(|frame| frame.submission.clone());
+            if let Err(e) = device.poll(PollType::Wait {
+                submission_index: oldest,
+                timeout: None,
+            }) {
+                warn!("capture poll failed: {e}");
+            }
+            ring.collect();
+        }
+
+        // buffers of an old size are not needed anymore.
+        let size = self.buffer_width as u64 * self.height as u64;
+        ring.free.retain(|buffer| buffer.size() == size);
+        let buffer = ring.free.pop().unwrap_or_else(|| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Headless Capture"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            })
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.buffer_width),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        ring.pending.push_back(PendingFrame {
+            buffer,
+            bytes_per_row: self.buffer_width,
+            row_len: self.width * self.format.block_copy_size(None).unwrap_or(4),
+            height: self.height,
+            submission: None,
+            mapped: None,
+        });
+    }
+}
+
+impl CaptureRing {
+    fn map_last(&mut self, submission: SubmissionIndex) {
+        let Some(frame) = self.pending.back_mut() else {
+            return;
+        };
+        if frame.mapped.is_some() {
+            return;
+        }
+        frame.submission = Some(submission);
+
+        let mapped: MapResult = Default::default();
+        let mapped_copy = mapped.clone();
+        frame
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                *mapped_copy.lock().expect("lock") = Some(result);
+            });
+        frame.mapped = Some(mapped);
+    }
+
+    // Move the mapped frames to the finished frames, in order.
+    pub(crate) fn collect(&mut self) {
+        while let Some(frame) = self.pending.front() {
+            let Some(result) = frame
+                .mapped
+                .as_ref()
+                .and_then(|mapped| mapped.lock().expect("lock").take())
+            else {
+                break;
+            };
+            let frame = self.pending.pop_front().expect("frame");
+
+            match result {
+                Ok(()) => {
+                    // strip the row padding.
+                    let row_len = frame.row_len as usize;
+                    let mapped = frame.buffer.slice(..).get_mapped_range();
+                    let mut pixels = Vec::with_capacity(row_len * frame.height as usize);
+                    for row in mapped.chunks(frame.bytes_per_row as usize) {
+                        pixels.extend_from_slice(&row[..row_len]);
+                    }
+                    drop(mapped);
+                    frame.buffer.unmap();
+                    self.frames.push_back(pixels);
+                }
+                Err(e) => {
+                    warn!("captured frame dropped: {e}");
+                }
+            }
+            self.free.push(frame.buffer);
+        }
+    }
+
+    // Oldest finished frame.
+    pub(crate) fn next_frame(&mut self) -> Option<Vec<u8>> {
+        self.frames.pop_front()
+    }
+
+    // Frames still on their way.
+    pub(crate) fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
 }
@@ -1,9 +1,12 @@
+use crate::Error;
 use ratatui_core::style::Color;
+use std::collections::HashMap;
 
 pub(crate) type Rgb = [u8; 3];
 
 #[allow(non_snake_case)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ColorTable {
     pub BLACK: Rgb,
     pub RED: Rgb,
@@ -47,10 +50,167 @@ impl ColorTable {
             Color::Indexed(idx) => ANSI_TO_RGB[idx as usize],
         }
     }
+
+    fn base16_mut(&mut self, color: Color) -> Option<&mut Rgb> {
+        match color {
+            Color::Black => Some(&mut self.BLACK),
+            Color::Red => Some(&mut self.RED),
+            Color::Green => Some(&mut self.GREEN),
+            Color::Yellow => Some(&mut self.YELLOW),
+            Color::Blue => Some(&mut self.BLUE),
+            Color::Magenta => Some(&mut self.MAGENTA),
+            Color::Cyan => Some(&mut self.CYAN),
+            Color::Gray => Some(&mut self.GRAY),
+            Color::DarkGray => Some(&mut self.DARKGRAY),
+            Color::LightRed => Some(&mut self.LIGHTRED),
+            Color::LightGreen => Some(&mut self.LIGHTGREEN),
+            Color::LightYellow => Some(&mut self.LIGHTYELLOW),
+            Color::LightBlue => Some(&mut self.LIGHTBLUE),
+            Color::LightMagenta => Some(&mut self.LIGHTMAGENTA),
+            Color::LightCyan => Some(&mut self.LIGHTCYAN),
+            Color::White => Some(&mut self.WHITE),
+            _ => None,
+        }
+    }
 }
 
 impl Default for ColorTable {
     fn default() -> Self {
+        Self::vga()
+    }
+}
+
+impl ColorTable {
+    /// Create a color table from 16 colors in the usual ANSI order.
+    ///
+    /// Black, Red, Green, Yellow, Blue, Magenta, Cyan, Gray,
+    /// DarkGray, LightRed, LightGreen, LightYellow, LightBlue,
+    /// LightMagenta, LightCyan, White.
+    pub fn from_base16(colors: [[u8; 3]; 16]) -> Self {
+        ColorTable {
+            BLACK: colors[0],
+            RED: colors[1],
+            GREEN: colors[2],
+            YELLOW: colors[3],
+            BLUE: colors[4],
+            MAGENTA: colors[5],
+            CYAN: colors[6],
+            GRAY: colors[7],
+            DARKGRAY: colors[8],
+            LIGHTRED: colors[9],
+            LIGHTGREEN: colors[10],
+            LIGHTYELLOW: colors[11],
+            LIGHTBLUE: colors[12],
+            LIGHTMAGENTA: colors[13],
+            LIGHTCYAN: colors[14],
+            WHITE: colors[15],
+        }
+    }
+
+    /// Create a color table from a map of color names to "#RRGGBB".
+    ///
+    /// The names are parsed like ratatui parses a [Color], so "red",
+    /// "dark_gray" or "bright blue" all work. Colors not in the map
+    /// are taken from the [default](ColorTable::vga).
+    pub fn from_hex_map(colors: &HashMap<String, String>) -> Result<Self, Error> {
+        let mut table = Self::default();
+        for (name, hex) in colors {
+            let Some(rgb) = name
+                .parse::<Color>()
+                .ok()
+                .and_then(|color| table.base16_mut(color))
+            else {
+                return Err(Error::InvalidColor(name.clone()));
+            };
+            let Ok(Color::Rgb(r, g, b)) = hex.parse::<Color>() else {
+                return Err(Error::InvalidColor(hex.clone()));
+            };
+            *rgb = [r, g, b];
+        }
+        Ok(table)
+    }
+
+    /// The colors as a map of color names to "#rrggbb".
+    ///
+    /// This is the inverse of [from_hex_map](ColorTable::from_hex_map).
+    pub fn to_hex_map(&self) -> HashMap<String, String> {
+        BASE16_NAMES
+            .iter()
+            .map(|(name, color)| {
+                let [r, g, b] = self.c2c(*color, [0; 3]);
+                (name.to_string(), format!("#{r:02x}{g:02x}{b:02x}"))
+            })
+            .collect()
+    }
+
+    /// Solarized dark.
+    pub fn solarized_dark() -> Self {
+        Self::from_base16([
+            [0x07, 0x36, 0x42],
+            [0xdc, 0x32, 0x2f],
+            [0x85, 0x99, 0x00],
+            [0xb5, 0x89, 0x00],
+            [0x26, 0x8b, 0xd2],
+            [0xd3, 0x36, 0x82],
+            [0x2a, 0xa1, 0x98],
+            [0xee, 0xe8, 0xd5],
+            [0x00, 0x2b, 0x36],
+            [0xcb, 0x4b, 0x16],
+            [0x58, 0x6e, 0x75],
+            [0x65, 0x7b, 0x83],
+            [0x83, 0x94, 0x96],
+            [0x6c, 0x71, 0xc4],
+            [0x93, 0xa1, 0xa1],
+            [0xfd, 0xf6, 0xe3],
+        ])
+    }
+
+    /// Gruvbox dark.
+    pub fn gruvbox_dark() -> Self {
+        Self::from_base16([
+            [0x28, 0x28, 0x28],
+            [0xcc, 0x24, 0x1d],
+            [0x98, 0x97, 0x1a],
+            [0xd7, 0x99, 0x21],
+            [0x45, 0x85, 0x88],
+            [0xb1, 0x62, 0x86],
+            [0x68, 0x9d, 0x6a],
+            [0xa8, 0x99, 0x84],
+            [0x92, 0x83, 0x74],
+            [0xfb, 0x49, 0x34],
+            [0xb8, 0xbb, 0x26],
+            [0xfa, 0xbd, 0x2f],
+            [0x83, 0xa5, 0x98],
+            [0xd3, 0x86, 0x9b],
+            [0x8e, 0xc0, 0x7c],
+            [0xeb, 0xdb, 0xb2],
+        ])
+    }
+
+    /// Dracula.
+    pub fn dracula() -> Self {
+        Self::from_base16([
+            [0x21, 0x22, 0x2c],
+            [0xff, 0x55, 0x55],
+            [0x50, 0xfa, 0x7b],
+            [0xf1, 0xfa, 0x8c],
+            [0xbd, 0x93, 0xf9],
+            [0xff, 0x79, 0xc6],
+            [0x8b, 0xe9, 0xfd],
+            [0xf8, 0xf8, 0xf2],
+            [0x62, 0x72, 0xa4],
+            [0xff, 0x6e, 0x6e],
+            [0x69, 0xff, 0x94],
+            [0xff, 0xff, 0xa5],
+            [0xd6, 0xac, 0xff],
+            [0xff, 0x92, 0xdf],
+            [0xa4, 0xff, 0xff],
+            [0xff, 0xff, 0xff],
+        ])
+    }
+
+    /// Classic VGA colors. This is the default.
+    pub fn vga() -> Self {
         ColorTable {
             BLACK: [0, 0, 0],
             RED: [170, 0, 0],
@@ -72,6 +232,25 @@ impl Default for ColorTable {
     }
 }
 
+const BASE16_NAMES: [(&str, Color); 16] = [
+    ("black", Color::Black),
+    ("red", Color::Red),
+    ("green", Color::Green),
+    ("yellow", Color::Yellow),
+    ("blue", Color::Blue),
+    ("magenta", Color::Magenta),
+    ("cyan", Color::Cyan),
+    ("gray", Color::Gray),
+    ("dark_gray", Color::DarkGray),
+    ("light_red", Color::LightRed),
+    ("light_green", Color::LightGreen),
+    ("light_yellow", Color::LightYellow),
+    ("light_blue", Color::LightBlue),
+    ("light_magenta", Color::LightMagenta),
+    ("light_cyan", Color::LightCyan),
+    ("white", Color::White),
+];
+
 /// This could be split into `[standard table]` + `[high intensity table]` +
 /// `<6x6x6 cube fn>` + `<grayscale step fn>`, but a lookup table is only 768
 /// bytes and way simpler to implement.
@@ -333,3 +512,71 @@ const ANSI_TO_RGB: [Rgb; 256] = [
     [0xe4, 0xe4, 0xe4],
     [0xee, 0xee, 0xee],
 ];
+
+#[cfg(test)]
+mod tests {
+    use crate::colors::ColorTable;
+    use ratatui_core::style::Color;
+    use std::collections::HashMap;
+
+    #[test]
+    fn presets() {
+        assert_eq!(
+            ColorTable::gruvbox_dark().c2c(Color::Red, [0; 3]),
+            [0xcc, 0x24, 0x1d]
+        );
+        assert_eq!(
+            ColorTable::solarized_dark().c2c(Color::Red, [0; 3]),
+            [0xdc, 0x32, 0x2f]
+        );
+        assert_eq!(ColorTable::vga().c2c(Color::Red, [0; 3]), [170, 0, 0]);
+        assert_eq!(
+            ColorTable::default().c2c(Color::LightCyan, [0; 3]),
+            [85, 255, 255]
+        );
+    }
+
+    #[test]
+    fn base16_order() {
+        let mut colors = [[0u8; 3]; 16];
+        for (n, c) in colors.iter_mut().enumerate() {
+            *c = [n as u8; 3];
+        }
+        let table = ColorTable::from_base16(colors);
+        assert_eq!(table.c2c(Color::Black, [0; 3]), [0; 3]);
+        assert_eq!(table.c2c(Color::Gray, [0; 3]), [7; 3]);
+        assert_eq!(table.c2c(Color::DarkGray, [0; 3]), [8; 3]);
+        assert_eq!(table.c2c(Color::White, [0; 3]), [15; 3]);
+        assert_eq!(table.c2c(Color::Reset, [99; 3]), [99; 3]);
+    }
+
+    #[test]
+    fn hex_map() {
+        let table = ColorTable::dracula();
+        assert_eq!(
+            ColorTable::from_hex_map(&table.to_hex_map()).unwrap(),
+            table
+        );
+
+        let mut colors = HashMap::new();
+        colors.insert("Bright Blue".to_string(), "#102030".to_string());
+        let table = ColorTable::from_hex_map(&colors).unwrap();
+        assert_eq!(table.LIGHTBLUE, [0x10, 0x20, 0x30]);
+        assert_eq!(table.RED, ColorTable::vga().RED);
+
+        colors.insert("orange".to_string(), "#102030".to_string());
+        assert!(ColorTable::from_hex_map(&colors).is_err());
+        colors.clear();
+        colors.insert("red".to_string(), "blue".to_string());
+        assert!(ColorTable::from_hex_map(&colors).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let table = ColorTable::solarized_dark();
+        let json = serde_json::to_string(&table).unwrap();
+        assert!(json.contains("\"LIGHTBLUE\":[131,148,150]"));
+        assert_eq!(serde_json::from_str::<ColorTable>(&json).unwrap(), table);
+    }
+}
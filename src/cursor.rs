@@ -11,6 +11,13 @@ pub enum CursorStyle {
     BoldBar,
     RtlBar,
     RtlBoldBar,
+    /// An image scaled to the cursor cell and drawn above the text.
+    ///
+    /// The image is set with
+    /// [set_cursor_image](crate::WgpuBackend::set_cursor_image),
+    /// without one the cursor is not drawn. Blinking and visibility
+    /// work the same as with the other styles.
+    Image,
 }
 
 impl CursorStyle {
@@ -1,4 +1,4 @@
-use crate::WgpuBackend;
+use crate::{CellBox, WgpuBackend};
 
 mod convert_crossterm;
 mod convert_winit;
@@ -27,6 +27,15 @@ pub trait ConvertEvent<Event> {
 
     /// Convert winit event.
     fn convert(&mut self, event: winit::event::WindowEvent) -> Option<Event>;
+
+    /// Events left over from the last [convert](ConvertEvent::convert).
+    ///
+    /// A single winit event can map to more than one event, e.g. a
+    /// pixel scroll over several cells. Call this after convert until
+    /// it returns None.
+    fn convert_pending(&mut self) -> Option<Event> {
+        None
+    }
 }
 
 /// Winit event with extra tracked modifier-state and window-size.
@@ -56,6 +65,20 @@ pub struct WinitEventState {
     x_px: f64,
     /// Mouse cursor in px
     y_px: f64,
+    /// Scale factor of the window.
+    scale_factor: Option<f64>,
+    /// IME is enabled.
+    ime_enabled: bool,
+    /// IME preedit text.
+    ime_preedit: String,
+    /// IME preedit cursor as byte-range.
+    ime_preedit_cursor: Option<(usize, usize)>,
+    /// Cell size. Used for pixel based scrolling.
+    cell_box: CellBox,
+    /// Accumulated pixel scroll delta.
+    scroll_px: (f64, f64),
+    /// Scroll steps of the last pixel delta that are not sent yet.
+    scroll_pending: Option<(crossterm::event::MouseEventKind, u32)>,
 }
 
 impl WinitEventState {
@@ -125,6 +148,25 @@ impl WinitEventState {
         self.window_size_px
     }
 
+    /// Scale factor of the window as reported by the last
+    /// `ScaleFactorChanged` event. Defaults to 1.0.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor.unwrap_or(1.0)
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = Some(scale_factor);
+    }
+
+    /// Cell size of the backend.
+    pub fn cell_box(&self) -> CellBox {
+        self.cell_box
+    }
+
+    pub fn set_cell_box(&mut self, cell_box: CellBox) {
+        self.cell_box = cell_box;
+    }
+
     pub fn set_window_size(
         &mut self,
         window_size: ratatui_core::backend::WindowSize,
@@ -132,6 +174,7 @@ impl WinitEventState {
     ) {
         self.window_size = window_size.columns_rows;
         self.window_size_px = window_size.pixels;
+        self.cell_box = backend.cell_box();
 
         (self.x, self.y) = backend.pos_to_cell((self.x_px as i32, self.y_px as i32));
     }
@@ -168,6 +211,44 @@ impl WinitEventState {
         self.dead_key_released = dc;
     }
 
+    /// IME is currently enabled.
+    ///
+    /// While IME is enabled dead keys are not tracked. The IME does
+    /// the composition and sends the result as commit string.
+    pub fn ime_enabled(&self) -> bool {
+        self.ime_enabled
+    }
+
+    pub fn set_ime_enabled(&mut self, enabled: bool) {
+        self.ime_enabled = enabled;
+        self.dead_key_pressed = None;
+        self.dead_key_released = None;
+        if !enabled {
+            self.ime_preedit.clear();
+            self.ime_preedit_cursor = None;
+        }
+    }
+
+    /// Current IME preedit text. This is the text that is being
+    /// composed and not yet committed. The application should render
+    /// it at the text cursor, usually underlined.
+    ///
+    /// Empty if there is no composition active.
+    pub fn ime_preedit(&self) -> &str {
+        self.ime_preedit.as_str()
+    }
+
+    /// Cursor position in the preedit text as byte-range.
+    /// None if the cursor should be hidden.
+    pub fn ime_preedit_cursor(&self) -> Option<(usize, usize)> {
+        self.ime_preedit_cursor
+    }
+
+    pub fn set_ime_preedit(&mut self, text: String, cursor: Option<(usize, usize)>) {
+        self.ime_preedit = text;
+        self.ime_preedit_cursor = cursor;
+    }
+
     pub fn left_pressed(&self) -> bool {
         (self.modifiers & WinitEventState::LEFT) != 0
     }
@@ -258,7 +339,26 @@ impl WinitEventState {
                 self.x = 0;
                 self.y = 0;
             }
-            winit::event::WindowEvent::MouseWheel { .. } => {}
+            winit::event::WindowEvent::MouseWheel { .. } => {
+                self.cell_box = backend.cell_box();
+            }
+            winit::event::WindowEvent::Ime(ime) => match ime {
+                winit::event::Ime::Enabled => {
+                    self.set_ime_enabled(true);
+                }
+                winit::event::Ime::Preedit(text, cursor) => {
+                    self.set_ime_preedit(text.clone(), *cursor);
+                }
+                winit::event::Ime::Commit(_) => {
+                    self.set_ime_preedit(String::default(), None);
+                }
+                winit::event::Ime::Disabled => {
+                    self.set_ime_enabled(false);
+                }
+            },
+            winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.set_scale_factor(*scale_factor);
+            }
             winit::event::WindowEvent::MouseInput { state, button, .. } => {
                 let pressed = match state {
                     winit::event::ElementState::Pressed => true,
@@ -5,6 +5,10 @@ use crate::WgpuBackend;
 ///
 /// Unmappable events are dropped.
 ///
+/// IME commit strings are sent as [crossterm::event::Event::Paste].
+/// The preedit text is tracked in the [WinitEventState], use
+/// [WinitEventState::ime_preedit] to render it.
+///
 /// __Requires__
 ///
 /// This requires a From<crossterm::event::Event> conversion for your target type.
@@ -46,7 +50,11 @@ pub struct ConvertCrossterm {
 
 /// Convert winit-events to crossterm-events.
 ///
-/// Any unconvertible events will be sent as a [CompositeWinitEvent]
+/// Any unconvertible events will be sent as a [CompositeWinitEvent].
+/// This includes the Back/Forward mouse buttons, crossterm has no
+/// equivalent for those.
+/// This includes IME preedit events, the preedit text is available
+/// via [WinitEventState::ime_preedit].
 ///
 /// __Requires__
 ///
@@ -92,6 +100,10 @@ where
             None
         }
     }
+
+    fn convert_pending(&mut self) -> Option<Event> {
+        pending_crossterm_event(&mut self.state).map(|v| v.into())
+    }
 }
 
 impl ConvertCrosstermEx {
@@ -135,6 +147,26 @@ where
             )
         }
     }
+
+    fn convert_pending(&mut self) -> Option<Event> {
+        pending_crossterm_event(&mut self.state).map(|v| v.into())
+    }
+}
+
+/// Scroll events left over from the last pixel delta.
+fn pending_crossterm_event(state: &mut WinitEventState) -> Option<crossterm::event::Event> {
+    let (kind, count) = state.scroll_pending.take()?;
+    if count > 1 {
+        state.scroll_pending = Some((kind, count - 1));
+    }
+    Some(crossterm::event::Event::Mouse(
+        crossterm::event::MouseEvent {
+            kind,
+            column: state.x,
+            row: state.y,
+            modifiers: map_modifiers(state),
+        },
+    ))
 }
 
 #[allow(dead_code)]
@@ -150,6 +182,14 @@ fn to_crossterm_event(
                     state.window_size.height,
                 )) //
             }
+            winit::event::WindowEvent::ScaleFactorChanged { .. } => {
+                // the application should call WgpuBackend::set_scale_factor()
+                // and update the window-size. this is the notification.
+                Some(crossterm::event::Event::Resize(
+                    state.window_size.width,
+                    state.window_size.height,
+                )) //
+            }
             winit::event::WindowEvent::Focused(v) => {
                 if *v {
                     Some(crossterm::event::Event::FocusGained)
@@ -204,6 +244,10 @@ fn to_crossterm_event(
                             None
                         }
                     }
+                    winit::keyboard::Key::Dead(_) if state.ime_enabled => {
+                        // the IME does the composition.
+                        None
+                    }
                     winit::keyboard::Key::Dead(v) => {
                         if *element_state == winit::event::ElementState::Pressed {
                             track_dead_key(
@@ -274,22 +318,48 @@ fn to_crossterm_event(
                 }
             }
             winit::event::WindowEvent::MouseWheel {
-                delta: winit::event::MouseScrollDelta::PixelDelta(_),
+                delta: winit::event::MouseScrollDelta::PixelDelta(delta),
                 ..
-            } => None,
+            } => {
+                let Some((kind, count)) = accumulate_pixel_scroll(state, delta.x, delta.y) else {
+                    state.scroll_pending = None;
+                    break 'm None;
+                };
+                state.scroll_pending = (count > 1).then_some((kind, count - 1));
+                let ct_key_modifiers = map_modifiers(state);
+
+                Some(crossterm::event::Event::Mouse(
+                    crossterm::event::MouseEvent {
+                        kind,
+                        column: state.x,
+                        row: state.y,
+                        modifiers: ct_key_modifiers,
+                    },
+                ))
+            }
             winit::event::WindowEvent::MouseWheel {
-                delta: winit::event::MouseScrollDelta::LineDelta(_horizontal, vertical),
+                delta: winit::event::MouseScrollDelta::LineDelta(horizontal, vertical),
                 ..
             } => {
+                // both axes may change with one event. use the dominant one.
+                let kind = if *horizontal == 0.0 && *vertical == 0.0 {
+                    break 'm None;
+                } else if vertical.abs() >= horizontal.abs() {
+                    if *vertical > 0.0 {
+                        crossterm::event::MouseEventKind::ScrollUp
+                    } else {
+                        crossterm::event::MouseEventKind::ScrollDown
+                    }
+                } else if *horizontal > 0.0 {
+                    crossterm::event::MouseEventKind::ScrollLeft
+                } else {
+                    crossterm::event::MouseEventKind::ScrollRight
+                };
                 let ct_key_modifiers = map_modifiers(&state);
 
                 Some(crossterm::event::Event::Mouse(
                     crossterm::event::MouseEvent {
-                        kind: if *vertical > 0.0 {
-                            crossterm::event::MouseEventKind::ScrollUp
-                        } else {
-                            crossterm::event::MouseEventKind::ScrollDown
-                        },
+                        kind,
                         column: state.x,
                         row: state.y,
                         modifiers: ct_key_modifiers,
@@ -325,6 +395,9 @@ fn to_crossterm_event(
             winit::event::WindowEvent::HoveredFile(_) => None,
             winit::event::WindowEvent::HoveredFileCancelled => None,
             winit::event::WindowEvent::ModifiersChanged(_) => None,
+            winit::event::WindowEvent::Ime(winit::event::Ime::Commit(text)) => {
+                Some(crossterm::event::Event::Paste(text.clone()))
+            }
             winit::event::WindowEvent::Ime(_) => None,
             winit::event::WindowEvent::CursorEntered { .. } => None,
             winit::event::WindowEvent::CursorLeft { .. } => None,
@@ -335,7 +408,6 @@ fn to_crossterm_event(
             winit::event::WindowEvent::TouchpadPressure { .. } => None,
             winit::event::WindowEvent::AxisMotion { .. } => None,
             winit::event::WindowEvent::Touch(_) => None,
-            winit::event::WindowEvent::ScaleFactorChanged { .. } => None,
             winit::event::WindowEvent::ThemeChanged(_) => None,
             winit::event::WindowEvent::Occluded(_) => None,
             winit::event::WindowEvent::RedrawRequested => None,
@@ -343,6 +415,51 @@ fn to_crossterm_event(
     }
 }
 
+/// Accumulate pixel deltas until they add up to full cells.
+/// Returns the direction and the number of scroll steps, the
+/// remainder is kept for the next event. A change of direction
+/// drops the remainder.
+fn accumulate_pixel_scroll(
+    state: &mut WinitEventState,
+    dx: f64,
+    dy: f64,
+) -> Option<(crossterm::event::MouseEventKind, u32)> {
+    let step_x = state.cell_box.width.max(1) as f64;
+    let step_y = state.cell_box.height.max(1) as f64;
+
+    if dx != 0.0 && dx.signum() != state.scroll_px.0.signum() {
+        state.scroll_px.0 = 0.0;
+    }
+    if dy != 0.0 && dy.signum() != state.scroll_px.1.signum() {
+        state.scroll_px.1 = 0.0;
+    }
+    state.scroll_px.0 += dx;
+    state.scroll_px.1 += dy;
+
+    let steps_x = (state.scroll_px.0 / step_x).trunc();
+    let steps_y = (state.scroll_px.1 / step_y).trunc();
+
+    if steps_y != 0.0 {
+        state.scroll_px.1 -= steps_y * step_y;
+        let kind = if steps_y > 0.0 {
+            crossterm::event::MouseEventKind::ScrollUp
+        } else {
+            crossterm::event::MouseEventKind::ScrollDown
+        };
+        Some((kind, steps_y.abs() as u32))
+    } else if steps_x != 0.0 {
+        state.scroll_px.0 -= steps_x * step_x;
+        let kind = if steps_x > 0.0 {
+            crossterm::event::MouseEventKind::ScrollLeft
+        } else {
+            crossterm::event::MouseEventKind::ScrollRight
+        };
+        Some((kind, steps_x.abs() as u32))
+    } else {
+        None
+    }
+}
+
 fn track_dead_key(
     dead_key: &mut Option<char>,
     v: Option<char>,
@@ -617,3 +734,190 @@ fn create_mouse_event_kind(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::CellBox;
+    use crate::events::convert_crossterm::{pending_crossterm_event, to_crossterm_event};
+    use crate::events::{
+        CompositeWinitEvent, ConvertCrossterm, ConvertCrosstermEx, ConvertEvent, WinitEventState,
+    };
+    use crossterm::event::{Event, MouseEventKind};
+    use winit::dpi::PhysicalPosition;
+    use winit::event::{
+        DeviceId, ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent,
+    };
+
+    fn wheel(delta: MouseScrollDelta) -> WindowEvent {
+        WindowEvent::MouseWheel {
+            device_id: DeviceId::dummy(),
+            delta,
+            phase: TouchPhase::Moved,
+        }
+    }
+
+    fn scroll_kind(event: Option<Event>) -> Option<MouseEventKind> {
+        match event {
+            Some(Event::Mouse(m)) => Some(m.kind),
+            _ => None,
+        }
+    }
+
+    fn state() -> WinitEventState {
+        let mut state = WinitEventState::new();
+        state.set_cell_box(CellBox {
+            width: 10,
+            height: 20,
+            ascender: 16,
+        });
+        state
+    }
+
+    #[test]
+    fn pixel_delta_accumulates() {
+        let mut state = state();
+
+        let ev = wheel(MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+            0.0, 8.0,
+        )));
+        assert_eq!(scroll_kind(to_crossterm_event(&mut state, &ev)), None);
+        assert_eq!(scroll_kind(to_crossterm_event(&mut state, &ev)), None);
+        assert_eq!(
+            scroll_kind(to_crossterm_event(&mut state, &ev)),
+            Some(MouseEventKind::ScrollUp)
+        );
+        assert_eq!(scroll_kind(to_crossterm_event(&mut state, &ev)), None);
+
+        // one event per cell.
+        let ev = wheel(MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+            0.0, -50.0,
+        )));
+        assert_eq!(
+            scroll_kind(to_crossterm_event(&mut state, &ev)),
+            Some(MouseEventKind::ScrollDown)
+        );
+        assert_eq!(
+            scroll_kind(pending_crossterm_event(&mut state)),
+            Some(MouseEventKind::ScrollDown)
+        );
+        assert_eq!(scroll_kind(pending_crossterm_event(&mut state)), None);
+    }
+
+    #[test]
+    fn pixel_delta_horizontal() {
+        let mut state = state();
+
+        let ev = wheel(MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+            -15.0, 0.0,
+        )));
+        assert_eq!(
+            scroll_kind(to_crossterm_event(&mut state, &ev)),
+            Some(MouseEventKind::ScrollRight)
+        );
+
+        // the remainder of the other direction is dropped.
+        let ev = wheel(MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+            6.0, 0.0,
+        )));
+        assert_eq!(scroll_kind(to_crossterm_event(&mut state, &ev)), None);
+        assert_eq!(
+            scroll_kind(to_crossterm_event(&mut state, &ev)),
+            Some(MouseEventKind::ScrollLeft)
+        );
+    }
+
+    #[test]
+    fn pixel_delta_pending() {
+        let mut convert = ConvertCrossterm::new();
+        convert.state.set_cell_box(CellBox {
+            width: 10,
+            height: 20,
+            ascender: 16,
+        });
+
+        let ev = wheel(MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+            35.0, 0.0,
+        )));
+        let mut kinds = Vec::new();
+        let event: Option<Event> = convert.convert(ev);
+        kinds.extend(scroll_kind(event));
+        while let Some(event) = ConvertEvent::<Event>::convert_pending(&mut convert) {
+            kinds.extend(scroll_kind(Some(event)));
+        }
+        assert_eq!(kinds, vec![MouseEventKind::ScrollLeft; 3]);
+    }
+
+    #[test]
+    fn line_delta_horizontal() {
+        let mut state = state();
+
+        let ev = wheel(MouseScrollDelta::LineDelta(1.0, 0.0));
+        assert_eq!(
+            scroll_kind(to_crossterm_event(&mut state, &ev)),
+            Some(MouseEventKind::ScrollLeft)
+        );
+        let ev = wheel(MouseScrollDelta::LineDelta(-1.0, 0.0));
+        assert_eq!(
+            scroll_kind(to_crossterm_event(&mut state, &ev)),
+            Some(MouseEventKind::ScrollRight)
+        );
+        let ev = wheel(MouseScrollDelta::LineDelta(0.0, 0.0));
+        assert_eq!(scroll_kind(to_crossterm_event(&mut state, &ev)), None);
+    }
+
+    #[test]
+    fn line_delta_dominant_axis() {
+        let mut state = state();
+
+        let ev = wheel(MouseScrollDelta::LineDelta(-2.0, 0.5));
+        assert_eq!(
+            scroll_kind(to_crossterm_event(&mut state, &ev)),
+            Some(MouseEventKind::ScrollRight)
+        );
+        let ev = wheel(MouseScrollDelta::LineDelta(0.5, -2.0));
+        assert_eq!(
+            scroll_kind(to_crossterm_event(&mut state, &ev)),
+            Some(MouseEventKind::ScrollDown)
+        );
+    }
+
+    #[derive(Debug)]
+    enum TestEvent {
+        Crossterm(Event),
+        Winit(Box<CompositeWinitEvent>),
+    }
+
+    impl From<Event> for TestEvent {
+        fn from(value: Event) -> Self {
+            TestEvent::Crossterm(value)
+        }
+    }
+
+    impl From<CompositeWinitEvent> for TestEvent {
+        fn from(value: CompositeWinitEvent) -> Self {
+            TestEvent::Winit(Box::new(value))
+        }
+    }
+
+    #[test]
+    fn back_forward() {
+        let mut convert = ConvertCrosstermEx::new();
+
+        for button in [MouseButton::Back, MouseButton::Forward] {
+            let ev = WindowEvent::MouseInput {
+                device_id: DeviceId::dummy(),
+                state: ElementState::Pressed,
+                button,
+            };
+            let event: Option<TestEvent> = convert.convert(ev);
+            match event {
+                Some(TestEvent::Winit(ev)) => match ev.event {
+                    WindowEvent::MouseInput { button: b, .. } => assert_eq!(b, button),
+                    ev => panic!("unexpected {:?}", ev),
+                },
+                Some(TestEvent::Crossterm(ev)) => panic!("unexpected {:?}", ev),
+                None => panic!("no event"),
+            }
+        }
+    }
+}
@@ -0,0 +1,561 @@
+use std::ops::Range;
+
+// Font id used in the glyph cache for the builtin box-drawing chars.
+pub(crate) const BOX_DRAWING_FONT_ID: u64 = u64::MAX;
+
+const NONE: u8 = 0;
+const LIGHT: u8 = 1;
+const HEAVY: u8 = 2;
+const DOUBLE: u8 = 3;
+
+// Line style for up, right, down and left of each char in
+// U+2500..=U+257F. Dashes, arcs and diagonals are handled in code.
+#[rustfmt::skip]
+const LINES: [[u8; 4]; 128] = [
+    [0, 1, 0, 1], [0, 2, 0, 2], [1, 0, 1, 0], [2, 0, 2, 0], // ─━│┃
+    [0, 1, 0, 1], [0, 2, 0, 2], [1, 0, 1, 0], [2, 0, 2, 0], // ┄┅┆┇
+    [0, 1, 0, 1], [0, 2, 0, 2], [1, 0, 1, 0], [2, 0, 2, 0], // ┈┉┊┋
+    [0, 1, 1, 0], [0, 2, 1, 0], [0, 1, 2, 0], [0, 2, 2, 0], // ┌┍┎┏
+    [0, 0, 1, 1], [0, 0, 1, 2], [0, 0, 2, 1], [0, 0, 2, 2], // ┐┑┒┓
+    [1, 1, 0, 0], [1, 2, 0, 0], [2, 1, 0, 0], [2, 2, 0, 0], // └┕┖┗
+    [1, 0, 0, 1], [1, 0, 0, 2], [2, 0, 0, 1], [2, 0, 0, 2], // ┘┙┚┛
+    [1, 1, 1, 0], [1, 2, 1, 0], [2, 1, 1, 0], [1, 1, 2, 0], // ├┝┞┟
+    [2, 1, 2, 0], [2, 2, 1, 0], [1, 2, 2, 0], [2, 2, 2, 0], // ┠┡┢┣
+    [1, 0, 1, 1], [1, 0, 1, 2], [2, 0, 1, 1], [1, 0, 2, 1], // ┤┥┦┧
+    [2, 0, 2, 1], [2, 0, 1, 2], [1, 0, 2, 2], [2, 0, 2, 2], // ┨┩┪┫
+    [0, 1, 1, 1], [0, 1, 1, 2], [0, 2, 1, 1], [0, 2, 1, 2], // ┬┭┮┯
+    [0, 1, 2, 1], [0, 1, 2, 2], [0, 2, 2, 1], [0, 2, 2, 2], // ┰┱┲┳
+    [1, 1, 0, 1], [1, 1, 0, 2], [1, 2, 0, 1], [1, 2, 0, 2], // ┴┵┶┷
+    [2, 1, 0, 1], [2, 1, 0, 2], [2, 2, 0, 1], [2, 2, 0, 2], // ┸┹┺┻
+    [1, 1, 1, 1], [1, 1, 1, 2], [1, 2, 1, 1], [1, 2, 1, 2], // ┼┽┾┿
+    [2, 1, 1, 1], [1, 1, 2, 1], [2, 1, 2, 1], [2, 1, 1, 2], // ╀╁╂╃
+    [2, 2, 1, 1], [1, 1, 2, 2], [1, 2, 2, 1], [2, 2, 1, 2], // ╄╅╆╇
+    [1, 2, 2, 2], [2, 1, 2, 2], [2, 2, 2, 1], [2, 2, 2, 2], // ╈╉╊╋
+    [0, 1, 0, 1], [0, 2, 0, 2], [1, 0, 1, 0], [2, 0, 2, 0], // ╌╍╎╏
+    [0, 3, 0, 3], [3, 0, 3, 0], [0, 3, 1, 0], [0, 1, 3, 0], // ═║╒╓
+    [0, 3, 3, 0], [0, 0, 1, 3], [0, 0, 3, 1], [0, 0, 3, 3], // ╔╕╖╗
+    [1, 3, 0, 0], [3, 1, 0, 0], [3, 3, 0, 0], [1, 0, 0, 3], // ╘╙╚╛
+    [3, 0, 0, 1], [3, 0, 0, 3], [1, 3, 1, 0], [3, 1, 3, 0], // ╜╝╞╟
+    [3, 3, 3, 0], [1, 0, 1, 3], [3, 0, 3, 1], [3, 0, 3, 3], // ╠╡╢╣
+    [0, 3, 1, 3], [0, 1, 3, 1], [0, 3, 3, 3], [1, 3, 0, 3], // ╤╥╦╧
+    [3, 1, 0, 1], [3, 3, 0, 3], [1, 3, 1, 3], [3, 1, 3, 1], // ╨╩╪╫
+    [3, 3, 3, 3], [0, 1, 1, 0], [0, 0, 1, 1], [1, 0, 0, 1], // ╬╭╮╯
+    [1, 1, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], // ╰╱╲╳
+    [0, 0, 0, 1], [1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], // ╴╵╶╷
+    [0, 0, 0, 2], [2, 0, 0, 0], [0, 2, 0, 0], [0, 0, 2, 0], // ╸╹╺╻
+    [0, 2, 0, 1], [1, 0, 2, 0], [0, 1, 0, 2], [2, 0, 1, 0], // ╼╽╾╿
+];
+
+/// Box-drawing (U+2500..=U+257F), block-element (U+2580..=U+259F)
+/// and Braille (U+2800..=U+28FF) chars.
+pub(crate) fn is_box_char(ch: char) -> bool {
+    ('\u{2500}'..='\u{259F}').contains(&ch) || ('\u{2800}'..='\u{28FF}').contains(&ch)
+}
+
+/// Draws a box-drawing, block-element or Braille char to fill exactly
+/// one cell of the given size. Lines end at the cell edges so adjacent
+/// cells join without gaps. Braille dots sit on a regular 2x4 grid.
+///
+/// Returns white with the coverage as alpha, the same as glyphs
+/// rasterized from a font.
+pub(crate) fn rasterize_box_char(ch: char, width: u32, height: u32) -> Vec<u32> {
+    let mut canvas = Canvas::new(width, height);
+
+    let cp = ch as u32;
+    match cp {
+        0x2504..=0x250B => {
+            // triple and quadruple dash.
+            let dashes = if cp < 0x2508 { 3 } else { 4 };
+            canvas.lines(LINES[(cp - 0x2500) as usize], dashes);
+        }
+        0x254C..=0x254F => {
+            canvas.lines(LINES[(cp - 0x2500) as usize], 2);
+        }
+        0x256D..=0x2570 => {
+            canvas.arc(cp);
+        }
+        0x2571..=0x2573 => {
+            canvas.diagonal(cp);
+        }
+        0x2500..=0x257F => {
+            canvas.lines(LINES[(cp - 0x2500) as usize], 1);
+        }
+        0x2580..=0x259F => {
+            canvas.block(cp);
+        }
+        0x2800..=0x28FF => {
+            canvas.braille(cp);
+        }
+        _ => {}
+    }
+
+    canvas.into_image()
+}
+
+struct Canvas {
+    width: u32,
+    height: u32,
+    // width of a light line. heavy is twice, double is three times that.
+    light: u32,
+    alpha: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            light: (width.min(height) / 8).max(1),
+            alpha: vec![0; width as usize * height as usize],
+        }
+    }
+
+    fn into_image(self) -> Vec<u32> {
+        self.alpha
+            .into_iter()
+            .map(|a| u32::from_le_bytes([a, a, a, a]))
+            .collect()
+    }
+
+    fn fill(&mut self, x: Range<u32>, y: Range<u32>, alpha: u8) {
+        for yy in y.start..y.end.min(self.height) {
+            for xx in x.start..x.end.min(self.width) {
+                let px = &mut self.alpha[(yy * self.width + xx) as usize];
+                *px = (*px).max(alpha);
+            }
+        }
+    }
+
+    fn clear(&mut self, x: Range<u32>, y: Range<u32>) {
+        for yy in y.start..y.end.min(self.height) {
+            for xx in x.start..x.end.min(self.width) {
+                self.alpha[(yy * self.width + xx) as usize] = 0;
+            }
+        }
+    }
+
+    // Anti-aliased shapes. Samples each pixel 4x4 times.
+    fn cover(&mut self, inside: impl Fn(f32, f32) -> bool) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut n = 0;
+                for sy in 0..4 {
+                    for sx in 0..4 {
+                        let px = x as f32 + (sx as f32 + 0.5) / 4.0;
+                        let py = y as f32 + (sy as f32 + 0.5) / 4.0;
+                        if inside(px, py) {
+                            n += 1;
+                        }
+                    }
+                }
+                let px = &mut self.alpha[(y * self.width + x) as usize];
+                *px = (*px).max((n * 255 / 16) as u8);
+            }
+        }
+    }
+
+    // Outer range across a line of the given style, and the gap
+    // between the two strokes of a double line.
+    fn band(&self, len: u32, style: u8) -> (Range<u32>, Range<u32>) {
+        let thickness = match style {
+            HEAVY => self.light * 2,
+            DOUBLE => self.light * 3,
+            _ => self.light,
+        };
+        let start = len.saturating_sub(thickness) / 2;
+        let outer = start..start + thickness;
+        let inner = if style == DOUBLE {
+            start + self.light..start + self.light * 2
+        } else {
+            outer.clone()
+        };
+        (outer, inner)
+    }
+
+    // Where an arm ends at the center. The arm runs from the edge
+    // at `far` to the returned outer and inner limit.
+    //
+    // Arms with an opposite arm meet in the middle. Otherwise they
+    // reach across the lines of the perpendicular arms. Single lines
+    // stop at the near stroke of a double line that passes through.
+    fn arm_limit(
+        &self,
+        len: u32,
+        style: u8,
+        opposite: u8,
+        perpendicular: [u8; 2],
+        towards_start: bool,
+    ) -> (u32, u32) {
+        if opposite != NONE || perpendicular == [NONE, NONE] {
+            return (len / 2, len / 2);
+        }
+
+        let mut outer = if towards_start { len } else { 0 };
+        let mut inner = outer;
+        for perp in perpendicular.into_iter().filter(|v| *v != NONE) {
+            let (perp_outer, perp_inner) = self.band(len, perp);
+            let (o, i) = if perp == DOUBLE && style != DOUBLE && perpendicular == [DOUBLE; 2] {
+                // the near stroke.
+                if towards_start {
+                    let start = perp_outer.end - self.light;
+                    (start, start)
+                } else {
+                    let end = perp_outer.start + self.light;
+                    (end, end)
+                }
+            } else if perp == DOUBLE {
+                if towards_start {
+                    (perp_outer.start, perp_inner.start)
+                } else {
+                    (perp_outer.end, perp_inner.end)
+                }
+            } else if towards_start {
+                (perp_outer.start, perp_outer.start)
+            } else {
+                (perp_outer.end, perp_outer.end)
+            };
+            if towards_start {
+                outer = outer.min(o);
+                inner = inner.min(i);
+            } else {
+                outer = outer.max(o);
+                inner = inner.max(i);
+            }
+        }
+        (outer, inner)
+    }
+
+    // Lines from the center to the edges.
+    fn lines(&mut self, [up, right, down, left]: [u8; 4], dashes: u32) {
+        let (width, height) = (self.width, self.height);
+
+        // x and y range of each arm, outer and inner.
+        let mut arms = Vec::new();
+        if up != NONE {
+            let (x, x_inner) = self.band(width, up);
+            let (end, end_inner) = self.arm_limit(height, up, down, [left, right], false);
+            arms.push((up, (x, 0..end), (x_inner, 0..end_inner)));
+        }
+        if down != NONE {
+            let (x, x_inner) = self.band(width, down);
+            let (start, start_inner) = self.arm_limit(height, down, up, [left, right], true);
+            arms.push((down, (x, start..height), (x_inner, start_inner..height)));
+        }
+        if left != NONE {
+            let (y, y_inner) = self.band(height, left);
+            let (end, end_inner) = self.arm_limit(width, left, right, [up, down], false);
+            arms.push((left, (0..end, y), (0..end_inner, y_inner)));
+        }
+        if right != NONE {
+            let (y, y_inner) = self.band(height, right);
+            let (start, start_inner) = self.arm_limit(width, right, left, [up, down], true);
+            arms.push((right, (start..width, y), (start_inner..width, y_inner)));
+        }
+
+        // double lines are a wide line with the middle cut out.
+        // single lines go on top, they cross the gap of a double line.
+        for (_, (x, y), _) in arms.iter().filter(|v| v.0 == DOUBLE) {
+            self.fill(x.clone(), y.clone(), 255);
+        }
+        for (_, _, (x, y)) in arms.iter().filter(|v| v.0 == DOUBLE) {
+            self.clear(x.clone(), y.clone());
+        }
+        for (_, (x, y), _) in arms.iter().filter(|v| v.0 != DOUBLE) {
+            self.fill(x.clone(), y.clone(), 255);
+        }
+
+        if dashes > 1 {
+            let vertical = up != NONE;
+            let len = if vertical { height } else { width };
+            let gap = (len / dashes / 3).max(1);
+            for i in 1..=dashes {
+                // half the gap at either end of each dash.
+                let end = len * i / dashes;
+                let start = len * (i - 1) / dashes;
+                for cut in [
+                    start..start + gap / 2,
+                    end.saturating_sub(gap - gap / 2)..end,
+                ] {
+                    if vertical {
+                        self.clear(0..width, cut);
+                    } else {
+                        self.clear(cut, 0..height);
+                    }
+                }
+            }
+        }
+    }
+
+    // Rounded corners.
+    fn arc(&mut self, cp: u32) {
+        let (width, height) = (self.width, self.height);
+        // towards right/down or left/up
+        let (dx, dy) = match cp {
+            0x256D => (1.0, 1.0),
+            0x256E => (-1.0, 1.0),
+            0x256F => (-1.0, -1.0),
+            _ => (1.0, -1.0),
+        };
+
+        // same position as the straight lines.
+        let (x_band, _) = self.band(width, LIGHT);
+        let (y_band, _) = self.band(height, LIGHT);
+        let cx = (x_band.start + x_band.end) as f32 / 2.0;
+        let cy = (y_band.start + y_band.end) as f32 / 2.0;
+
+        let radius_x = if dx > 0.0 { width as f32 - cx } else { cx };
+        let radius_y = if dy > 0.0 { height as f32 - cy } else { cy };
+        // keep a straight piece at the edges, so they match the lines.
+        let radius = (radius_x.min(radius_y) - 1.0).max(1.0);
+        let center_x = cx + dx * radius;
+        let center_y = cy + dy * radius;
+
+        let half = self.light as f32 / 2.0;
+        self.cover(|x, y| {
+            let in_quadrant = (x - center_x) * dx <= 0.0 && (y - center_y) * dy <= 0.0;
+            let distance = ((x - center_x).powi(2) + (y - center_y).powi(2)).sqrt();
+            in_quadrant && (distance - radius).abs() <= half
+        });
+
+        // the rest is straight.
+        let center_x = center_x.round() as u32;
+        let center_y = center_y.round() as u32;
+        let x_range = if dx > 0.0 {
+            center_x..width
+        } else {
+            0..center_x
+        };
+        let y_range = if dy > 0.0 {
+            center_y..height
+        } else {
+            0..center_y
+        };
+        self.fill(x_range, y_band, 255);
+        self.fill(x_band, y_range, 255);
+    }
+
+    fn diagonal(&mut self, cp: u32) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let length = (width * width + height * height).sqrt();
+        let half = self.light as f32 / 2.0;
+
+        let falling = cp == 0x2572 || cp == 0x2573;
+        let rising = cp == 0x2571 || cp == 0x2573;
+        self.cover(|x, y| {
+            // distance to the diagonals from corner to corner.
+            let to_falling = (height * x - width * y).abs() / length;
+            let to_rising = (height * x + width * y - width * height).abs() / length;
+            falling && to_falling <= half || rising && to_rising <= half
+        });
+    }
+
+    fn block(&mut self, cp: u32) {
+        let (width, height) = (self.width, self.height);
+        let eighths = |len: u32, n: u32| (len * n + 4) / 8;
+        let half_x = eighths(width, 4);
+        let half_y = eighths(height, 4);
+
+        match cp {
+            0x2580 => self.fill(0..width, 0..half_y, 255),
+            0x2581..=0x2588 => {
+                let n = cp - 0x2580;
+                self.fill(0..width, height - eighths(height, n)..height, 255)
+            }
+            0x2589..=0x258F => {
+                let n = 0x2590 - cp;
+                self.fill(0..eighths(width, n), 0..height, 255)
+            }
+            0x2590 => self.fill(half_x..width, 0..height, 255),
+            0x2591 => self.fill(0..width, 0..height, 64),
+            0x2592 => self.fill(0..width, 0..height, 128),
+            0x2593 => self.fill(0..width, 0..height, 191),
+            0x2594 => self.fill(0..width, 0..eighths(height, 1), 255),
+            0x2595 => self.fill(width - eighths(width, 1)..width, 0..height, 255),
+            _ => {
+                // quadrants: upper left, upper right, lower left, lower right.
+                let quadrants = match cp {
+                    0x2596 => [false, false, true, false],
+                    0x2597 => [false, false, false, true],
+                    0x2598 => [true, false, false, false],
+                    0x2599 => [true, false, true, true],
+                    0x259A => [true, false, false, true],
+                    0x259B => [true, true, true, false],
+                    0x259C => [true, true, false, true],
+                    0x259D => [false, true, false, false],
+                    0x259E => [false, true, true, false],
+                    _ => [false, true, true, true],
+                };
+                let ranges = [
+                    (0..half_x, 0..half_y),
+                    (half_x..width, 0..half_y),
+                    (0..half_x, half_y..height),
+                    (half_x..width, half_y..height),
+                ];
+                for ((x, y), set) in ranges.into_iter().zip(quadrants) {
+                    if set {
+                        self.fill(x, y, 255);
+                    }
+                }
+            }
+        }
+    }
+
+    // Square dots, each centered in its part of the 2x4 grid.
+    fn braille(&mut self, cp: u32) {
+        let (width, height) = (self.width, self.height);
+        let size = (width / 4).max(1);
+
+        // dot 1-3 and 7 are the left column, 4-6 and 8 the right one.
+        const DOTS: [(u32, u32); 8] = [
+            (0, 0),
+            (0, 1),
+            (0, 2),
+            (1, 0),
+            (1, 1),
+            (1, 2),
+            (0, 3),
+            (1, 3),
+        ];
+        for (bit, (col, row)) in DOTS.into_iter().enumerate() {
+            if cp & (1 << bit) != 0 {
+                let x = (width * col / 2 + width * (col + 1) / 2).saturating_sub(size) / 2;
+                let y = (height * row / 4 + height * (row + 1) / 4).saturating_sub(size) / 2;
+                self.fill(x..x + size, y..y + size, 255);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::box_drawing::{HEAVY, LIGHT, LINES, NONE, rasterize_box_char};
+
+    const SIZES: [(u32, u32); 3] = [(8, 16), (13, 27), (20, 41)];
+
+    fn column(img: &[u32], width: u32, height: u32, x: u32) -> Vec<u32> {
+        (0..height).map(|y| img[(y * width + x) as usize]).collect()
+    }
+
+    fn row(img: &[u32], width: u32, y: u32) -> Vec<u32> {
+        img[(y * width) as usize..((y + 1) * width) as usize].to_vec()
+    }
+
+    #[test]
+    fn all_chars() {
+        for (width, height) in SIZES {
+            for ch in '\u{2500}'..='\u{259F}' {
+                let img = rasterize_box_char(ch, width, height);
+                assert_eq!(img.len(), (width * height) as usize);
+                assert!(img.iter().any(|v| *v != 0), "{:?}", ch);
+            }
+        }
+    }
+
+    #[test]
+    fn seamless() {
+        // the edges of each char match the straight lines of the same style.
+        for (width, height) in SIZES {
+            let horizontal = |style: u8| {
+                let ch = match style {
+                    LIGHT => '─',
+                    HEAVY => '━',
+                    _ => '═',
+                };
+                column(&rasterize_box_char(ch, width, height), width, height, 0)
+            };
+            let vertical = |style: u8| {
+                let ch = match style {
+                    LIGHT => '│',
+                    HEAVY => '┃',
+                    _ => '║',
+                };
+                row(&rasterize_box_char(ch, width, height), width, 0)
+            };
+
+            for cp in (0x2500..=0x2570).chain(0x2574..=0x257F) {
+                let ch = char::from_u32(cp).expect("char");
+                let img = rasterize_box_char(ch, width, height);
+                let [up, right, down, left] = LINES[(cp - 0x2500) as usize];
+                for (style, edge, reference) in [
+                    (up, row(&img, width, 0), vertical(up)),
+                    (down, row(&img, width, height - 1), vertical(down)),
+                    (left, column(&img, width, height, 0), horizontal(left)),
+                    (
+                        right,
+                        column(&img, width, height, width - 1),
+                        horizontal(right),
+                    ),
+                ] {
+                    if style == NONE {
+                        assert!(edge.iter().all(|v| *v == 0), "{:?}", ch);
+                    } else if !(0x2504..=0x250B).contains(&cp) && !(0x254C..=0x254F).contains(&cp) {
+                        assert_eq!(edge, reference, "{:?} {}x{}", ch, width, height);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn double_corner() {
+        let (width, height) = (13, 27);
+        let img = rasterize_box_char('╔', width, height);
+        let px = |x: u32, y: u32| img[(y * width + x) as usize] != 0;
+
+        // light is 1px, the double line covers 5..8 and 12..15.
+        assert!(px(5, 12) && px(12, 12) && px(5, 26));
+        assert!(!px(6, 13) && !px(12, 13) && !px(6, 26));
+        // the inner corner.
+        assert!(px(7, 14) && px(8, 14) && px(7, 15));
+        assert!(!px(6, 14) && !px(7, 13));
+    }
+
+    #[test]
+    fn blocks() {
+        let (width, height) = (13, 27);
+        let full = rasterize_box_char('█', width, height);
+        assert!(full.iter().all(|v| *v == 0xFFFF_FFFF));
+
+        let upper = rasterize_box_char('▀', width, height);
+        assert!(upper[..(width * 14) as usize].iter().all(|v| *v != 0));
+        assert!(upper[(width * 14) as usize..].iter().all(|v| *v == 0));
+
+        let shade = rasterize_box_char('▒', width, height);
+        assert!(shade.iter().all(|v| *v == 0x8080_8080));
+    }
+
+    #[test]
+    fn braille() {
+        let (width, height) = (13, 27);
+        let ink = |ch: char| {
+            let img = rasterize_box_char(ch, width, height);
+            img.iter().filter(|v| **v != 0).count()
+        };
+
+        assert_eq!(ink('\u{2800}'), 0);
+        // 3x3 px dots.
+        let dot = ink('\u{2801}');
+        assert_eq!(dot, 9);
+        assert_eq!(ink('\u{28FF}'), 8 * dot);
+
+        // every dot is at its own place.
+        let all = (0..8)
+            .map(|bit| {
+                rasterize_box_char(
+                    char::from_u32(0x2800 + (1 << bit)).expect("char"),
+                    width,
+                    height,
+                )
+            })
+            .fold(vec![0; (width * height) as usize], |acc, img| {
+                acc.iter().zip(img).map(|(a, b)| a | b).collect()
+            });
+        assert_eq!(all, rasterize_box_char('\u{28FF}', width, height));
+        let left = rasterize_box_char('\u{2847}', width, height);
+        assert!(
+            left.iter()
+                .enumerate()
+                .all(|(idx, v)| *v == 0 || (idx as u32 % width) < width / 2)
+        );
+    }
+}
@@ -1,5 +1,28 @@
-use rustybuzz::Face;
-use rustybuzz::ttf_parser::GlyphId;
+use rustybuzz::ttf_parser::{GlyphId, Tag};
+use rustybuzz::{Face, Feature, Variation};
+
+/// Metrics of a font, in font units as read from the font.
+///
+/// Multiply with `height_px / (ascender - descender)` to get
+/// pixels at a font size. The cell height is the font size, the
+/// baseline is the scaled ascender of the first regular font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontMetrics {
+    /// Distance from the baseline to the top. Positive.
+    pub ascender: i16,
+    /// Distance from the baseline to the bottom. Usually negative.
+    pub descender: i16,
+    /// Extra space between lines. Not used for the cell height.
+    pub line_gap: i16,
+    /// Size of the em-square.
+    pub units_per_em: u16,
+    /// Position of the top of the underline relative to the baseline.
+    /// None if the font doesn't declare it.
+    pub underline_position: Option<i16>,
+    /// Thickness of the underline.
+    /// None if the font doesn't declare it.
+    pub underline_thickness: Option<i16>,
+}
 
 /// A Font which can be used for rendering.
 #[derive(Clone)]
@@ -9,6 +32,8 @@ pub struct Font<'a> {
     advance: f32,
     height_px: u32,
     width_px: u32,
+    features: Vec<Feature>,
+    variations: Vec<Variation>,
     id: u64,
 }
 
@@ -17,15 +42,7 @@ impl<'a> Font<'a> {
     /// be parsed.
     pub fn new(data: &'a [u8]) -> Option<Self> {
         Face::from_slice(data, 0).map(|font| {
-            let em_idx;
-            let advance;
-            if font.is_monospaced() {
-                em_idx = font.glyph_index('m').unwrap_or_default();
-                advance = font.glyph_hor_advance(em_idx).unwrap_or_default() as f32;
-            } else {
-                em_idx = font.glyph_index('n').unwrap_or_default();
-                advance = font.glyph_hor_advance(em_idx).unwrap_or_default() as f32;
-            }
+            let advance = base_advance(&font);
 
             Self {
                 font,
@@ -33,6 +50,8 @@ impl<'a> Font<'a> {
                 advance,
                 height_px: 0,
                 width_px: 0,
+                features: Vec::new(),
+                variations: Vec::new(),
                 id: 0,
             }
         })
@@ -46,6 +65,71 @@ impl<'a> Font<'a> {
         self.id = id;
     }
 
+    /// OpenType features used for shaping with this font.
+    ///
+    /// They apply on top of the defaults of the font, so a feature
+    /// with value 0 switches it off, any other value enables it or
+    /// selects an alternate.
+    pub fn set_features(&mut self, features: &[Feature]) {
+        self.features = features.to_vec();
+    }
+
+    /// OpenType features used for shaping with this font.
+    pub fn features(&self) -> &[Feature] {
+        &self.features
+    }
+
+    /// Switches off ligatures and contextual alternates.
+    ///
+    /// This replaces any features set before.
+    pub fn disable_ligatures(&mut self) {
+        self.set_features(&[
+            Feature::new(Tag::from_bytes(b"liga"), 0, ..),
+            Feature::new(Tag::from_bytes(b"clig"), 0, ..),
+            Feature::new(Tag::from_bytes(b"calt"), 0, ..),
+        ]);
+    }
+
+    /// Set a variation axis of a variable font, e.g. `wght` = 600.
+    ///
+    /// Shaping and rasterization use the varied outlines, which
+    /// gives a proper weight instead of the stroked fake bold.
+    /// Axes the font doesn't have are ignored.
+    ///
+    /// __Info__
+    ///
+    /// Set this before handing the font to the backend. A changed
+    /// font needs [update_fonts](crate::WgpuBackend::update_fonts),
+    /// which also clears the cached glyphs.
+    pub fn set_variation(&mut self, axis: [u8; 4], value: f32) {
+        let tag = Tag::from_bytes(&axis);
+        if let Some(variation) = self.variations.iter_mut().find(|v| v.tag == tag) {
+            variation.value = value;
+        } else {
+            self.variations.push(Variation { tag, value });
+        }
+        self.font.set_variations(&self.variations);
+        self.advance = base_advance(&self.font);
+    }
+
+    /// Variation axes set for this font.
+    pub fn variations(&self) -> &[Variation] {
+        &self.variations
+    }
+
+    /// Raw metrics of the font.
+    pub fn metrics(&self) -> FontMetrics {
+        let underline = self.font.underline_metrics();
+        FontMetrics {
+            ascender: self.font.ascender(),
+            descender: self.font.descender(),
+            line_gap: self.font.line_gap(),
+            units_per_em: self.font.units_per_em() as u16,
+            underline_position: underline.map(|v| v.position),
+            underline_thickness: underline.map(|v| v.thickness),
+        }
+    }
+
     pub fn face(&'_ self) -> &'_ Face<'_> {
         &self.font
     }
@@ -139,30 +223,32 @@ impl<'a> Font<'a> {
         }
     }
 
-    pub(crate) fn underline_metrics(&self, ascender: u32, box_height_px: u32) -> (u32, u32) {
-        let font_ascender = self.font.ascender() as f32;
-        let underline_position = self
+    // Underline position and thickness declared by the font,
+    // scaled like the glyphs.
+    fn declared_underline(&self) -> (f32, f32) {
+        let scale = self.height_px as f32 / self.font.height() as f32;
+
+        let (position, thickness) = self
             .font
             .underline_metrics()
-            .map(|m| m.position as f32)
-            .unwrap_or(0.0);
-        let mut underline_percent = underline_position / font_ascender;
+            .map(|m| (m.position as f32, m.thickness as f32))
+            .unwrap_or((0.0, 100.0 /* observed average */));
         // hack 9902
-        if underline_percent > 0.0 {
-            underline_percent = -0.05;
-        }
+        let position = if position > 0.0 {
+            -0.05 * self.font.ascender() as f32
+        } else {
+            position
+        };
 
-        let underline_thickness = self
-            .font
-            .underline_metrics()
-            .map(|m| m.thickness as f32)
-            .unwrap_or(100.0); /* observed average */
-        // default underlines are a bit thin for larger font-sizes.
-        let underline_thickness = underline_thickness * 1.3;
-        let underline_thickness_percent = underline_thickness / font_ascender;
+        (position * scale, (thickness * scale).round().max(1.0))
+    }
 
-        let underline_position = ascender - (ascender as f32 * underline_percent) as u32;
-        let underline_thickness = ((ascender as f32 * underline_thickness_percent) as u32).max(1);
+    pub(crate) fn underline_metrics(&self, ascender: u32, box_height_px: u32) -> (u32, u32) {
+        let (position, thickness) = self.declared_underline();
+
+        // the position is the top of the line below the baseline.
+        let underline_position = ascender + (-position).round() as u32;
+        let underline_thickness = thickness as u32;
 
         // might overflow the box
         if underline_position + underline_thickness < box_height_px {
@@ -175,7 +261,14 @@ impl<'a> Font<'a> {
         }
     }
 
-    pub(crate) fn strikeout_metrics(&self, ascender: u32) -> (u32, u32) {
+    // Fonts don't declare an overline. It uses the underline
+    // thickness at the top of the cell.
+    pub(crate) fn overline_metrics(&self, box_height_px: u32) -> (u32, u32) {
+        let (_, thickness) = self.declared_underline();
+        (0, (thickness as u32).min(box_height_px))
+    }
+
+    pub(crate) fn strikeout_metrics(&self, ascender: u32, box_height_px: u32) -> (u32, u32) {
         let font_ascender = self.font.ascender() as f32;
 
         let strikeout_position = self
@@ -202,6 +295,19 @@ impl<'a> Font<'a> {
         let strikeout_position = ascender - (ascender as f32 * strikeout_percent) as u32;
         let strikeout_thickness = ((ascender as f32 * strikeout_thickness_percent) as u32).max(1);
 
+        // might overflow the box
+        let strikeout_position =
+            strikeout_position.min(box_height_px.saturating_sub(strikeout_thickness));
         (strikeout_position, strikeout_position + strikeout_thickness)
     }
 }
+
+// Advance of the em (or en for proportional fonts).
+fn base_advance(font: &Face<'_>) -> f32 {
+    let em_idx = if font.is_monospaced() {
+        font.glyph_index('m').unwrap_or_default()
+    } else {
+        font.glyph_index('n').unwrap_or_default()
+    };
+    font.glyph_hor_advance(em_idx).unwrap_or_default() as f32
+}
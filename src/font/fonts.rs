@@ -4,6 +4,194 @@ use log::warn;
 use ratatui_core::buffer::Cell;
 use ratatui_core::style::Modifier;
 
+/// Vertical placement of the glyphs in the cell.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VerticalAlign {
+    /// All glyphs sit on the baseline of the first regular font.
+    #[default]
+    Baseline,
+    /// The ink of each glyph is centered in the cell.
+    CenterInk,
+    /// The em-box of the glyph's font is centered in the cell.
+    CenterEm,
+}
+
+/// Horizontal placement of glyphs from a fallback font in the cell.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HorizontalAlign {
+    /// The glyph starts at the left edge of the cell.
+    Left,
+    /// The glyph is centered in the cell.
+    #[default]
+    Center,
+    /// The glyph ends at the right edge of the cell.
+    Right,
+}
+
+/// How glyphs missing from all fonts are shown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NotdefStyle {
+    /// The outline of a small box.
+    #[default]
+    HollowBox,
+    /// A small filled box.
+    FilledBox,
+    /// The question mark of the font.
+    QuestionMark,
+    /// Nothing at all.
+    Blank,
+}
+
+/// Settings for rasterizing the glyphs of all [Fonts].
+///
+/// Change them with [Fonts::set_raster_settings].
+///
+/// __Info__
+///
+/// Use [update_fonts](crate::WgpuBackend::update_fonts) to apply
+/// changed settings to the backend.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RasterSettings {
+    /// Stroke width (in pixels) used to fake bold text, when there
+    /// is no bold font.
+    ///
+    /// The default is 0.75. Small font-sizes may look better with
+    /// less, large font-sizes with more.
+    pub fake_bold_strength: f32,
+    /// Skew factor used to fake italic text, when there is no
+    /// italic font.
+    ///
+    /// This is the horizontal shift per vertical pixel. The default
+    /// is -0.25, negative values slant to the right.
+    pub fake_italic_skew: f32,
+    /// Vertical placement of the glyphs in the cell.
+    ///
+    /// The default [VerticalAlign::Baseline] is right for text.
+    /// Centering helps with symbols and emojis from fallback fonts
+    /// whose metrics differ a lot from the regular font. Block and box
+    /// drawing chars from fallback fonts always stay on the grid.
+    pub vertical_align: VerticalAlign,
+    /// Horizontal placement of glyphs from a fallback font that are
+    /// narrower than the cell.
+    ///
+    /// The default is [HorizontalAlign::Center]. Symbols next to
+    /// monospace text may look better left aligned. Block and box
+    /// drawing chars from fallback fonts always stay on the grid.
+    pub fallback_align: HorizontalAlign,
+    /// How glyphs missing from all fonts are shown.
+    ///
+    /// The default [NotdefStyle::HollowBox] draws the outline of a
+    /// small box instead of the `.notdef` glyph of the font.
+    /// [NotdefStyle::Blank] hides missing glyphs.
+    pub notdef_style: NotdefStyle,
+    /// Render glyphs without anti-aliasing if the font height is
+    /// below this many pixels.
+    ///
+    /// Grayscale anti-aliasing makes very small text look muddy.
+    /// The default 0 always anti-aliases. Color glyphs are not
+    /// affected.
+    pub aa_min_height_px: u32,
+    /// Glyphs are rasterized at this multiple of their size and
+    /// scaled down for anti-aliasing. The factor is clamped to 1..=4,
+    /// the default is 2.
+    ///
+    /// Small fonts may look better with more, for large fonts less
+    /// saves memory and time. 1 disables supersampling, which is the
+    /// fastest and gives the sharpest glyphs.
+    pub supersample: u8,
+    /// Keep the shaped positions of all glyphs in a cell.
+    ///
+    /// Each cell starts at its place on the grid, any kerning between
+    /// cells is dropped. By default, this also happens for glyphs after
+    /// the first in a cell: the advance of the glyphs before is undone,
+    /// and each glyph only keeps its own offset. Fonts that position
+    /// marks relative to the base char look better this way.
+    ///
+    /// With true, the glyphs of a cell are laid out as the font shaped
+    /// them, which suits proportional fonts with multi-glyph clusters.
+    /// The default is false.
+    pub preserve_cluster_kerning: bool,
+    /// Cache glyphs at fractional x-positions.
+    ///
+    /// Glyphs are placed at whole pixels by default, and every
+    /// instance of a glyph looks the same. Text with fractional
+    /// advances, like proportional fonts with
+    /// [preserve_cluster_kerning](Self::preserve_cluster_kerning),
+    /// can look unevenly spaced this way. With 3 phases a glyph is
+    /// rasterized at 0, 1/3 and 2/3 of a pixel, whatever is nearest
+    /// to its exact position.
+    ///
+    /// Each phase needs its own space in the glyph atlas. The value is
+    /// clamped to 1..=4, the default 1 disables this.
+    pub subpixel_phases: u8,
+    /// Snap glyphs of monospace fonts to whole pixels.
+    ///
+    /// The left edge of the first vertical stem is moved to the nearest
+    /// pixel boundary when the glyph is rasterized. Stems at small sizes
+    /// get less blurry this way, at the cost of moving a glyph by up
+    /// to half a pixel. Fallback fonts and proportional fonts are
+    /// not affected. The default is false.
+    pub grid_fit: bool,
+}
+
+impl Default for RasterSettings {
+    fn default() -> Self {
+        Self {
+            fake_bold_strength: 0.75,
+            fake_italic_skew: -0.25,
+            vertical_align: VerticalAlign::Baseline,
+            fallback_align: HorizontalAlign::Center,
+            notdef_style: NotdefStyle::HollowBox,
+            aa_min_height_px: 0,
+            supersample: 2,
+            preserve_cluster_kerning: false,
+            subpixel_phases: 1,
+            grid_fit: false,
+        }
+    }
+}
+
+/// Problems found with a font in [Fonts::add_fonts].
+///
+/// The font is used anyway, but the rendering may suffer. Only the
+/// first font for each style is checked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontWarning {
+    /// The font is not flagged as monospace.
+    NotMonospace {
+        /// [Id](Font::id) of the font.
+        id: u64,
+    },
+    /// The width/height ratio differs by more than 10% from the
+    /// first regular font, or the fallback font if there is none.
+    AspectRatio {
+        /// [Id](Font::id) of the font.
+        id: u64,
+        /// Width/height of the reference font.
+        expected: f32,
+        /// Width/height of this font.
+        actual: f32,
+    },
+    /// Printable ASCII characters without a glyph in the font.
+    MissingGlyphs {
+        /// [Id](Font::id) of the font.
+        id: u64,
+        /// The missing characters.
+        missing: Vec<char>,
+    },
+}
+
+impl FontWarning {
+    /// [Id](Font::id) of the font.
+    pub fn id(&self) -> u64 {
+        match self {
+            FontWarning::NotMonospace { id }
+            | FontWarning::AspectRatio { id, .. }
+            | FontWarning::MissingGlyphs { id, .. } => *id,
+        }
+    }
+}
+
 /// A collection of fonts to use for rendering. Supports font fallback.
 ///
 /// It is recommended, but not required, that all fonts have the same/very
@@ -16,6 +204,9 @@ pub struct Fonts<'a> {
 
     em_advance: f32,
 
+    raster: RasterSettings,
+    cell_width_px: Option<u32>,
+
     fallback: Vec<Font<'a>>,
 
     regular: Vec<Font<'a>>,
@@ -25,6 +216,9 @@ pub struct Fonts<'a> {
 
     // give an id in insertion order.
     id_count: u64,
+
+    // problems found in add_fonts.
+    warnings: Vec<FontWarning>,
 }
 
 impl<'a> Fonts<'a> {
@@ -46,12 +240,15 @@ impl<'a> Fonts<'a> {
             height_px: size_px,
             ascender: font.ascender(),
             em_advance: font.em_advance(),
+            raster: RasterSettings::default(),
+            cell_width_px: None,
             fallback: vec![font],
             regular: vec![],
             bold: vec![],
             italic: vec![],
             bold_italic: vec![],
             id_count: 1,
+            warnings: vec![],
         }
     }
 
@@ -77,14 +274,17 @@ impl<'a> Fonts<'a> {
         Self {
             width_px: size_px / 2, // rough estimate
             height_px: size_px,
-            ascender: size_px * 4 / 5,         // rough estimate
+            ascender: size_px * 4 / 5,        // rough estimate
             em_advance: size_px as f32 / 2.0, // rough estimate
+            raster: RasterSettings::default(),
+            cell_width_px: None,
             fallback: fonts,
             regular: vec![],
             bold: vec![],
             italic: vec![],
             bold_italic: vec![],
             id_count,
+            warnings: vec![],
         }
     }
 
@@ -138,6 +338,9 @@ impl<'a> Fonts<'a> {
             self.ascender = self.height_px * 4 / 5;
             self.em_advance = self.height_px as f32 / 2.0;
         }
+        if let Some(cell_width_px) = self.cell_width_px {
+            self.width_px = cell_width_px;
+        }
 
         self.regular
             .iter_mut()
@@ -153,20 +356,91 @@ impl<'a> Fonts<'a> {
         assert_ne!(self.width_px, 0);
     }
 
+    /// Settings for rasterizing the glyphs.
+    #[inline]
+    pub fn raster_settings(&self) -> RasterSettings {
+        self.raster
+    }
+
+    /// Change the settings for rasterizing the glyphs.
+    ///
+    /// The supersampling factor and the sub-pixel phases are clamped
+    /// to 1..=4.
+    pub fn set_raster_settings(&mut self, settings: RasterSettings) {
+        self.raster = RasterSettings {
+            supersample: settings.supersample.clamp(1, 4),
+            subpixel_phases: settings.subpixel_phases.clamp(1, 4),
+            ..settings
+        };
+    }
+
     /// The minimum width (in pixels) across all fonts.
     pub fn width_px(&self) -> u32 {
         self.width_px
     }
 
+    /// Fixed cell width that overrides the width of the fonts.
+    #[inline]
+    pub fn cell_width_px(&self) -> Option<u32> {
+        self.cell_width_px
+    }
+
+    /// Use a fixed cell width instead of the advance of the fonts.
+    /// None uses the width of the fonts again. A width of 0 is ignored.
+    ///
+    /// Glyphs keep their size, wider glyphs may be clipped.
+    ///
+    /// Use [update_fonts](crate::WgpuBackend::update_fonts) or
+    /// [set_cell_width_px](crate::WgpuBackend::set_cell_width_px) to
+    /// apply the change to the backend.
+    pub fn set_cell_width_px(&mut self, width_px: Option<u32>) {
+        self.cell_width_px = width_px.filter(|w| *w > 0);
+        self.set_height_px(self.height_px);
+    }
+
     /// Remove the non-fallback fonts.
     pub fn clear_fonts(&mut self) {
         self.bold_italic.clear();
         self.italic.clear();
         self.bold.clear();
         self.regular.clear();
+        self.warnings.clear();
         self.set_height_px(self.height_px);
     }
 
+    /// Remove the font with the given [id](Font::id).
+    ///
+    /// The ids of the other fonts don't change. The last fallback font
+    /// can't be removed.
+    ///
+    /// Returns false if there is no such font.
+    pub fn remove_font(&mut self, id: u64) -> bool {
+        if self.fallback.len() == 1 && self.fallback[0].id() == id {
+            return false;
+        }
+        let removed = [
+            &mut self.regular,
+            &mut self.bold,
+            &mut self.italic,
+            &mut self.bold_italic,
+            &mut self.fallback,
+        ]
+        .into_iter()
+        .any(|fonts| {
+            if let Some(idx) = fonts.iter().position(|f| f.id() == id) {
+                fonts.remove(idx);
+                true
+            } else {
+                false
+            }
+        });
+        if removed {
+            self.warnings.retain(|w| w.id() != id);
+            self.set_height_px(self.height_px);
+        }
+        removed
+    }
+
     /// Add a collection of fonts for various styles. They will automatically be
     /// added to the appropriate fallback font list based on the font's
     /// bold/italic properties. Note that this will automatically organize fonts
@@ -180,22 +454,72 @@ impl<'a> Fonts<'a> {
             font.set_id(self.id_count);
             self.id_count += 1;
 
-            if !font.face().is_monospaced() {
+            // later fonts are fallbacks and only fill in missing glyphs.
+            if self.style_fonts(&font).is_empty() {
+                self.check_font(&font);
+            } else if !font.face().is_monospaced() {
                 warn!("Non monospace font used in add_fonts, this may cause unexpected rendering.");
             }
-            if font.face().is_italic() && font.face().is_bold() {
-                self.bold_italic.push(font);
-            } else if font.face().is_italic() {
-                self.italic.push(font);
-            } else if font.face().is_bold() {
-                self.bold.push(font);
-            } else {
-                self.regular.push(font);
-            }
+            self.style_fonts(&font).push(font);
         }
         self.set_height_px(self.height_px);
     }
 
+    // The list for the style of the font.
+    fn style_fonts(&mut self, font: &Font<'a>) -> &mut Vec<Font<'a>> {
+        if font.face().is_italic() && font.face().is_bold() {
+            &mut self.bold_italic
+        } else if font.face().is_italic() {
+            &mut self.italic
+        } else if font.face().is_bold() {
+            &mut self.bold
+        } else {
+            &mut self.regular
+        }
+    }
+
+    /// Problems found with the fonts given to [add_fonts](Self::add_fonts).
+    ///
+    /// Only the first font for each style is checked, the fonts after
+    /// it are fallbacks for missing glyphs. Warnings of removed fonts
+    /// are dropped.
+    pub fn warnings(&self) -> &[FontWarning] {
+        &self.warnings
+    }
+
+    // Record anything that may cause unexpected rendering.
+    fn check_font(&mut self, font: &Font<'a>) {
+        let id = font.id();
+        let face = font.face();
+
+        if !face.is_monospaced() {
+            warn!("Non monospace font used in add_fonts, this may cause unexpected rendering.");
+            self.warnings.push(FontWarning::NotMonospace { id });
+        }
+
+        if let Some(reference) = self.regular.first().or(self.fallback.first()) {
+            let expected = reference.em_advance() / reference.face().height() as f32;
+            let actual = font.em_advance() / face.height() as f32;
+            if (actual - expected).abs() > expected * 0.1 {
+                warn!("Font {id} has a width/height ratio of {actual}, expected {expected}.");
+                self.warnings.push(FontWarning::AspectRatio {
+                    id,
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        let missing = (' '..='~')
+            .filter(|c| face.glyph_index(*c).is_none())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            warn!("Font {id} has no glyphs for {missing:?}.");
+            self.warnings
+                .push(FontWarning::MissingGlyphs { id, missing });
+        }
+    }
+
     /// Add a new collection of fonts for regular styled text. These fonts will
     /// come _after_ previously provided fonts in the fallback order.
     pub fn add_regular_fonts(&mut self, fonts: impl IntoIterator<Item = Font<'a>>) {
@@ -262,9 +586,48 @@ impl<'a> Fonts<'a> {
         }
     }
 
+    /// Which font renders each char of the sample.
+    ///
+    /// Uses the same selection as the regular style while rendering,
+    /// and returns the [id](Font::id) of the chosen font. None means
+    /// no font has a glyph for the char and it will be shown as `.notdef`.
+    pub fn coverage(&self, sample: &str) -> Vec<(char, Option<u64>)> {
+        sample
+            .chars()
+            .map(|ch| {
+                let mut cell = Cell::default();
+                cell.set_char(ch);
+                let id = self.font_for_cell(&cell);
+                let has_glyph = self
+                    .regular_style_fonts()
+                    .any(|f| f.id() == id && f.face().glyph_index(ch).is_some());
+                (ch, has_glyph.then_some(id))
+            })
+            .collect()
+    }
 
-    pub(crate) fn count(&self) -> usize {
-        1 + self.bold.len() + self.italic.len() + self.bold_italic.len() + self.regular.len()
+    /// Number of fonts, including the fallback fonts.
+    pub fn count(&self) -> usize {
+        self.fallback.len()
+            + self.bold.len()
+            + self.italic.len()
+            + self.bold_italic.len()
+            + self.regular.len()
+    }
+
+    // Strikeout position in the cell. This uses the metrics of the
+    // first regular font for all fonts, so the line stays continuous
+    // across glyphs from different fonts.
+    pub(crate) fn strikeout_metrics(&self) -> (u32, u32) {
+        self.regular
+            .iter()
+            .chain(self.bold.iter())
+            .chain(self.italic.iter())
+            .chain(self.bold_italic.iter())
+            .chain(self.fallback.iter())
+            .next()
+            .expect("font")
+            .strikeout_metrics(self.ascender, self.height_px)
     }
 
     pub(crate) fn get_by_id(&'a self, id: u64) -> &'a Font<'a> {
@@ -278,47 +641,56 @@ impl<'a> Fonts<'a> {
             .expect("font")
     }
 
+    // The fonts are tried in order of the closest style, all
+    // styles come before the fallback fonts. A font that has the
+    // glyph wins over one with a better style, the style is the
+    // tiebreaker.
     pub(crate) fn font_for_cell(&'_ self, cell: &Cell) -> u64 {
         if cell.modifier.contains(Modifier::BOLD | Modifier::ITALIC) {
             self.select_font(
                 cell.symbol(),
                 self.bold_italic
                     .iter()
-                    .map(|f| f)
-                    .chain(self.italic.iter().map(|f| f))
-                    .chain(self.bold.iter().map(|f| f))
-                    .chain(self.regular.iter().map(|f| f))
-                    .chain(self.fallback.iter().map(|f| f)),
+                    .chain(self.italic.iter())
+                    .chain(self.bold.iter())
+                    .chain(self.regular.iter())
+                    .chain(self.fallback.iter()),
             )
         } else if cell.modifier.contains(Modifier::BOLD) {
             self.select_font(
                 cell.symbol(),
                 self.bold
                     .iter()
-                    .map(|f| f)
-                    .chain(self.regular.iter().map(|f| f))
-                    .chain(self.fallback.iter().map(|f| f)),
+                    .chain(self.regular.iter())
+                    .chain(self.bold_italic.iter())
+                    .chain(self.italic.iter())
+                    .chain(self.fallback.iter()),
             )
         } else if cell.modifier.contains(Modifier::ITALIC) {
             self.select_font(
                 cell.symbol(),
                 self.italic
                     .iter()
-                    .map(|f| f)
-                    .chain(self.regular.iter().map(|f| f))
-                    .chain(self.fallback.iter().map(|f| f)),
+                    .chain(self.regular.iter())
+                    .chain(self.bold_italic.iter())
+                    .chain(self.bold.iter())
+                    .chain(self.fallback.iter()),
             )
         } else {
-            self.select_font(
-                cell.symbol(),
-                self.regular
-                    .iter()
-                    .map(|f| f)
-                    .chain(self.fallback.iter().map(|f| f)),
-            )
+            self.select_font(cell.symbol(), self.regular_style_fonts())
         }
     }
 
+    // Search order for cells without bold or italic.
+    fn regular_style_fonts(&self) -> impl Iterator<Item = &Font<'a>> {
+        self.regular
+            .iter()
+            .chain(self.bold.iter())
+            .chain(self.italic.iter())
+            .chain(self.bold_italic.iter())
+            .chain(self.fallback.iter())
+    }
+
     fn select_font<'fonts>(
         &'fonts self,
         cluster: &str,
@@ -362,3 +734,114 @@ impl<'a> Fonts<'a> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::font::{Font, FontWarning, Fonts};
+    use ratatui_core::buffer::Cell;
+    use ratatui_core::style::Modifier;
+
+    // Set the bold and italic flags in the OS/2 table.
+    fn with_style(data: &[u8], bold: bool, italic: bool) -> Vec<u8> {
+        let mut data = data.to_vec();
+        let num_tables = u16::from_be_bytes([data[4], data[5]]) as usize;
+        let os2 = (0..num_tables)
+            .map(|n| 12 + n * 16)
+            .find(|record| &data[*record..*record + 4] == b"OS/2")
+            .map(|record| {
+                u32::from_be_bytes(data[record + 8..record + 12].try_into().unwrap()) as usize
+            })
+            .expect("OS/2 table");
+        let mut selection = u16::from_be_bytes([data[os2 + 62], data[os2 + 63]]);
+        // italic, bold, regular
+        selection &= !(1 | 1 << 5 | 1 << 6);
+        selection |= u16::from(italic) | u16::from(bold) << 5;
+        data[os2 + 62..os2 + 64].copy_from_slice(&selection.to_be_bytes());
+        data
+    }
+
+    fn cell(symbol: &'static str, modifier: Modifier) -> Cell {
+        let mut cell = Cell::new(symbol);
+        cell.modifier = modifier;
+        cell
+    }
+
+    #[test]
+    fn warnings() {
+        let cascadia = include_bytes!("CascadiaMono-Regular.ttf");
+        let symbols = include_bytes!("NotoSansSymbols2-Regular.ttf");
+        let emoji = include_bytes!("NotoEmoji-Regular.ttf");
+
+        // fallbacks are not checked.
+        let mut fonts = Fonts::new(Font::new(cascadia).expect("font"), 24);
+        fonts.add_fonts([Font::new(cascadia).expect("font")]);
+        fonts.add_fonts([
+            Font::new(symbols).expect("font"),
+            Font::new(emoji).expect("font"),
+        ]);
+        assert!(fonts.warnings().is_empty());
+
+        let mut fonts = Fonts::new(Font::new(cascadia).expect("font"), 24);
+        fonts.add_fonts([
+            Font::new(symbols).expect("font"),
+            Font::new(emoji).expect("font"),
+        ]);
+        let id = fonts.regular[0].id();
+        assert!(fonts.warnings().contains(&FontWarning::NotMonospace { id }));
+        assert!(
+            fonts
+                .warnings()
+                .iter()
+                .any(|w| matches!(w, FontWarning::AspectRatio { .. }))
+        );
+        assert!(fonts.warnings().iter().all(|w| w.id() == id));
+
+        let mut fonts = Fonts::new(Font::new(cascadia).expect("font"), 24);
+        fonts.add_fonts([Font::new(emoji).expect("font")]);
+        let emoji_id = fonts.regular[0].id();
+        assert!(fonts.warnings().iter().any(|w| match w {
+            FontWarning::MissingGlyphs { id, missing } => *id == emoji_id && missing.contains(&'a'),
+            _ => false,
+        }));
+
+        fonts.remove_font(emoji_id);
+        assert!(fonts.warnings().is_empty());
+    }
+
+    #[test]
+    fn font_by_glyph_presence() {
+        let cascadia = include_bytes!("CascadiaMono-Regular.ttf");
+        let fairfax = include_bytes!("../../tests/fonts/Fairfax.ttf");
+        let italic = with_style(cascadia, false, true);
+        let bold = with_style(fairfax, true, false);
+
+        let mut fonts = Fonts::new(Font::new(cascadia).expect("font"), 24);
+        fonts.add_fonts([
+            Font::new(&italic).expect("font"),
+            Font::new(&bold).expect("font"),
+        ]);
+        let italic_id = fonts.italic[0].id();
+        let bold_id = fonts.bold[0].id();
+
+        // only the bold font has the glyph.
+        let bold_italic = cell("\u{180}", Modifier::BOLD | Modifier::ITALIC);
+        assert_eq!(fonts.font_for_cell(&bold_italic), bold_id);
+        assert_eq!(
+            fonts.font_for_cell(&cell("\u{180}", Modifier::ITALIC)),
+            bold_id
+        );
+        assert_eq!(
+            fonts.font_for_cell(&cell("\u{180}", Modifier::empty())),
+            bold_id
+        );
+        // coverage reports the same font.
+        assert_eq!(fonts.coverage("\u{180}"), [('\u{180}', Some(bold_id))]);
+
+        // the style decides if both have the glyph.
+        assert_eq!(
+            fonts.font_for_cell(&cell("a", Modifier::BOLD | Modifier::ITALIC)),
+            italic_id
+        );
+        assert_eq!(fonts.font_for_cell(&cell("a", Modifier::BOLD)), bold_id);
+    }
+}
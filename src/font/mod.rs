@@ -1,9 +1,10 @@
+pub(crate) mod box_drawing;
 mod font;
 mod font_data;
 mod fonts;
 pub(crate) mod outline;
 pub(crate) mod rasterize;
 
-pub use font::Font;
+pub use font::{Font, FontMetrics};
 pub use font_data::FontData;
-pub use fonts::Fonts;
+pub use fonts::{FontWarning, Fonts, HorizontalAlign, NotdefStyle, RasterSettings, VerticalAlign};
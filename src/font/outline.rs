@@ -45,6 +45,51 @@ impl rustybuzz::ttf_parser::OutlineBuilder for Outline {
     }
 }
 
+/// Finds the left edge of the leftmost vertical stem of a glyph.
+pub(crate) struct Stems {
+    // shorter vertical lines are not a stem.
+    min_len: f32,
+    last: (f32, f32),
+    left: Option<f32>,
+}
+
+impl Stems {
+    pub(crate) fn new(min_len: f32) -> Self {
+        Self {
+            min_len,
+            last: (0.0, 0.0),
+            left: None,
+        }
+    }
+
+    pub(crate) fn left(&self) -> Option<f32> {
+        self.left
+    }
+}
+
+impl rustybuzz::ttf_parser::OutlineBuilder for Stems {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.last = (x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        if x == self.last.0 && (y - self.last.1).abs() >= self.min_len {
+            self.left = Some(self.left.map_or(x, |left| left.min(x)));
+        }
+        self.last = (x, y);
+    }
+
+    fn quad_to(&mut self, _x1: f32, _y1: f32, x: f32, y: f32) {
+        self.last = (x, y);
+    }
+
+    fn curve_to(&mut self, _x1: f32, _y1: f32, _x2: f32, _y2: f32, x: f32, y: f32) {
+        self.last = (x, y);
+    }
+
+    fn close(&mut self) {}
+}
+
 pub(crate) struct Painter<'f, 'd, 'p> {
     font: &'f Face<'d>,
     target: &'f mut DrawTarget<&'p mut [u32]>,
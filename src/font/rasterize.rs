@@ -1,33 +1,128 @@
-use crate::font::outline::{Outline, Painter};
+use crate::font::outline::{Outline, Painter, Stems};
+use crate::font::{HorizontalAlign, NotdefStyle, RasterSettings, VerticalAlign};
 use crate::text_atlas::{CacheRect, Entry};
 use bitvec::order::Lsb0;
 use bitvec::slice::BitSlice;
 use raqote::{DrawOptions, DrawTarget, SolidSource, StrokeStyle, Transform};
 use rustybuzz::ttf_parser::{
-    GlyphId, OutlineBuilder, RasterGlyphImage, RasterImageFormat, RgbaColor,
+    GlyphId, OutlineBuilder, RasterGlyphImage, RasterImageFormat, Rect, RgbaColor,
 };
 use unicode_properties::GeneralCategory;
 
+// Upper limit for the size of the supersampled glyph buffer.
+// Larger glyphs are rendered with a smaller factor.
+const MAX_SUPERSAMPLE_PIXELS: u32 = 2048 * 2048;
+
+/// Everything that decides how one glyph is rasterized.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GlyphRaster {
+    pub(crate) bold: bool,
+    pub(crate) italic: bool,
+    pub(crate) settings: RasterSettings,
+    pub(crate) aliased: bool,
+    pub(crate) text_outline: f32,
+    pub(crate) subpixel_x: f32,
+    pub(crate) advance_scale: f32,
+    pub(crate) advance_scale_y: f32,
+    pub(crate) ascender: u32,
+    pub(crate) emoji: bool,
+    pub(crate) block_char: bool,
+    pub(crate) enclosing: bool,
+    // the glyph takes half of the cell.
+    pub(crate) condensed: bool,
+    pub(crate) category: GeneralCategory,
+    pub(crate) is_fallback: bool,
+}
+
 pub(crate) fn rasterize_glyph(
     cached: Entry,
     face: &rustybuzz::Face,
     info: &rustybuzz::GlyphInfo,
-    bold: bool,
-    italic: bool,
-    advance_scale: f32,
-    advance_scale_y: f32,
-    mut ascender: u32,
-    emoji: bool,
-    block_char: bool,
-    category: GeneralCategory,
-    is_fallback: bool,
+    raster: &GlyphRaster,
 ) -> (CacheRect, Vec<u32>) {
+    let GlyphRaster {
+        bold,
+        italic,
+        settings,
+        aliased,
+        text_outline,
+        subpixel_x,
+        advance_scale,
+        advance_scale_y,
+        mut ascender,
+        emoji,
+        block_char,
+        enclosing,
+        condensed,
+        category,
+        is_fallback,
+    } = *raster;
+    let RasterSettings {
+        fake_bold_strength: fake_bold,
+        fake_italic_skew: fake_italic,
+        vertical_align,
+        fallback_align,
+        notdef_style,
+        supersample,
+        grid_fit,
+        ..
+    } = settings;
+
+    // the question mark of the font stands in for a missing glyph.
+    let mut info = *info;
+    if info.glyph_id == 0
+        && notdef_style == NotdefStyle::QuestionMark
+        && let Some(glyph) = face.glyph_index('?')
+    {
+        info.glyph_id = glyph.0 as u32;
+    }
+
+    // render at a multiple of the size and scale down for anti-aliasing.
+    // aliased glyphs are rendered directly with a hard coverage cutoff.
+    let (supersample, antialias) = if aliased {
+        (1.0, raqote::AntialiasMode::None)
+    } else {
+        (
+            supersample_factor(cached.width, cached.height, supersample) as f32,
+            raqote::AntialiasMode::Gray,
+        )
+    };
+    let draw_options = DrawOptions {
+        antialias,
+        ..Default::default()
+    };
+
     let computed_offset_x;
     let computed_offset_y;
 
+    // condensed glyphs are placed in the left half of the raster.
+    let cell_width = if condensed {
+        cached.width as f32 / 2.0
+    } else {
+        cached.width as f32
+    };
+
     let scale;
     let scale_y;
-    if is_fallback && block_char {
+    if enclosing
+        && let Some(bounds) = face.glyph_bounding_box(GlyphId(info.glyph_id as u16))
+        && bounds.width() > 0
+        && bounds.height() > 0
+    {
+        // enclosing marks are drawn around the base char.
+        // scale the ink to fit the cell and center it, whatever
+        // the offsets of the font say.
+        let fit = (cached.width as f32 / bounds.width() as f32)
+            .min(cached.height as f32 / bounds.height() as f32);
+
+        scale = fit * supersample;
+        scale_y = fit * supersample;
+
+        let ink_center_y = (bounds.y_max as f32 + bounds.y_min as f32) / 2.0;
+        computed_offset_x = center_ink(bounds, cached.width as f32 * supersample, scale);
+        computed_offset_y = cached.height as f32 * supersample / 2.0 + ink_center_y * scale_y
+            - ascender as f32 * supersample;
+    } else if is_fallback && block_char {
         // block-chars must always scale according to the originating font.
         // otherwise this leaves gaps.
         // they also must not be centered.
@@ -36,52 +131,109 @@ pub(crate) fn rasterize_glyph(
         computed_offset_y = 0.0;
 
         ascender = (face.ascender() as f32 * advance_scale_y) as u32;
-        scale = advance_scale * 2.0;
-        scale_y = advance_scale_y * 2.0;
+        scale = advance_scale * supersample;
+        scale_y = advance_scale_y * supersample;
     } else if is_fallback {
         // glyphs from a fallback font will probably not fit.
         // scale them down either vertically or horizontally, whatever fits.
-        // then align them centered.
+        // then align them as configured, centered by default.
         // and later render them at the same baseline as the regular font.
 
         let actual_width = face
             .glyph_hor_advance(GlyphId(info.glyph_id as u16))
             .unwrap_or_default();
 
-        computed_offset_x = (cached.width as f32 - actual_width as f32 * advance_scale) / 2.0;
-        computed_offset_y = 0.0;
+        scale = advance_scale * supersample;
+        scale_y = advance_scale_y * supersample;
 
-        scale = advance_scale * 2.0;
-        scale_y = advance_scale_y * 2.0;
+        let free_x = (cell_width - actual_width as f32 * advance_scale) * supersample;
+        computed_offset_x = if actual_width == 0
+            && let Some(bounds) = face.glyph_bounding_box(GlyphId(info.glyph_id as u16))
+        {
+            // combining marks have no advance to align.
+            center_ink(bounds, cell_width * supersample, scale)
+        } else {
+            match fallback_align {
+                HorizontalAlign::Left => 0.0,
+                HorizontalAlign::Center => free_x / 2.0,
+                HorizontalAlign::Right => free_x,
+            }
+        };
+        computed_offset_y = 0.0;
     } else if !face.is_monospaced() {
         let actual_width = face
             .glyph_hor_advance(GlyphId(info.glyph_id as u16))
             .unwrap_or_default();
 
-        computed_offset_x = (cached.width as f32 - actual_width as f32 * advance_scale) / 2.0;
-        computed_offset_y = 0.0;
+        scale = advance_scale * supersample;
+        scale_y = advance_scale_y * supersample;
 
-        scale = advance_scale * 2.0;
-        scale_y = advance_scale_y * 2.0;
+        computed_offset_x = if actual_width == 0
+            && let Some(bounds) = face.glyph_bounding_box(GlyphId(info.glyph_id as u16))
+        {
+            // combining marks have no advance to center.
+            center_ink(bounds, cell_width * supersample, scale)
+        } else {
+            (cell_width - actual_width as f32 * advance_scale) * supersample / 2.0
+        };
+        computed_offset_y = 0.0;
     } else {
         // regular fonts will probably be from one font family and therefore have
         // more regular properties.
 
         // don't offset. font should fit.
-        computed_offset_x = 0.0;
+        // with grid-fitting the left edge of the first vertical stem,
+        // or of the ink if there is none, is moved to the nearest pixel.
+        // this keeps the stems crisp.
+        computed_offset_x = if grid_fit
+            && let Some(bounds) = face.glyph_bounding_box(GlyphId(info.glyph_id as u16))
+        {
+            let mut stems = Stems::new(bounds.height() as f32 / 4.0);
+            face.outline_glyph(GlyphId(info.glyph_id as u16), &mut stems);
+            let left = stems.left().unwrap_or(bounds.x_min as f32) * advance_scale;
+            (left.round() - left) * supersample
+        } else {
+            0.0
+        };
         computed_offset_y = 0.0;
 
-        scale = advance_scale * 2.0;
-        scale_y = advance_scale_y * 2.0;
+        scale = advance_scale * supersample;
+        scale_y = advance_scale_y * supersample;
     }
 
-    let skew = if !emoji && !face.is_italic() && italic {
+    // the fraction of a pixel the glyph is placed right of its position.
+    let computed_offset_x = computed_offset_x + subpixel_x * supersample;
+
+    // block-chars from a fallback font stay on the grid.
+    let computed_offset_y = if is_fallback && block_char || enclosing {
+        computed_offset_y
+    } else {
+        match vertical_align {
+            VerticalAlign::Baseline => computed_offset_y,
+            VerticalAlign::CenterInk => {
+                if let Some(bounds) = face.glyph_bounding_box(GlyphId(info.glyph_id as u16)) {
+                    let ink_center = (bounds.y_max as f32 + bounds.y_min as f32) / 2.0;
+                    cached.height as f32 - ascender as f32 * supersample + ink_center * scale_y
+                } else {
+                    computed_offset_y
+                }
+            }
+            VerticalAlign::CenterEm => {
+                let em_center = (face.ascender() as f32 + face.descender() as f32) / 2.0;
+                cached.height as f32 - ascender as f32 * supersample + em_center * scale_y
+            }
+        }
+    };
+
+    // symbols and emojis from a fallback font don't get a fake italic.
+    let symbol = emoji || (is_fallback && category == GeneralCategory::OtherSymbol);
+    let skew = if !symbol && !face.is_italic() && italic {
         Transform::new(
             /* scale x */ 1.0,
             /* skew x */ 0.0,
-            /* skew y */ -0.25,
+            /* skew y */ fake_italic,
             /* scale y */ 1.0,
-            /* translate x */ -0.25 * cached.width as f32,
+            /* translate x */ fake_italic * cached.width as f32,
             /* translate y */ 0.0,
         )
     } else {
@@ -97,28 +249,43 @@ pub(crate) fn rasterize_glyph(
 
         let mut target = DrawTarget::from_backing(width as i32, height as i32, &mut image[..]);
 
-        let w1 = width as f32 * 0.33;
-        let w2 = width as f32 * 0.67;
-        let h1 = height as f32 * 0.33;
-        let h2 = height as f32 * 0.67;
-
-        let mut render = Outline::default();
-        render.move_to(w1, h1);
-        render.line_to(w2, h1);
-        render.line_to(w2, h2);
-        render.line_to(w1, h2);
-        render.close();
-        let path = render.finish();
+        // snapped to the pixels, the hollow box is the border of the
+        // filled box.
+        let w1 = (width as f32 * 0.33).round();
+        let w2 = (width as f32 * 0.67).round();
+        let h1 = (height as f32 * 0.33).round();
+        let h2 = (height as f32 * 0.67).round();
+
+        let rect = |inset: f32| {
+            let mut render = Outline::default();
+            render.move_to(w1 + inset, h1 + inset);
+            render.line_to(w2 - inset, h1 + inset);
+            render.line_to(w2 - inset, h2 - inset);
+            render.line_to(w1 + inset, h2 - inset);
+            render.close();
+            render.finish()
+        };
 
-        target.stroke(
-            &path,
-            &raqote::Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
-            &StrokeStyle {
-                width: 1.5,
-                ..Default::default()
-            },
-            &DrawOptions::new(),
-        );
+        let white =
+            raqote::Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255));
+        match notdef_style {
+            NotdefStyle::Blank => {}
+            NotdefStyle::FilledBox => {
+                target.fill(&rect(0.0), &white, &DrawOptions::new());
+            }
+            // without a question mark in the font.
+            NotdefStyle::HollowBox | NotdefStyle::QuestionMark => {
+                target.stroke(
+                    &rect(0.5),
+                    &white,
+                    &StrokeStyle {
+                        width: 1.0,
+                        ..Default::default()
+                    },
+                    &DrawOptions::new(),
+                );
+            }
+        }
 
         return (
             CacheRect {
@@ -129,19 +296,18 @@ pub(crate) fn rasterize_glyph(
         );
     }
 
-    let mut image = vec![0u32; cached.width as usize * 2 * cached.height as usize * 2];
-    let mut target = DrawTarget::from_backing(
-        cached.width as i32 * 2,
-        cached.height as i32 * 2,
-        &mut image[..],
-    );
+    let target_width = (cached.width as f32 * supersample) as i32;
+    let target_height = (cached.height as f32 * supersample) as i32;
+
+    let mut image = vec![0u32; target_width as usize * target_height as usize];
+    let mut target = DrawTarget::from_backing(target_width, target_height, &mut image[..]);
 
     let mut painter = Painter::new(
         face,
         &mut target,
         skew,
         scale,
-        ascender as f32 * 2.0 + computed_offset_y,
+        ascender as f32 * supersample + computed_offset_y,
         computed_offset_x,
     );
     if face
@@ -160,8 +326,8 @@ pub(crate) fn rasterize_glyph(
             0.,
             0.,
             &raqote::Image {
-                width: cached.width as i32 * 2,
-                height: cached.height as i32 * 2,
+                width: target_width,
+                height: target_height,
                 data: &image,
             },
             &DrawOptions {
@@ -213,13 +379,9 @@ pub(crate) fn rasterize_glyph(
             0.
         };
         let x_off = x_off * scale + computed_offset_x;
-        let y_off = ascender as f32 * 2.0 + computed_offset_y;
+        let y_off = ascender as f32 * supersample + computed_offset_y;
 
-        let mut target = DrawTarget::from_backing(
-            cached.width as i32 * 2,
-            cached.height as i32 * 2,
-            &mut image[..],
-        );
+        let mut target = DrawTarget::from_backing(target_width, target_height, &mut image[..]);
         target.set_transform(
             &Transform::scale(scale, -scale_y)
                 .then(&skew)
@@ -229,7 +391,7 @@ pub(crate) fn rasterize_glyph(
         target.fill(
             &path,
             &raqote::Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
-            &DrawOptions::default(),
+            &draw_options,
         );
 
         if !face.is_bold() && bold {
@@ -237,10 +399,11 @@ pub(crate) fn rasterize_glyph(
                 &path,
                 &raqote::Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
                 &StrokeStyle {
-                    width: 1.5 / scale,
+                    // rendered at the supersampled size.
+                    width: fake_bold * supersample / scale,
                     ..Default::default()
                 },
-                &DrawOptions::new(),
+                &draw_options,
             );
         } else if emoji {
             // noto-emoji and open-moji need this.
@@ -251,7 +414,7 @@ pub(crate) fn rasterize_glyph(
                     width: 1.0 / scale,
                     ..Default::default()
                 },
-                &DrawOptions::new(),
+                &draw_options,
             );
         } else if is_fallback && category == GeneralCategory::OtherSymbol {
             // noto-emoji and open-moji need this.
@@ -262,34 +425,75 @@ pub(crate) fn rasterize_glyph(
                     width: 1.0 / scale,
                     ..Default::default()
                 },
-                &DrawOptions::new(),
+                &draw_options,
             );
         }
 
-        let mut final_image = DrawTarget::new(cached.width as i32, cached.height as i32);
-        final_image.draw_image_with_size_at(
-            cached.width as f32,
-            cached.height as f32,
-            0.,
-            0.,
-            &raqote::Image {
-                width: cached.width as i32 * 2,
-                height: cached.height as i32 * 2,
-                data: &image,
-            },
-            &DrawOptions {
-                blend_mode: raqote::BlendMode::Src,
-                antialias: raqote::AntialiasMode::None,
-                ..Default::default()
-            },
-        );
+        let downscale = |image: &[u32]| {
+            let mut final_image = DrawTarget::new(cached.width as i32, cached.height as i32);
+            final_image.draw_image_with_size_at(
+                cached.width as f32,
+                cached.height as f32,
+                0.,
+                0.,
+                &raqote::Image {
+                    width: target_width,
+                    height: target_height,
+                    data: image,
+                },
+                &DrawOptions {
+                    blend_mode: raqote::BlendMode::Src,
+                    antialias: raqote::AntialiasMode::None,
+                    ..Default::default()
+                },
+            );
+            final_image.into_vec()
+        };
+
+        let final_image = if text_outline > 0.0 && !emoji {
+            // the outline is the glyph stroked on both sides of the
+            // path, the fill is drawn over it.
+            let mut outline_image = image.clone();
+            let mut target =
+                DrawTarget::from_backing(target_width, target_height, &mut outline_image[..]);
+            target.set_transform(
+                &Transform::scale(scale, -scale_y)
+                    .then(&skew)
+                    .then_translate((x_off, y_off).into()),
+            );
+            target.stroke(
+                &path,
+                &raqote::Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
+                &StrokeStyle {
+                    // rendered at the supersampled size.
+                    width: 2.0 * text_outline * supersample / scale,
+                    join: raqote::LineJoin::Round,
+                    ..Default::default()
+                },
+                &draw_options,
+            );
+
+            // the alpha is the coverage of fill and outline,
+            // the colors are the coverage of the fill.
+            downscale(&image)
+                .into_iter()
+                .zip(downscale(&outline_image))
+                .map(|(fill, outline)| {
+                    let fill = fill >> 24;
+                    let alpha = fill.max(outline >> 24);
+                    alpha << 24 | fill << 16 | fill << 8 | fill
+                })
+                .collect()
+        } else {
+            downscale(&image)
+        };
 
         return (
             CacheRect {
                 color: false,
                 ..*cached
             },
-            final_image.into_vec(),
+            final_image,
         );
     }
 
@@ -318,6 +522,27 @@ pub(crate) fn rasterize_glyph(
     )
 }
 
+// Offset that centers the ink of the glyph in the given width.
+fn center_ink(bounds: Rect, width: f32, scale: f32) -> f32 {
+    // outlines that are entirely left of the origin are moved
+    // right when drawn, this must not count twice.
+    let x_min = if bounds.x_max < 0 {
+        0.0
+    } else {
+        bounds.x_min as f32
+    };
+    (width - bounds.width() as f32 * scale) / 2.0 - x_min * scale
+}
+
+// Reduce the factor until the supersampled buffer fits the cap.
+fn supersample_factor(width: u32, height: u32, factor: u8) -> u8 {
+    let mut factor = factor.max(1);
+    while factor > 1 && width * height * (factor as u32).pow(2) > MAX_SUPERSAMPLE_PIXELS {
+        factor -= 1;
+    }
+    factor
+}
+
 fn extract_color_image(
     image: &mut Vec<u32>,
     raster: RasterGlyphImage,
@@ -518,7 +743,9 @@ const LUT_4: [u8; 16] = [
 
 #[cfg(test)]
 mod tests {
-    use crate::font::rasterize::{LUT_2, LUT_4, extract_bw_image, extract_color_image};
+    use crate::font::rasterize::{
+        LUT_2, LUT_4, extract_bw_image, extract_color_image, supersample_factor,
+    };
     use crate::text_atlas::{CacheRect, Entry};
     use image::{GenericImageView, load_from_memory};
     use rustybuzz::ttf_parser::RasterGlyphImage;
@@ -634,7 +861,7 @@ mod tests {
 
         assert_eq!(
             bytemuck::cast_slice::<_, u8>(&extracted),
-            bytemuck::cast_slice(&[
+            bytemuck::cast_slice::<_, u8>(&[
                 [
                     [255u8, 255, 255, 255,],
                     [255, 255, 255, 0,],
@@ -683,7 +910,7 @@ mod tests {
 
         assert_eq!(
             bytemuck::cast_slice::<_, u8>(&extracted),
-            bytemuck::cast_slice(&[
+            bytemuck::cast_slice::<_, u8>(&[
                 [
                     [255u8, 255, 255, 255,],
                     [255, 255, 255, 0,],
@@ -742,7 +969,7 @@ mod tests {
 
         assert_eq!(
             bytemuck::cast_slice::<_, u8>(&extracted),
-            bytemuck::cast_slice(&[
+            bytemuck::cast_slice::<_, u8>(&[
                 [
                     [255, 255, 255, LUT_2[0b10],],
                     [255, 255, 255, LUT_2[0b10],],
@@ -799,7 +1026,7 @@ mod tests {
 
         assert_eq!(
             bytemuck::cast_slice::<_, u8>(&extracted),
-            bytemuck::cast_slice(&[
+            bytemuck::cast_slice::<_, u8>(&[
                 [
                     [255, 255, 255, LUT_2[0b10],],
                     [255, 255, 255, LUT_2[0b10],],
@@ -868,7 +1095,7 @@ mod tests {
 
         assert_eq!(
             bytemuck::cast_slice::<_, u8>(&extracted),
-            bytemuck::cast_slice(&[
+            bytemuck::cast_slice::<_, u8>(&[
                 [[255, 255, 255, LUT_4[0b1010],],],
                 [[255, 255, 255, LUT_4[0b0000],],],
             ])
@@ -907,7 +1134,7 @@ mod tests {
 
         assert_eq!(
             bytemuck::cast_slice::<_, u8>(&extracted),
-            bytemuck::cast_slice(&[
+            bytemuck::cast_slice::<_, u8>(&[
                 [
                     [255, 255, 255, LUT_4[0b1111],],
                     [255, 255, 255, LUT_4[0b0001],],
@@ -919,4 +1146,16 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn supersample_cap() {
+        assert_eq!(supersample_factor(12, 24, 1), 1);
+        assert_eq!(supersample_factor(12, 24, 3), 3);
+        assert_eq!(supersample_factor(12, 24, 0), 1);
+
+        // huge glyphs fall back to less supersampling.
+        assert_eq!(supersample_factor(1024, 1024, 4), 2);
+        assert_eq!(supersample_factor(2048, 2048, 4), 1);
+        assert_eq!(supersample_factor(1500, 1500, 2), 1);
+    }
 }
@@ -1,6 +1,7 @@
 use crate::CellBox;
 use crate::util::intersect;
 use euclid::Vector2D;
+use log::warn;
 use raqote::Transform;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
@@ -68,23 +69,63 @@ pub enum ImageFit {
     /// make the image fit. The image will be clipped or the background
     /// will be visible.
     VerticalEnd,
+    /// The whole image is visible, the background shows on two sides.
+    /// Same as `contain` in CSS and [FitCenter](ImageFit::FitCenter).
+    Contain,
+    /// The image covers the whole area and is centered, the overflow
+    /// is clipped. Same as `cover` in CSS, it's
+    /// [HorizontalCenter](ImageFit::HorizontalCenter) for an image with
+    /// a taller aspect ratio than the area and
+    /// [VerticalCenter](ImageFit::VerticalCenter) otherwise.
+    Cover,
 }
 
 /// The rendered data for one image.
+///
+/// Create it with [ImageCell::new], more fields may be added.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ImageCell {
     pub image_id: usize,
     pub view_rect: (i32, i32, u32, u32),
     pub view_clip: (i32, i32, u32, u32),
     pub below_text: bool,
+    pub z: i32,
     pub tr: Transform,
 }
 
+impl ImageCell {
+    /// New image cell with z-order 0.
+    pub fn new(
+        image_id: usize,
+        view_rect: (i32, i32, u32, u32),
+        view_clip: (i32, i32, u32, u32),
+        below_text: bool,
+        tr: Transform,
+    ) -> Self {
+        Self {
+            image_id,
+            view_rect,
+            view_clip,
+            below_text,
+            z: 0,
+            tr,
+        }
+    }
+
+    /// Paint order within the layer, see [ImageArg::z].
+    pub fn with_z(mut self, z: i32) -> Self {
+        self.z = z;
+        self
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ImageArg {
     view_clip_area: Option<ratatui_core::layout::Rect>,
     view_clip: Option<(i32, i32, u32, u32)>,
     below_text: bool,
+    z: i32,
     fit: Option<ImageFit>,
     tr: Option<Transform>,
 }
@@ -116,12 +157,36 @@ impl ImageArg {
         self
     }
 
+    /// Paint order within the layer below or above the text.
+    ///
+    /// Images with a higher z are painted over images with a lower z.
+    /// Images with the same z are painted in the order they were
+    /// rendered. The default is 0.
+    pub fn z(mut self, z: i32) -> Self {
+        self.z = z;
+        self
+    }
+
     pub fn fit(mut self, fit: ImageFit) -> Self {
         self.fit = Some(fit);
         self.tr = None;
         self
     }
 
+    /// Use a transform to map the view area to the image.
+    ///
+    /// The transform works with normalized coordinates.
+    /// It maps the view area (0,0 is top-left, 1,1 is bottom-right)
+    /// to the image texture (0,0 is top-left, 1,1 is bottom-right).
+    /// Anything mapped outside the range 0..1 is transparent.
+    ///
+    /// As this goes from view to image it's the inverse of what you
+    /// would expect. To zoom in by 2 use `Transform::scale(0.5, 0.5)`,
+    /// to pan the image to the left by a quarter of its width use
+    /// `then_translate(Vector2D::new(0.25, 0.0))`.
+    ///
+    /// The transform must be invertible, otherwise the image will
+    /// not be rendered.
     pub fn transform(mut self, tr: Transform) -> Self {
         self.tr = Some(tr);
         self.fit = None;
@@ -164,6 +229,8 @@ pub struct ImageBuffer {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ImageScale {
     XY,
+    // the other way round than XY.
+    Cover,
     X,
     Y,
 }
@@ -176,6 +243,11 @@ enum ImageAlign {
 }
 
 impl ImageBuffer {
+    /// Create a new ImageBuffer with the given image-sizes.
+    ///
+    /// This doesn't need a backend, which allows rendering images
+    /// into a buffer and checking the resulting [ImageCell]s without
+    /// a GPU.
     pub fn new(
         area: ratatui_core::layout::Rect,
         cell_box: CellBox,
@@ -274,6 +346,11 @@ impl ImageBuffer {
     /// To get an ImageHandle add the image first with [add_image]. Add image
     /// will create the texture for the image.
     pub fn render_px(&mut self, id: &ImageHandle, rect: (i32, i32, u32, u32), arg: ImageArg) {
+        if !self.image_size.contains_key(&id.id()) {
+            warn!("image {}: unknown handle, not rendered.", id.id());
+            return;
+        }
+
         let tr = if let Some(fit) = arg.fit {
             use ImageAlign::*;
             use ImageScale::*;
@@ -316,6 +393,14 @@ impl ImageBuffer {
                     let img = self.image_size(id).expect("img1");
                     self.scale_to_fit(img, (rect.2, rect.3), Y, End)
                 }
+                ImageFit::Contain => {
+                    let img = self.image_size(id).expect("img1");
+                    self.scale_to_fit(img, (rect.2, rect.3), XY, Center)
+                }
+                ImageFit::Cover => {
+                    let img = self.image_size(id).expect("img1");
+                    self.scale_to_fit(img, (rect.2, rect.3), Cover, Center)
+                }
             }
         } else if let Some(tr) = arg.tr {
             tr
@@ -323,6 +408,15 @@ impl ImageBuffer {
             Transform::default()
         };
 
+        if !valid_uv_transform(&tr) {
+            warn!(
+                "image {}: degenerate transform {:?}, not rendered.",
+                id.id(),
+                tr
+            );
+            return;
+        }
+
         let clip = if let Some(clip) = arg.view_clip {
             clip
         } else if let Some(area) = arg.view_clip_area {
@@ -331,13 +425,8 @@ impl ImageBuffer {
             rect
         };
 
-        self.images.push(ImageCell {
-            image_id: id.id(),
-            view_rect: rect,
-            view_clip: clip,
-            below_text: arg.below_text,
-            tr,
-        });
+        self.images
+            .push(ImageCell::new(id.id(), rect, clip, arg.below_text, tr).with_z(arg.z));
     }
 
     /// Scale the image for the best fit in the given area.
@@ -363,6 +452,13 @@ impl ImageBuffer {
                 scale = X;
             }
         }
+        if scale == Cover {
+            if view_width * img_height / view_height > img_width {
+                scale = X;
+            } else {
+                scale = Y;
+            }
+        }
 
         if scale == Y {
             let w_scale = (view_width * img_height) / (view_height * img_width);
@@ -395,3 +491,104 @@ impl ImageBuffer {
         }
     }
 }
+
+// The transform is used for the uv-coordinates. A singular transform
+// would map everything to a line or a point.
+fn valid_uv_transform(tr: &Transform) -> bool {
+    let det = tr.determinant();
+    det.is_finite() && det != 0.0 && tr.m31.is_finite() && tr.m32.is_finite()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CellBox;
+    use crate::image::{ImageArg, ImageBuffer, ImageFit, ImageHandle};
+    use raqote::Transform;
+    use std::collections::HashMap;
+
+    // Transform for an image rendered with the given fit.
+    fn fit_transform(img: (u32, u32), view: (u32, u32), fit: ImageFit) -> Transform {
+        let handle = ImageHandle::new(1);
+        let mut buffer = ImageBuffer::new(
+            Default::default(),
+            CellBox::default(),
+            HashMap::from([(handle.id(), img)]),
+        );
+        buffer.render_px(&handle, (0, 0, view.0, view.1), ImageArg::new().fit(fit));
+        buffer.images()[0].tr
+    }
+
+    // scale and translation of the transform.
+    fn parts(tr: Transform) -> (f32, f32, f32, f32) {
+        assert_eq!((tr.m12, tr.m21), (0.0, 0.0));
+        (tr.m11, tr.m22, tr.m31, tr.m32)
+    }
+
+    #[test]
+    fn fit_wide_image() {
+        // the image covers half of the view height.
+        let wide = |fit| parts(fit_transform((200, 100), (100, 100), fit));
+        assert_eq!(wide(ImageFit::Fill), (1.0, 1.0, 0.0, 0.0));
+        assert_eq!(wide(ImageFit::FitStart), (1.0, 2.0, 0.0, 0.0));
+        assert_eq!(wide(ImageFit::FitCenter), (1.0, 2.0, 0.0, -0.5));
+        assert_eq!(wide(ImageFit::FitEnd), (1.0, 2.0, 0.0, -1.0));
+        assert_eq!(wide(ImageFit::HorizontalStart), (1.0, 2.0, 0.0, 0.0));
+        assert_eq!(wide(ImageFit::HorizontalCenter), (1.0, 2.0, 0.0, -0.5));
+        assert_eq!(wide(ImageFit::HorizontalEnd), (1.0, 2.0, 0.0, -1.0));
+        // the image is clipped to the middle half.
+        assert_eq!(wide(ImageFit::VerticalStart), (0.5, 1.0, 0.0, 0.0));
+        assert_eq!(wide(ImageFit::VerticalCenter), (0.5, 1.0, 0.25, 0.0));
+        assert_eq!(wide(ImageFit::VerticalEnd), (0.5, 1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn fit_tall_image() {
+        // the image covers half of the view width.
+        let tall = |fit| parts(fit_transform((100, 200), (100, 100), fit));
+        assert_eq!(tall(ImageFit::FitStart), (2.0, 1.0, 0.0, 0.0));
+        assert_eq!(tall(ImageFit::FitCenter), (2.0, 1.0, -0.5, 0.0));
+        assert_eq!(tall(ImageFit::FitEnd), (2.0, 1.0, -1.0, 0.0));
+        assert_eq!(tall(ImageFit::HorizontalCenter), (1.0, 0.5, 0.0, 0.25));
+        assert_eq!(tall(ImageFit::VerticalCenter), (2.0, 1.0, -0.5, 0.0));
+    }
+
+    #[test]
+    fn contain_and_cover() {
+        // a wide image in a tall area.
+        let contain = parts(fit_transform((200, 100), (100, 200), ImageFit::Contain));
+        let cover = parts(fit_transform((200, 100), (100, 200), ImageFit::Cover));
+        // letterboxed, the image covers the middle quarter of the height.
+        assert_eq!(contain, (1.0, 4.0, 0.0, -1.5));
+        assert_eq!(
+            contain,
+            parts(fit_transform((200, 100), (100, 200), ImageFit::FitCenter))
+        );
+        // the middle quarter of the width is visible.
+        assert_eq!(cover, (0.25, 1.0, 0.375, 0.0));
+
+        // the area is covered in both directions.
+        let cover = fit_transform((100, 200), (200, 100), ImageFit::Cover);
+        let top_left = cover.transform_point((0.0, 0.0).into());
+        let bottom_right = cover.transform_point((1.0, 1.0).into());
+        assert!(top_left.x >= 0.0 && top_left.y >= 0.0);
+        assert!(bottom_right.x <= 1.0 && bottom_right.y <= 1.0);
+    }
+
+    #[test]
+    fn fit_maps_view_to_image() {
+        // a 4:1 image centered in a 200x100 view spans 0.25..0.75 of
+        // the view height.
+        let tr = fit_transform((400, 100), (200, 100), ImageFit::FitCenter);
+        let top = tr.transform_point((0.0, 0.25).into());
+        let bottom = tr.transform_point((1.0, 0.75).into());
+        assert_eq!((top.x, top.y), (0.0, 0.0));
+        assert_eq!((bottom.x, bottom.y), (1.0, 1.0));
+    }
+
+    #[test]
+    fn unknown_image() {
+        let mut buffer = ImageBuffer::default();
+        buffer.render_px(&ImageHandle::new(1), (0, 0, 10, 10), ImageArg::new());
+        assert!(buffer.images().is_empty());
+    }
+}
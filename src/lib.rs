@@ -11,11 +11,16 @@ pub(crate) mod util;
 #[cfg(feature = "winit-event")]
 pub mod events;
 
+pub use backend::RenderStats;
 pub use backend::backend::WgpuBackend;
 pub use backend::builder::Builder;
+pub use backend::shape_cache::ShapingStats;
+pub use text_atlas::{AtlasStats, GlyphKey};
 
 pub mod wgpu {
-    pub use wgpu::Backends;
+    pub use wgpu::{
+        AdapterInfo, Backend, Backends, Color, FilterMode, LoadOp, PowerPreference, TextureFormat,
+    };
 }
 
 /// The metrics needed for rendering.
@@ -44,14 +49,221 @@ impl CellBox {
     }
 }
 
+/// Margins in px that are kept free of cells.
+///
+/// Cells in the rounded corners of a window, or below a notch, may
+/// be clipped. The inset is filled with the margin color.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Inset {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl Inset {
+    /// Same inset on all sides.
+    pub fn uniform(px: u32) -> Self {
+        Self {
+            top: px,
+            right: px,
+            bottom: px,
+            left: px,
+        }
+    }
+
+    /// Columns and rows that fit into the surface.
+    pub(crate) fn grid(&self, width: u32, height: u32, cell_box: CellBox) -> (u32, u32) {
+        (
+            width.saturating_sub(self.left + self.right) / cell_box.width,
+            height.saturating_sub(self.top + self.bottom) / cell_box.height,
+        )
+    }
+}
+
+/// Rotation of the whole terminal on the surface, clockwise.
+///
+/// For portrait or upside-down mounted displays. The cell grid is
+/// laid out for the rotated orientation, with 90 and 270 degrees
+/// width and height of the surface are swapped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// Not rotated.
+    #[default]
+    None,
+    /// Rotated by 90 degrees clockwise. The top of the terminal is at
+    /// the right side of the surface.
+    Cw90,
+    /// Upside down.
+    Cw180,
+    /// Rotated by 270 degrees clockwise. The top of the terminal is at
+    /// the left side of the surface.
+    Cw270,
+}
+
+impl Rotation {
+    /// Size of the terminal for a surface of the given size.
+    pub(crate) fn rotated_size(self, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Rotation::None | Rotation::Cw180 => (width, height),
+            Rotation::Cw90 | Rotation::Cw270 => (height, width),
+        }
+    }
+
+    /// Position on the surface to the position on the terminal.
+    /// Width and height are the size of the surface.
+    pub(crate) fn unrotate(self, x: i32, y: i32, width: u32, height: u32) -> (i32, i32) {
+        let (width, height) = (width as i32, height as i32);
+        match self {
+            Rotation::None => (x, y),
+            Rotation::Cw90 => (y, width - 1 - x),
+            Rotation::Cw180 => (width - 1 - x, height - 1 - y),
+            Rotation::Cw270 => (height - 1 - y, x),
+        }
+    }
+
+    /// Position on the terminal to the position on the surface.
+    /// Width and height are the size of the surface.
+    pub(crate) fn rotate(self, x: i32, y: i32, width: u32, height: u32) -> (i32, i32) {
+        let (width, height) = (width as i32, height as i32);
+        match self {
+            Rotation::None => (x, y),
+            Rotation::Cw90 => (width - 1 - y, x),
+            Rotation::Cw180 => (width - 1 - x, height - 1 - y),
+            Rotation::Cw270 => (y, height - 1 - x),
+        }
+    }
+}
+
+/// How control characters (U+0000..=U+001F and U+007F) in a cell
+/// are shown.
+///
+/// Ratatui filters control chars when writing to a buffer, they only
+/// arrive with the low-level APIs like [Cell::set_symbol] and cells
+/// given to [Backend::draw] directly.
+///
+/// [Cell::set_symbol]: ratatui_core::buffer::Cell::set_symbol
+/// [Backend::draw]: ratatui_core::backend::Backend::draw
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCharDisplay {
+    /// As a blank cell.
+    #[default]
+    Blank,
+    /// In caret notation, `^A` for U+0001 and `^?` for U+007F.
+    /// Both chars are squeezed into the cell.
+    Caret,
+    /// With the control pictures, `␁` for U+0001.
+    Pictures,
+}
+
+impl ControlCharDisplay {
+    /// Push the symbol of a cell to the shaping buffer.
+    /// A control char is replaced accordingly.
+    pub(crate) fn push_symbol(self, buf: &mut String, symbol: &str) {
+        let Some(ch) = control_char(symbol) else {
+            buf.push_str(symbol);
+            return;
+        };
+        match self {
+            ControlCharDisplay::Blank => buf.push(' '),
+            ControlCharDisplay::Caret => {
+                buf.push('^');
+                buf.push((ch as u8 ^ 0x40) as char);
+            }
+            ControlCharDisplay::Pictures => {
+                let picture = if ch == '\x7f' {
+                    '\u{2421}'
+                } else {
+                    char::from_u32(0x2400 + ch as u32).expect("control picture")
+                };
+                buf.push(picture);
+            }
+        }
+    }
+
+    /// The cell is shown in caret notation.
+    pub(crate) fn is_caret(self, symbol: &str) -> bool {
+        self == ControlCharDisplay::Caret && control_char(symbol).is_some()
+    }
+}
+
+/// The symbol is a single C0 control char or DEL.
+fn control_char(symbol: &str) -> Option<char> {
+    let mut chars = symbol.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if ch < ' ' || ch == '\x7f' => Some(ch),
+        _ => None,
+    }
+}
+
+/// Modifiers that are rendered with decorations ratatui has no
+/// modifier for. A cell with any of the modifiers of a field gets
+/// the decoration in addition to its usual styling.
+///
+/// The default is empty and maps nothing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ModifierRemap {
+    /// A line at the top of the cell.
+    pub overline: ratatui_core::style::Modifier,
+    /// Two lines at the underline position.
+    pub double_underline: ratatui_core::style::Modifier,
+}
+
+/// Base direction for the bidi algorithm.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BaseDirection {
+    /// Each row detects its direction from the first strong character.
+    #[default]
+    Auto,
+    /// Left-to-right.
+    Ltr,
+    /// Right-to-left. Rows are aligned to the right even if they
+    /// start with neutral characters.
+    Rtl,
+}
+
+/// Scaling of a single row, like the DEC double-width and
+/// double-height lines.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineScale {
+    #[default]
+    Normal,
+    /// Each cell is rendered twice as wide. Only the left half
+    /// of the row is visible.
+    DoubleWidth,
+    /// Upper half of a double-height, double-width row.
+    DoubleHeightTop,
+    /// Lower half of a double-height, double-width row.
+    DoubleHeightBottom,
+}
+
+impl LineScale {
+    /// Horizontal scale factor.
+    pub(crate) fn scale_x(&self) -> i32 {
+        match self {
+            LineScale::Normal => 1,
+            LineScale::DoubleWidth | LineScale::DoubleHeightTop | LineScale::DoubleHeightBottom => {
+                2
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     SurfaceCreationFailed(Box<dyn std::error::Error>),
     AdapterRequestFailed(Box<dyn std::error::Error>),
+    AdapterNotFound(::wgpu::AdapterInfo),
+    AdapterNotCompatible(::wgpu::AdapterInfo),
     DeviceRequestFailed(Box<dyn std::error::Error>),
+    DeviceLimitsNotSupported,
     SurfaceConfigurationRequestFailed,
+    SurfaceFormatNotSupported(::wgpu::TextureFormat),
     PollError(Box<dyn std::error::Error>),
+    SurfaceTextureFailed(::wgpu::SurfaceError),
     BufferAsyncError(String),
+    HeadlessReadbackDisabled,
+    InvalidColor(String),
 }
 
 impl Display for Error {
@@ -22,6 +22,8 @@ struct Uniforms {
     margin_color: u32,
     preserve_aspect: u32,
     use_srgb: u32,
+    opacity: f32,
+    premultiplied: u32,
     _fill: u32
 }
 
@@ -39,5 +41,9 @@ fn fs_main(@builtin(position) gl_Position: vec4<f32>) -> FragmentOutput {
 
     let out = select(color, marginColor, uv.x > 1.0 || uv.y > 1.0);
 
-    return FragmentOutput(out);
+    // window opacity. a premultiplied surface needs it in the color too.
+    let alpha = out.a * uniforms.opacity;
+    let rgb = select(out.rgb, out.rgb * alpha, uniforms.premultiplied != 0u);
+
+    return FragmentOutput(vec4(rgb, alpha));
 }
\ No newline at end of file
@@ -0,0 +1,558 @@
+use crate::CellBox;
+use crate::postprocessor::{PostProcessor, PostProcessorBuilder};
+use std::num::NonZeroU64;
+use wgpu::{
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferBindingType, BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites,
+    CommandEncoder, CompositeAlphaMode, Device, Extent3d, FilterMode, FragmentState, LoadOp,
+    MipmapFilterMode, MultisampleState, Operations, PipelineCompilationOptions, PipelineLayout,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+    SamplerDescriptor, ShaderModule, ShaderStages, StoreOp, SurfaceConfiguration,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDescriptor, TextureViewDimension, VertexState, include_wgsl,
+};
+
+/// Builder for the [BloomPostProcessor].
+#[derive(Debug, Clone)]
+pub struct BloomPostProcessorBuilder {
+    threshold: f32,
+    intensity: f32,
+}
+
+impl Default for BloomPostProcessorBuilder {
+    fn default() -> Self {
+        Self {
+            threshold: 0.6,
+            intensity: 0.8,
+        }
+    }
+}
+
+impl BloomPostProcessorBuilder {
+    /// Luminance above which pixels start to glow.
+    ///
+    /// A good range of values is 0.3 to 0.9.
+    /// Defaults to 0.6.
+    #[must_use]
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Strength of the glow added to the text.
+    ///
+    /// A good range of values is 0.2 to 2.0.
+    /// Defaults to 0.8.
+    #[must_use]
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+}
+
+/// A post-processor which adds a glow around bright text.
+///
+/// The bright parts of the text are extracted, blurred with a
+/// separable gaussian and added on top of the text.
+pub struct BloomPostProcessor {
+    threshold: f32,
+    intensity: f32,
+    opacity: f32,
+
+    pipelines: BloomPipelines,
+    targets: BloomTargets,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Uniforms {
+    screen_size: [f32; 2],
+    direction: [f32; 2],
+    threshold: f32,
+    intensity: f32,
+    margin_color: u32,
+    preserve_aspect: u32,
+    use_srgb: u32,
+    opacity: f32,
+    premultiplied: u32,
+    _fill: u32,
+}
+
+struct BloomPipelines {
+    layout: BindGroupLayout,
+    bloom_layout: BindGroupLayout,
+    sampler: Sampler,
+    blur_sampler: Sampler,
+
+    uniforms: Buffer,
+    blur_x_uniforms: Buffer,
+    blur_y_uniforms: Buffer,
+
+    bright: RenderPipeline,
+    blur: RenderPipeline,
+    composite: RenderPipeline,
+}
+
+// Ping-pong textures and their bindings. Sized like the text view.
+//
+// bright-pass: text -> pong
+// blur x: pong -> ping
+// blur y: ping -> pong
+// composite: text + pong -> surface
+struct BloomTargets {
+    width: u32,
+    height: u32,
+
+    ping: TextureView,
+    pong: TextureView,
+
+    bright_bindings: BindGroup,
+    blur_x_bindings: BindGroup,
+    blur_y_bindings: BindGroup,
+    composite_bindings: BindGroup,
+    bloom_bindings: BindGroup,
+}
+
+impl PostProcessorBuilder for BloomPostProcessorBuilder {
+    type PostProcessor<'a> = BloomPostProcessor;
+
+    fn compile(
+        self,
+        device: &Device,
+        text_view: &TextureView,
+        surface_config: &SurfaceConfiguration,
+    ) -> BloomPostProcessor {
+        let pipelines = BloomPipelines::new(device, surface_config);
+        let targets = BloomTargets::new(device, &pipelines, text_view);
+
+        BloomPostProcessor {
+            threshold: self.threshold,
+            intensity: self.intensity,
+            opacity: 1.0,
+            pipelines,
+            targets,
+        }
+    }
+}
+
+impl BloomPipelines {
+    fn new(device: &Device, surface_config: &SurfaceConfiguration) -> Self {
+        let uniforms_buffer = |label| {
+            device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size: size_of::<Uniforms>() as u64,
+                usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+                mapped_at_creation: false,
+            })
+        };
+        let uniforms = uniforms_buffer("Bloom Uniforms");
+        let blur_x_uniforms = uniforms_buffer("Bloom Blur x Uniforms");
+        let blur_y_uniforms = uniforms_buffer("Bloom Blur y Uniforms");
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let blur_sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let texture_entry = BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = BindGroupLayoutEntry {
+            binding: 1,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        };
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Bloom Bindings Layout"),
+            entries: &[
+                texture_entry,
+                sampler_entry,
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(size_of::<Uniforms>() as u64),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bloom_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Bloom Texture Layout"),
+            entries: &[texture_entry, sampler_entry],
+        });
+
+        let shader = device.create_shader_module(include_wgsl!("bloom.wgsl"));
+
+        let pass_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Bloom Pass Layout"),
+            bind_group_layouts: &[&layout],
+            immediate_size: 0,
+        });
+        let composite_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Bloom Composite Layout"),
+            bind_group_layouts: &[&layout, &bloom_layout],
+            immediate_size: 0,
+        });
+
+        let bright = build_pipeline(
+            device,
+            &shader,
+            &pass_layout,
+            "fs_bright",
+            TextureFormat::Rgba8Unorm,
+        );
+        let blur = build_pipeline(
+            device,
+            &shader,
+            &pass_layout,
+            "fs_blur",
+            TextureFormat::Rgba8Unorm,
+        );
+        let composite = build_pipeline(
+            device,
+            &shader,
+            &composite_layout,
+            "fs_composite",
+            surface_config.format,
+        );
+
+        Self {
+            layout,
+            bloom_layout,
+            sampler,
+            blur_sampler,
+            uniforms,
+            blur_x_uniforms,
+            blur_y_uniforms,
+            bright,
+            blur,
+            composite,
+        }
+    }
+}
+
+fn build_pipeline(
+    device: &Device,
+    shader: &ShaderModule,
+    layout: &PipelineLayout,
+    entry_point: &str,
+    format: TextureFormat,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Bloom Pipeline"),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            compilation_options: PipelineCompilationOptions::default(),
+            buffers: &[],
+        },
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: Some(entry_point),
+            compilation_options: PipelineCompilationOptions::default(),
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        multiview_mask: None,
+        cache: None,
+    })
+}
+
+impl BloomTargets {
+    fn new(device: &Device, pipelines: &BloomPipelines, text_view: &TextureView) -> Self {
+        let width = text_view.texture().width();
+        let height = text_view.texture().height();
+
+        let target = |label| {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: Some(label),
+                    size: Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8Unorm,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor::default())
+        };
+        let ping = target("Bloom Ping");
+        let pong = target("Bloom Pong");
+
+        let bindings = |label, source: &TextureView, sampler: &Sampler, uniforms: &Buffer| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some(label),
+                layout: &pipelines.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: uniforms.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+
+        let bright_bindings = bindings(
+            "Bloom Bright Bindings",
+            text_view,
+            &pipelines.sampler,
+            &pipelines.uniforms,
+        );
+        let blur_x_bindings = bindings(
+            "Bloom Blur x Bindings",
+            &pong,
+            &pipelines.blur_sampler,
+            &pipelines.blur_x_uniforms,
+        );
+        let blur_y_bindings = bindings(
+            "Bloom Blur y Bindings",
+            &ping,
+            &pipelines.blur_sampler,
+            &pipelines.blur_y_uniforms,
+        );
+        let composite_bindings = bindings(
+            "Bloom Composite Bindings",
+            text_view,
+            &pipelines.sampler,
+            &pipelines.uniforms,
+        );
+        let bloom_bindings = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Bloom Texture Bindings"),
+            layout: &pipelines.bloom_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&pong),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&pipelines.blur_sampler),
+                },
+            ],
+        });
+
+        Self {
+            width,
+            height,
+            ping,
+            pong,
+            bright_bindings,
+            blur_x_bindings,
+            blur_y_bindings,
+            composite_bindings,
+            bloom_bindings,
+        }
+    }
+}
+
+impl BloomPostProcessor {
+    /// Luminance above which pixels start to glow.
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Luminance above which pixels start to glow.
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    /// Strength of the glow.
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    /// Strength of the glow.
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+}
+
+impl PostProcessor for BloomPostProcessor {
+    fn map_to_cell(&self, scr_x: i32, scr_y: i32, font_box: CellBox) -> (u16, u16) {
+        if scr_x < 0 || scr_y < 0 {
+            (0, 0)
+        } else {
+            (
+                (scr_x as u32 / font_box.width) as u16,
+                (scr_y as u32 / font_box.height) as u16,
+            )
+        }
+    }
+
+    fn resize(
+        &mut self,
+        device: &Device,
+        text_view: &TextureView,
+        _surface_config: &SurfaceConfiguration,
+    ) {
+        self.targets = BloomTargets::new(device, &self.pipelines, text_view);
+    }
+
+    fn process(
+        &mut self,
+        margin_color: u32,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        _text_view: &TextureView,
+        surface_config: &SurfaceConfiguration,
+        surface_view: &TextureView,
+    ) {
+        #[cfg(feature = "scale_to_window")]
+        let preserve_aspect = false;
+        #[cfg(not(feature = "scale_to_window"))]
+        let preserve_aspect = true;
+
+        let uniforms = Uniforms {
+            screen_size: [surface_config.width as f32, surface_config.height as f32],
+            direction: [0.0, 0.0],
+            threshold: self.threshold,
+            intensity: self.intensity,
+            margin_color,
+            preserve_aspect: u32::from(preserve_aspect),
+            use_srgb: u32::from(surface_config.format.is_srgb()),
+            opacity: self.opacity,
+            premultiplied: u32::from(
+                surface_config.alpha_mode == CompositeAlphaMode::PreMultiplied,
+            ),
+            _fill: 0,
+        };
+        for (buffer, direction) in [
+            (&self.pipelines.uniforms, [0.0, 0.0]),
+            (
+                &self.pipelines.blur_x_uniforms,
+                [1.0 / self.targets.width as f32, 0.0],
+            ),
+            (
+                &self.pipelines.blur_y_uniforms,
+                [0.0, 1.0 / self.targets.height as f32],
+            ),
+        ] {
+            let mut view = queue
+                .write_buffer_with(
+                    buffer,
+                    0,
+                    NonZeroU64::new(size_of::<Uniforms>() as u64).unwrap(),
+                )
+                .unwrap();
+            view.copy_from_slice(bytemuck::bytes_of(&Uniforms {
+                direction,
+                ..uniforms
+            }));
+        }
+
+        for (label, pipeline, bindings, target) in [
+            (
+                "Bloom Bright Pass",
+                &self.pipelines.bright,
+                &self.targets.bright_bindings,
+                &self.targets.pong,
+            ),
+            (
+                "Bloom Blur x Pass",
+                &self.pipelines.blur,
+                &self.targets.blur_x_bindings,
+                &self.targets.ping,
+            ),
+            (
+                "Bloom Blur y Pass",
+                &self.pipelines.blur,
+                &self.targets.blur_y_bindings,
+                &self.targets.pong,
+            ),
+        ] {
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                ..Default::default()
+            });
+
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, bindings, &[]);
+            pass.draw(0..3, 0..1);
+        }
+
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Bloom Composite Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            ..Default::default()
+        });
+
+        pass.set_pipeline(&self.pipelines.composite);
+        pass.set_bind_group(0, &self.targets.composite_bindings, &[]);
+        pass.set_bind_group(1, &self.targets.bloom_bindings, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn set_window_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+}
@@ -0,0 +1,86 @@
+struct VertexOutput {
+    @builtin(position) gl_Position: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2(f32((index << 1) & 2), f32(index & 2));
+    return VertexOutput(vec4(uv * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0));
+}
+
+struct FragmentOutput {
+    @location(0) FragColor: vec4<f32>,
+}
+
+struct Uniforms {
+    screen_size: vec2<f32>,
+    direction: vec2<f32>,
+    threshold: f32,
+    intensity: f32,
+    margin_color: u32,
+    preserve_aspect: u32,
+    use_srgb: u32,
+    opacity: f32,
+    premultiplied: u32,
+    _fill: u32,
+}
+
+@group(0) @binding(0)
+var tex: texture_2d<f32>;
+@group(0) @binding(1)
+var tex_s: sampler;
+@group(0) @binding(2)
+var<uniform> uniforms: Uniforms;
+
+@group(1) @binding(0)
+var bloom_tex: texture_2d<f32>;
+@group(1) @binding(1)
+var bloom_tex_s: sampler;
+
+// Keep only what is brighter than the threshold.
+@fragment
+fn fs_bright(@builtin(position) gl_Position: vec4<f32>) -> FragmentOutput {
+    let uv = gl_Position.xy / vec2<f32>(textureDimensions(tex));
+    let color = textureSample(tex, tex_s, uv);
+    let luma = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+    let weight = clamp((luma - uniforms.threshold) / max(1.0 - uniforms.threshold, 0.0001), 0.0, 1.0);
+
+    return FragmentOutput(vec4(color.rgb * weight, 1.0));
+}
+
+// One direction of a separable 9-tap gaussian.
+@fragment
+fn fs_blur(@builtin(position) gl_Position: vec4<f32>) -> FragmentOutput {
+    let uv = gl_Position.xy / vec2<f32>(textureDimensions(tex));
+    let weights = array(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+    var blur = textureSample(tex, tex_s, uv).rgb * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = f32(i) * uniforms.direction;
+        blur += textureSample(tex, tex_s, uv + offset).rgb * weights[i];
+        blur += textureSample(tex, tex_s, uv - offset).rgb * weights[i];
+    }
+
+    return FragmentOutput(vec4(blur, 1.0));
+}
+
+// Add the glow to the text and blit to the surface.
+@fragment
+fn fs_composite(@builtin(position) gl_Position: vec4<f32>) -> FragmentOutput {
+    let target_size = select(vec2<f32>(textureDimensions(tex)), uniforms.screen_size, uniforms.preserve_aspect == 0u);
+    let uv = gl_Position.xy / target_size;
+    let factor = select(2.2, 1.0, uniforms.use_srgb == 0u);
+
+    let text = textureSample(tex, tex_s, uv);
+    let bloom = textureSample(bloom_tex, bloom_tex_s, uv).rgb * uniforms.intensity;
+    let color = pow(vec4(min(text.rgb + bloom, vec3(1.0)), text.a), vec4(vec3(factor), 1.0));
+    let marginColor = pow(unpack4x8unorm(uniforms.margin_color), vec4(vec3(factor), 1.0));
+
+    let out = select(color, marginColor, uv.x > 1.0 || uv.y > 1.0);
+
+    // window opacity. a premultiplied surface needs it in the color too.
+    let alpha = out.a * uniforms.opacity;
+    let rgb = select(out.rgb, out.rgb * alpha, uniforms.premultiplied != 0u);
+
+    return FragmentOutput(vec4(rgb, alpha));
+}
@@ -6,14 +6,14 @@ use wgpu::{
     AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
     BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
     BufferBindingType, BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites,
-    CommandEncoder, Device, Extent3d, FilterMode, FragmentState, LoadOp, MipmapFilterMode,
-    MultisampleState, Operations, PipelineCompilationOptions, PipelineLayoutDescriptor,
-    PrimitiveState, PrimitiveTopology, Queue, RenderBundle, RenderBundleDescriptor,
-    RenderBundleEncoderDescriptor, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
-    RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages,
-    StoreOp, SurfaceConfiguration, Texture, TextureDescriptor, TextureDimension, TextureFormat,
-    TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
-    VertexState, include_wgsl,
+    CommandEncoder, CompositeAlphaMode, Device, Extent3d, FilterMode, FragmentState, LoadOp,
+    MipmapFilterMode, MultisampleState, Operations, PipelineCompilationOptions,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, Queue, RenderBundle,
+    RenderBundleDescriptor, RenderBundleEncoderDescriptor, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+    SamplerDescriptor, ShaderStages, StoreOp, SurfaceConfiguration, Texture, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexState, include_wgsl,
 };
 
 #[repr(C)]
@@ -35,7 +35,8 @@ struct CrtUniforms {
     mask_strength: f32,
     use_srgb: i32,
     milliseconds: u32,
-    _pad1: [f32; 2],
+    opacity: f32,
+    premultiplied: u32,
 }
 
 /// Settings for the CRT post-processor.
@@ -139,7 +140,11 @@ pub struct CrtPostProcessor {
 
     width: u32,
     height: u32,
+    // the text is stretched to the surface.
+    text_width: u32,
+    text_height: u32,
     timer: Instant,
+    opacity: f32,
 
     settings: CrtSettings,
 }
@@ -453,24 +458,77 @@ impl PostProcessorBuilder for CrtPostProcessorBuilder {
             accumulate_view_out,
             width: drawable_width,
             height: drawable_height,
+            text_width: text_view.texture().width(),
+            text_height: text_view.texture().height(),
             timer: Instant::now(),
+            opacity: 1.0,
             settings: self.settings,
         }
     }
 }
 
+// Screen uv to text uv, the same as the curve in crt.wgsl.
+fn warp(uv: (f32, f32), curve_factor: f32) -> (f32, f32) {
+    let (x, y) = (uv.0 * 2.0 - 1.0, uv.1 * 2.0 - 1.0);
+    let curved = (
+        (x * (1.0 + y * y * 0.031)) / 2.0 + 0.5,
+        (y * (1.0 + x * x * 0.041)) / 2.0 + 0.5,
+    );
+    let curved = (
+        (curved.0 - uv.0) * curve_factor + uv.0,
+        (curved.1 - uv.1) * curve_factor + uv.1,
+    );
+    // mix(curve(uv), uv, 0.4)
+    (curved.0 * 0.6 + uv.0 * 0.4, curved.1 * 0.6 + uv.1 * 0.4)
+}
+
+// Text uv to screen uv. There is no closed form, but the warp is
+// close to the identity, a few fixed-point iterations are enough.
+fn unwarp(uv: (f32, f32), curve_factor: f32) -> (f32, f32) {
+    let mut scr = uv;
+    for _ in 0..16 {
+        let warped = warp(scr, curve_factor);
+        scr = (scr.0 + uv.0 - warped.0, scr.1 + uv.1 - warped.1);
+    }
+    scr
+}
+
 impl PostProcessor for CrtPostProcessor {
     fn map_to_cell(&self, scr_x: i32, scr_y: i32, font_box: CellBox) -> (u16, u16) {
-        if scr_x < 0 || scr_y < 0 {
+        let uv = warp(
+            (
+                scr_x as f32 / self.width as f32,
+                scr_y as f32 / self.height as f32,
+            ),
+            self.settings.curve_factor,
+        );
+        let x = uv.0 * self.text_width as f32;
+        let y = uv.1 * self.text_height as f32;
+
+        if x < 0.0 || y < 0.0 {
             (0, 0)
         } else {
             (
-                (scr_x as u32 / font_box.width) as u16,
-                (scr_y as u32 / font_box.height) as u16,
+                (x / font_box.width as f32) as u16,
+                (y / font_box.height as f32) as u16,
             )
         }
     }
 
+    fn map_from_cell(&self, text_x: u32, text_y: u32) -> (i32, i32) {
+        let uv = unwarp(
+            (
+                text_x as f32 / self.text_width as f32,
+                text_y as f32 / self.text_height as f32,
+            ),
+            self.settings.curve_factor,
+        );
+        (
+            (uv.0 * self.width as f32).round() as i32,
+            (uv.1 * self.height as f32).round() as i32,
+        )
+    }
+
     fn resize(
         &mut self,
         device: &Device,
@@ -581,7 +639,10 @@ impl PostProcessor for CrtPostProcessor {
                 mask_strength: self.settings.mask_strength,
                 use_srgb: i32::from(surface_config.format.is_srgb()),
                 milliseconds: self.timer.elapsed().as_millis() as u32,
-                _pad1: [0.0; 2],
+                opacity: self.opacity,
+                premultiplied: u32::from(
+                    surface_config.alpha_mode == CompositeAlphaMode::PreMultiplied,
+                ),
             }));
         }
         self.timer = Instant::now();
@@ -629,6 +690,10 @@ impl PostProcessor for CrtPostProcessor {
     fn needs_update(&self) -> bool {
         self.settings.slow_fade == 1.0
     }
+
+    fn set_window_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
 }
 
 fn build_blur(device: &Device) -> (RenderPipeline, BindGroupLayout) {
@@ -784,3 +849,25 @@ fn build_crt(
 
     (pipeline, fs_uniforms)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{unwarp, warp};
+
+    #[test]
+    fn unwarp_inverts_warp() {
+        for curve_factor in [0.0, 1.0, 2.0] {
+            for uv in [(0.0, 0.0), (0.1, 0.9), (0.5, 0.5), (0.73, 0.2), (1.0, 1.0)] {
+                let scr = unwarp(uv, curve_factor);
+                let back = warp(scr, curve_factor);
+                assert!((back.0 - uv.0).abs() < 1e-4);
+                assert!((back.1 - uv.1).abs() < 1e-4);
+            }
+        }
+
+        // the center stays, the corners move out.
+        assert_eq!(unwarp((0.5, 0.5), 1.0), (0.5, 0.5));
+        let corner = unwarp((0.0, 0.0), 1.0);
+        assert!(corner.0 > 0.0 && corner.1 > 0.0);
+    }
+}
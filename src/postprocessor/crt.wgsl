@@ -43,6 +43,8 @@ struct Uniforms {
     mask_strength: f32,
     use_srgb: i32,
     milliseconds: u32,
+    opacity: f32,
+    premultiplied: u32,
 }
 
 @group(3) @binding(0)
@@ -187,5 +189,11 @@ fn fs_main(@builtin(position) gl_Position: vec4<f32>) -> FragmentOutput {
     let crtColor = vec4(mix(col * vec3(uniforms.modulate_crt), vec3(0.0, 0.0, 0.0), vec3(distance)), 1.0);
     let clampedCrt = select(crtColor, vec4(vec3(0.0), 1.0), curved_uv.x < 0.0 || curved_uv.x > 1.0 || curved_uv.y < 0.0 || curved_uv.y > 1.0);
 
-    return FragmentOutput(pow(clampedCrt, vec4(factor)), acc);
+    let out = pow(clampedCrt, vec4(factor));
+
+    // window opacity. a premultiplied surface needs it in the color too.
+    let alpha = out.a * uniforms.opacity;
+    let rgb = select(out.rgb, out.rgb * alpha, uniforms.premultiplied != 0u);
+
+    return FragmentOutput(vec4(rgb, alpha), acc);
 }
\ No newline at end of file
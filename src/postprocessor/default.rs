@@ -4,13 +4,14 @@ use std::num::NonZeroU64;
 use wgpu::{
     AddressMode, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
     BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
-    BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoder, Device,
-    FilterMode, FragmentState, LoadOp, MipmapFilterMode, MultisampleState, Operations,
-    PipelineCompilationOptions, PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, Queue,
-    RenderBundle, RenderBundleDescriptor, RenderBundleEncoderDescriptor, RenderPassColorAttachment,
-    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType,
-    SamplerDescriptor, ShaderStages, StoreOp, SurfaceConfiguration, TextureSampleType, TextureView,
-    TextureViewDimension, VertexState, include_wgsl,
+    BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoder,
+    CompositeAlphaMode, Device, FilterMode, FragmentState, LoadOp, MipmapFilterMode,
+    MultisampleState, Operations, PipelineCompilationOptions, PipelineLayoutDescriptor,
+    PrimitiveState, PrimitiveTopology, Queue, RenderBundle, RenderBundleDescriptor,
+    RenderBundleEncoderDescriptor, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages,
+    StoreOp, SurfaceConfiguration, TextureSampleType, TextureView, TextureViewDimension,
+    VertexState, include_wgsl,
 };
 
 #[derive(Default)]
@@ -27,6 +28,7 @@ pub struct DefaultPostProcessor {
     bindings: BindGroupLayout,
     sampler: Sampler,
     pipeline: RenderPipeline,
+    opacity: f32,
 
     blitter: RenderBundle,
 }
@@ -38,6 +40,8 @@ struct Uniforms {
     margin_color: u32,
     preserve_aspect: u32,
     use_srgb: u32,
+    opacity: f32,
+    premultiplied: u32,
     _fill: u32,
 }
 
@@ -154,6 +158,7 @@ impl PostProcessorBuilder for DefaultPostProcessorBuilder {
             bindings: layout,
             sampler,
             pipeline,
+            opacity: 1.0,
             blitter,
         }
     }
@@ -262,6 +267,10 @@ impl PostProcessor for DefaultPostProcessor {
                 margin_color,
                 preserve_aspect: u32::from(preserve_aspect),
                 use_srgb: u32::from(surface_config.format.is_srgb()),
+                opacity: self.opacity,
+                premultiplied: u32::from(
+                    surface_config.alpha_mode == CompositeAlphaMode::PreMultiplied,
+                ),
                 _fill: 0,
             }));
         }
@@ -282,4 +291,8 @@ impl PostProcessor for DefaultPostProcessor {
 
         pass.execute_bundles(Some(&self.blitter));
     }
+
+    fn set_window_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
 }
@@ -4,6 +4,8 @@ use wgpu::{CommandEncoder, Device, Queue, SurfaceConfiguration, TextureView};
 
 pub mod default;
 pub mod crt;
+pub mod overlay;
+pub mod bloom;
 
 pub trait PostProcessorBuilder {
     /// Resulting postprocessor.
@@ -23,8 +25,25 @@ pub trait PostProcessorBuilder {
 /// A pipeline for post-processing rendered text.
 pub trait PostProcessor: Any {
     /// Map the screen-coordinates to cell-coordinates.
+    ///
+    /// The cells start at the top-left of the text layer, the inset is
+    /// handled by the backend. The backend calls this with a cell of
+    /// 1x1 px to map the pixels of the text layer.
     fn map_to_cell(&self, scr_x: i32, scr_y: i32, font_box: CellBox) -> (u16, u16);
 
+    /// Map a pixel position in the text layer to screen-coordinates.
+    /// This is the inverse of [`PostProcessor::map_to_cell`] with a
+    /// cell of 1x1 px.
+    ///
+    /// The position is in pixels, not cells. The text layer includes
+    /// the inset, the backend calls this with the corners of a cell
+    /// after adding the inset.
+    ///
+    /// The default assumes the text is not moved or scaled.
+    fn map_from_cell(&self, text_x: u32, text_y: u32) -> (i32, i32) {
+        (text_x as i32, text_y as i32)
+    }
+
     /// Called after the drawing dimensions have changed (e.g. the surface was
     /// resized).
     fn resize(
@@ -44,6 +63,11 @@ pub trait PostProcessor: Any {
     /// the swapchain is recreated.
     ///
     /// </div>
+    ///
+    /// Any external textures used by the post processor are not managed by
+    /// the backend. Keeping them alive and valid across a swapchain
+    /// recreation is the responsibility of the post processor and its user.
+    /// See [overlay::OverlayPostProcessor].
     fn process(
         &mut self,
         margin_color: u32,
@@ -62,4 +86,11 @@ pub trait PostProcessor: Any {
     fn needs_update(&self) -> bool {
         false
     }
+
+    /// Called before [`PostProcessor::process`] with the window opacity
+    /// set with
+    /// [`WgpuBackend::set_window_opacity`](crate::WgpuBackend::set_window_opacity).
+    /// Multiply the alpha of the output with it to support transparent
+    /// windows. The default ignores it.
+    fn set_window_opacity(&mut self, _opacity: f32) {}
 }
@@ -0,0 +1,406 @@
+use crate::CellBox;
+use crate::postprocessor::{PostProcessor, PostProcessorBuilder};
+use std::num::NonZeroU64;
+use wgpu::{
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferBindingType, BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites,
+    CommandEncoder, CompositeAlphaMode, Device, Extent3d, FilterMode, FragmentState, LoadOp,
+    MipmapFilterMode, MultisampleState, Operations, PipelineCompilationOptions,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+    SamplerDescriptor, ShaderStages, StoreOp, SurfaceConfiguration, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexState, include_wgsl,
+};
+
+/// Provides the background texture.
+///
+/// This is called when the post-processor is compiled and
+/// every time the surface is resized.
+pub type BackgroundSource = Box<dyn FnMut(&Device, &SurfaceConfiguration) -> TextureView>;
+
+/// Builder for the [OverlayPostProcessor].
+pub struct OverlayPostProcessorBuilder {
+    source: BackgroundSource,
+}
+
+impl Default for OverlayPostProcessorBuilder {
+    /// Uses a transparent background.
+    fn default() -> Self {
+        Self::with_source(|device, _| {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: Some("Overlay Background"),
+                    size: Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8Unorm,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor::default())
+        })
+    }
+}
+
+impl OverlayPostProcessorBuilder {
+    /// Use a fixed background texture.
+    pub fn new(background: TextureView) -> Self {
+        Self {
+            source: Box::new(move |_, _| background.clone()),
+        }
+    }
+
+    /// Use a closure that provides the background texture.
+    ///
+    /// It is called when the post-processor is compiled and every time
+    /// the surface is resized. This way the background can be
+    /// created with the device of the backend and follow the surface size.
+    pub fn with_source(
+        source: impl FnMut(&Device, &SurfaceConfiguration) -> TextureView + 'static,
+    ) -> Self {
+        Self {
+            source: Box::new(source),
+        }
+    }
+}
+
+/// Renders the terminal over an external texture.
+///
+/// The composited text is blended over the background texture
+/// using the alpha of the text layer. The margin at the right/bottom
+/// shows only the background.
+///
+/// The background is stretched to the full surface. It should use a
+/// filterable format, and it should use the same sRGB-ness as the
+/// surface format.
+///
+/// __Info__
+///
+/// Retaining the background view is your responsibility. It is not
+/// tied to the swapchain and survives a resize, but if you recreate
+/// your texture you must call [set_background](OverlayPostProcessor::set_background)
+/// or provide it via the source closure.
+pub struct OverlayPostProcessor {
+    source: BackgroundSource,
+    background: TextureView,
+
+    uniforms: Buffer,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    bg_sampler: Sampler,
+    pipeline: RenderPipeline,
+    opacity: f32,
+
+    bindings: BindGroup,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Uniforms {
+    screen_size: [f32; 2],
+    preserve_aspect: u32,
+    use_srgb: u32,
+    opacity: f32,
+    premultiplied: u32,
+    _fill: [u32; 2],
+}
+
+impl PostProcessorBuilder for OverlayPostProcessorBuilder {
+    type PostProcessor<'a> = OverlayPostProcessor;
+
+    fn compile(
+        mut self,
+        device: &Device,
+        text_view: &TextureView,
+        surface_config: &SurfaceConfiguration,
+    ) -> OverlayPostProcessor {
+        let uniforms = device.create_buffer(&BufferDescriptor {
+            label: Some("Overlay Uniforms"),
+            size: size_of::<Uniforms>() as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        });
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bg_sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Overlay Bindings Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(size_of::<Uniforms>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(include_wgsl!("overlay.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Overlay Layout"),
+            bind_group_layouts: &[&layout],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(ColorTargetState {
+                    format: surface_config.format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let background = (self.source)(device, surface_config);
+
+        let bindings = build_bindings(
+            device,
+            &layout,
+            text_view,
+            &sampler,
+            &uniforms,
+            &background,
+            &bg_sampler,
+        );
+
+        OverlayPostProcessor {
+            source: self.source,
+            background,
+            uniforms,
+            layout,
+            sampler,
+            bg_sampler,
+            pipeline,
+            opacity: 1.0,
+            bindings,
+        }
+    }
+}
+
+fn build_bindings(
+    device: &Device,
+    layout: &BindGroupLayout,
+    text_view: &TextureView,
+    sampler: &Sampler,
+    uniforms: &Buffer,
+    background: &TextureView,
+    bg_sampler: &Sampler,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Overlay Bindings"),
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(text_view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(sampler),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: uniforms.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(background),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: BindingResource::Sampler(bg_sampler),
+            },
+        ],
+    })
+}
+
+impl OverlayPostProcessor {
+    /// Current background.
+    pub fn background(&self) -> &TextureView {
+        &self.background
+    }
+
+    /// Replace the background texture.
+    ///
+    /// The text_view is the composited text, the same that is
+    /// given to [PostProcessor::resize].
+    pub fn set_background(
+        &mut self,
+        device: &Device,
+        text_view: &TextureView,
+        background: TextureView,
+    ) {
+        self.background = background;
+        self.bindings = build_bindings(
+            device,
+            &self.layout,
+            text_view,
+            &self.sampler,
+            &self.uniforms,
+            &self.background,
+            &self.bg_sampler,
+        );
+    }
+}
+
+impl PostProcessor for OverlayPostProcessor {
+    fn map_to_cell(&self, scr_x: i32, scr_y: i32, font_box: CellBox) -> (u16, u16) {
+        if scr_x < 0 || scr_y < 0 {
+            (0, 0)
+        } else {
+            (
+                (scr_x as u32 / font_box.width) as u16,
+                (scr_y as u32 / font_box.height) as u16,
+            )
+        }
+    }
+
+    fn resize(
+        &mut self,
+        device: &Device,
+        text_view: &TextureView,
+        surface_config: &SurfaceConfiguration,
+    ) {
+        let background = (self.source)(device, surface_config);
+        self.set_background(device, text_view, background);
+    }
+
+    fn process(
+        &mut self,
+        _margin_color: u32,
+        encoder: &mut CommandEncoder,
+        queue: &Queue,
+        _text_view: &TextureView,
+        surface_config: &SurfaceConfiguration,
+        surface_view: &TextureView,
+    ) {
+        {
+            #[cfg(feature = "scale_to_window")]
+            let preserve_aspect = false;
+            #[cfg(not(feature = "scale_to_window"))]
+            let preserve_aspect = true;
+
+            let mut uniforms = queue
+                .write_buffer_with(
+                    &self.uniforms,
+                    0,
+                    NonZeroU64::new(size_of::<Uniforms>() as u64).unwrap(),
+                )
+                .unwrap();
+            uniforms.copy_from_slice(bytemuck::bytes_of(&Uniforms {
+                screen_size: [surface_config.width as f32, surface_config.height as f32],
+                preserve_aspect: u32::from(preserve_aspect),
+                use_srgb: u32::from(surface_config.format.is_srgb()),
+                opacity: self.opacity,
+                premultiplied: u32::from(
+                    surface_config.alpha_mode == CompositeAlphaMode::PreMultiplied,
+                ),
+                _fill: [0; 2],
+            }));
+        }
+
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            ..Default::default()
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bindings, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn set_window_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+}
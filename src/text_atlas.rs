@@ -4,12 +4,71 @@ use ratatui_core::style::Modifier;
 use std::num::NonZeroUsize;
 use std::ops::Deref;
 
+/// Identifies a rasterized glyph in the glyph atlas.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
-pub(crate) struct Key {
+pub struct GlyphKey {
     pub(crate) style: Modifier,
     pub(crate) glyph: u32,
     pub(crate) width: u8,
     pub(crate) font: u64,
+    pub(crate) phase: u8,
+    pub(crate) condensed: bool,
+    pub(crate) enclosing: bool,
+}
+
+impl GlyphKey {
+    /// Style the glyph was rasterized with.
+    /// Only BOLD and ITALIC are relevant.
+    pub fn style(&self) -> Modifier {
+        self.style
+    }
+
+    /// Glyph id in the font.
+    pub fn glyph_id(&self) -> u32 {
+        self.glyph
+    }
+
+    /// Width in cells.
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    /// Id of the font. See [Font::id](crate::font::Font::id).
+    pub fn font_id(&self) -> u64 {
+        self.font
+    }
+
+    /// Sub-pixel phase the glyph was rasterized at.
+    /// See [RasterSettings::subpixel_phases](crate::font::RasterSettings::subpixel_phases).
+    pub fn phase(&self) -> u8 {
+        self.phase
+    }
+
+    /// Rasterized at half the width, for the caret notation of
+    /// control chars. See [ControlCharDisplay](crate::ControlCharDisplay).
+    pub fn condensed(&self) -> bool {
+        self.condensed
+    }
+
+    /// Rasterized as an enclosing mark, scaled over the whole cell
+    /// of the base char.
+    pub fn enclosing(&self) -> bool {
+        self.enclosing
+    }
+}
+
+/// Usage of the glyph atlas.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasStats {
+    /// Number of cached glyphs.
+    pub used_rects: usize,
+    /// Area in px² used by the cached glyphs.
+    pub used_area: u64,
+    /// Area in px² of the space taken from the atlas that holds no
+    /// glyph. These are free halves and slots of evicted glyphs.
+    pub wasted_area: u64,
+    /// Area in px² of the atlas texture.
+    pub total_area: u64,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -42,85 +101,120 @@ impl Deref for Entry {
     }
 }
 
+/// Glyph cache in a texture.
+///
+/// The texture is divided into slots two cells wide. A wide glyph takes
+/// a whole slot, a narrow glyph one half of it. The other half is kept
+/// for the next narrow glyph, and two free halves of the same slot
+/// are joined again for a wide glyph.
 #[derive(Debug)]
 pub(crate) struct Atlas {
-    lru: Lru<Key, CacheRect>,
+    lru: Lru<GlyphKey, CacheRect>,
     width: u32,
     height: u32,
 
-    entry_width: u32,
-    entry_height: u32,
+    cell_width: u32,
+    cell_height: u32,
 
-    next_entry: u32,
-    max_entries: u32,
+    next_slot: u32,
+    max_slots: u32,
+    // free slots and half-slots.
+    free: Vec<CacheRect>,
 }
 
 impl Atlas {
     pub(crate) fn new(font_box: CellBox, width: u32, height: u32) -> Self {
-        let entry_width = font_box.width * 2;
-        let entry_height = font_box.height;
-        let max_entries = ((width / entry_width) * (height / entry_height)).max(1);
-        // debug!("Atlas with WxH {entry_width}x{entry_height} can hold {max_entries}");
+        let max_slots = ((width / (font_box.width * 2)) * (height / font_box.height)).max(1);
 
         Atlas {
+            // at most two glyphs per slot.
             lru: Lru::new(
-                NonZeroUsize::new(max_entries as usize).expect("Max entries must be non-zero"),
+                NonZeroUsize::new(max_slots as usize * 2).expect("Max entries must be non-zero"),
             ),
             width,
             height,
-            entry_width,
-            entry_height,
-            next_entry: 0,
-            max_entries,
+            cell_width: font_box.width,
+            cell_height: font_box.height,
+            next_slot: 0,
+            max_slots,
+            free: Vec::new(),
         }
     }
 
     pub(crate) fn update_font_box(&mut self, cell_box: CellBox) {
-        if cell_box.width != self.entry_width || cell_box.height != self.entry_height {
-            self.clear();
-            self.entry_width = cell_box.width * 2;
-            self.entry_height = cell_box.height;
-            self.max_entries = (self.width / self.entry_width) * (self.height / self.entry_height);
+        if cell_box.width != self.cell_width || cell_box.height != self.cell_height {
+            *self = Atlas::new(cell_box, self.width, self.height);
         }
     }
 
-    fn clear(&mut self) {
+    pub(crate) fn clear(&mut self) {
         self.lru.clear();
-        self.next_entry = 0;
+        self.next_slot = 0;
+        self.free.clear();
+    }
+
+    pub(crate) fn stats(&self) -> AtlasStats {
+        let used_area = self
+            .lru
+            .iter()
+            .map(|(_, rect)| rect.width as u64 * rect.height as u64)
+            .sum();
+        let slot_area = self.cell_width as u64 * 2 * self.cell_height as u64;
+        AtlasStats {
+            used_rects: self.lru.len(),
+            used_area,
+            wasted_area: (self.next_slot as u64 * slot_area).saturating_sub(used_area),
+            total_area: self.width as u64 * self.height as u64,
+        }
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = GlyphKey> + '_ {
+        self.lru.iter().map(|(key, _)| *key)
     }
 
-    pub(crate) fn try_get(&mut self, key: &Key) -> Option<Entry> {
+    /// Removes the glyph and keeps its slot for reuse.
+    pub(crate) fn evict(&mut self, key: &GlyphKey) -> bool {
+        if let Some(rect) = self.lru.remove(key) {
+            self.free.push(rect);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn try_get(&mut self, key: &GlyphKey) -> Option<Entry> {
         self.lru.get(key).copied().map(Entry::Cached)
     }
 
-    pub(crate) fn update_colored(&mut self, key: &Key, colored: bool) {
+    pub(crate) fn update_colored(&mut self, key: &GlyphKey, colored: bool) {
         let c = self.lru.get_mut(key).expect("cached rect");
         c.color = colored;
     }
 
     #[allow(unused_variables)]
-    pub(crate) fn get(&mut self, key: &Key, width: u32, height: u32) -> Entry {
+    pub(crate) fn get(&mut self, key: &GlyphKey, width: u32, height: u32) -> Entry {
         #[cfg(debug_assertions)]
-        if self.entry_height != height {
+        if self.cell_height != height {
             panic!(
                 "Internal height not equal to provided height. Did you forget to call match_fonts?"
             );
         }
         #[cfg(debug_assertions)]
-        if self.entry_width % width != 0 {
+        if width != self.cell_width && width != self.cell_width * 2 {
             panic!(
-                "{:?} width = {}: Internal width not a multiple of provided width. Did you forget to call match_fonts?",
+                "{:?} width = {}: Internal width not one or two cells. Did you forget to call match_fonts?",
                 key, width
             );
         }
 
         self.try_get(key).unwrap_or_else(|| {
-            let rect = if self.next_entry == self.max_entries {
-                self.lru.pop().expect("Atlas has zero max entries!").1
-            } else {
-                let entry = self.next_entry;
-                self.next_entry += 1;
-                self.slot_to_rect(entry, width)
+            let rect = loop {
+                if let Some(rect) = self.alloc(width) {
+                    break rect;
+                }
+                // a wide glyph may need more than one narrow glyph evicted.
+                let (_, rect) = self.lru.pop().expect("Atlas has zero max entries!");
+                self.free.push(rect);
             };
 
             self.lru.insert(*key, rect);
@@ -128,15 +222,164 @@ impl Atlas {
         })
     }
 
-    fn slot_to_rect(&self, slot: u32, width: u32) -> CacheRect {
-        let x = slot % (self.width / self.entry_width) * self.entry_width;
-        let y = slot / (self.width / self.entry_width) * self.entry_height;
+    fn alloc(&mut self, width: u32) -> Option<CacheRect> {
+        if let Some(rect) = self.take_free(width) {
+            Some(rect)
+        } else if self.next_slot < self.max_slots {
+            let slot = self.next_slot;
+            self.next_slot += 1;
+            self.free.push(self.slot_to_rect(slot));
+            self.take_free(width)
+        } else {
+            None
+        }
+    }
+
+    fn take_free(&mut self, width: u32) -> Option<CacheRect> {
+        let slot_width = self.cell_width * 2;
+
+        let rect = if width <= self.cell_width {
+            if let Some(idx) = self.free.iter().position(|v| v.width <= self.cell_width) {
+                self.free.swap_remove(idx)
+            } else {
+                // split a free slot.
+                let idx = self.free.iter().position(|v| v.width > self.cell_width)?;
+                let rect = self.free.swap_remove(idx);
+                self.free.push(CacheRect {
+                    x: rect.x + self.cell_width,
+                    width: self.cell_width,
+                    ..rect
+                });
+                rect
+            }
+        } else if let Some(idx) = self.free.iter().position(|v| v.width > self.cell_width) {
+            self.free.swap_remove(idx)
+        } else {
+            // join both halves of a slot.
+            let (left, right) = self.free.iter().enumerate().find_map(|(left, v)| {
+                if v.x % slot_width != 0 {
+                    return None;
+                }
+                let right = self
+                    .free
+                    .iter()
+                    .position(|w| w.y == v.y && w.x == v.x + self.cell_width)?;
+                Some((left, right))
+            })?;
+            let rect = self.free[left];
+            // remove the higher index first.
+            self.free.swap_remove(left.max(right));
+            self.free.swap_remove(left.min(right));
+            rect
+        };
+
+        Some(CacheRect {
+            color: false,
+            width,
+            ..rect
+        })
+    }
+
+    fn slot_to_rect(&self, slot: u32) -> CacheRect {
+        let slot_width = self.cell_width * 2;
+        let x = slot % (self.width / slot_width) * slot_width;
+        let y = slot / (self.width / slot_width) * self.cell_height;
         CacheRect {
             color: false,
             x,
             y,
+            width: slot_width,
+            height: self.cell_height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(glyph: u32, width: u8) -> GlyphKey {
+        GlyphKey {
+            style: Modifier::empty(),
+            glyph,
             width,
-            height: self.entry_height,
+            font: 0,
+            phase: 0,
+            condensed: false,
+            enclosing: false,
         }
     }
+
+    fn atlas() -> Atlas {
+        // 5x2 slots, room for 20 narrow glyphs.
+        let cell_box = CellBox {
+            width: 10,
+            height: 20,
+            ascender: 15,
+        };
+        Atlas::new(cell_box, 100, 40)
+    }
+
+    fn overlaps(a: &CacheRect, b: &CacheRect) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    #[test]
+    fn mixed_widths() {
+        let mut atlas = atlas();
+
+        // 7 narrow and 6 wide glyphs leave one half-slot.
+        let mut rects = Vec::new();
+        for glyph in 0..13 {
+            let width = if glyph % 2 == 0 { 1 } else { 2 };
+            let entry = atlas.get(&key(glyph, width), width as u32 * 10, 20);
+            assert!(!entry.cached());
+            rects.push(*entry);
+        }
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.x + a.width <= 100 && a.y + a.height <= 40);
+            for b in &rects[i + 1..] {
+                assert!(!overlaps(a, b), "{a:?} {b:?}");
+            }
+        }
+
+        let stats = atlas.stats();
+        assert_eq!(stats.used_rects, 13);
+        assert_eq!(stats.used_area, stats.total_area - 200);
+        assert_eq!(stats.wasted_area, 200);
+
+        // which takes one more narrow glyph without evicting anything.
+        atlas.get(&key(13, 1), 10, 20);
+        let stats = atlas.stats();
+        assert_eq!(stats.used_rects, 14);
+        assert_eq!(stats.used_area, stats.total_area);
+        assert_eq!(stats.wasted_area, 0);
+        for glyph in 0..13 {
+            assert!(atlas.try_get(&key(glyph, 1 + glyph as u8 % 2)).is_some());
+        }
+    }
+
+    #[test]
+    fn join_halves() {
+        let mut atlas = atlas();
+        for glyph in 0..20 {
+            atlas.get(&key(glyph, 1), 10, 20);
+        }
+        assert_eq!(atlas.stats().wasted_area, 0);
+
+        // the two oldest share a slot and make room for a wide glyph.
+        let wide = *atlas.get(&key(100, 2), 20, 20);
+        assert_eq!(atlas.stats().used_rects, 19);
+        assert!(atlas.try_get(&key(0, 1)).is_none());
+        assert!(atlas.try_get(&key(1, 1)).is_none());
+        assert!(atlas.try_get(&key(2, 1)).is_some());
+        assert_eq!((wide.x % 20, wide.width), (0, 20));
+
+        // an evicted half is reused by the next narrow glyph.
+        assert!(atlas.evict(&key(5, 1)));
+        assert_eq!(atlas.stats().wasted_area, 200);
+        atlas.get(&key(101, 1), 10, 20);
+        assert_eq!(atlas.stats().wasted_area, 0);
+        assert_eq!(atlas.stats().used_rects, 19);
+    }
 }
@@ -46,3 +46,48 @@ pub(crate) fn intersect(
         Some((c0.0, c0.1, w as u32, h as u32))
     }
 }
+
+/// Convert premultiplied RGBA to straight alpha.
+pub(crate) fn unpremultiply(image: &[u8]) -> Vec<u8> {
+    let mut image = image.to_vec();
+    for px in image.chunks_exact_mut(4) {
+        let a = px[3] as u32;
+        if a == 0 {
+            px[..3].fill(0);
+        } else if a < 255 {
+            for c in &mut px[..3] {
+                *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+            }
+        }
+    }
+    image
+}
+
+/// Drive a future to completion on the current thread.
+///
+/// Parks the thread while the future is pending.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::Thread;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
@@ -2,10 +2,26 @@ use image::ImageBuffer;
 use image::Rgba;
 use image::load_from_memory;
 use image::{ExtendedColorType, GenericImageView};
-use rat_wgpu::Builder;
-use rat_wgpu::font::{Font, Fonts};
+use rat_wgpu::cursor::{Blinking, CursorStyle};
+use rat_wgpu::font::{
+    Font, FontData, Fonts, HorizontalAlign, NotdefStyle, RasterSettings, VerticalAlign,
+};
+use rat_wgpu::image::{ImageArg, ImageFit, ImageHandle};
+use rat_wgpu::postprocessor::PostProcessorBuilder;
+use rat_wgpu::postprocessor::bloom::BloomPostProcessorBuilder;
+use rat_wgpu::postprocessor::crt::CrtPostProcessorBuilder;
 use rat_wgpu::postprocessor::default::DefaultPostProcessorBuilder;
+use rat_wgpu::postprocessor::overlay::OverlayPostProcessorBuilder;
+use rat_wgpu::{
+    BaseDirection, Builder, ControlCharDisplay, Inset, LineScale, ModifierRemap, Rotation,
+    WgpuBackend,
+};
+use ratatui_core::backend::{Backend, ClearType};
+use ratatui_core::buffer::Cell;
+use ratatui_core::layout::{Position, Rect};
 use ratatui_core::style::Color;
+use ratatui_core::style::Modifier;
+use ratatui_core::style::Style;
 use ratatui_core::style::Stylize;
 use ratatui_core::terminal::Terminal;
 use ratatui_core::text::Line;
@@ -13,7 +29,53 @@ use ratatui_widgets::block::Block;
 use ratatui_widgets::paragraph::Paragraph;
 use serial_test::serial;
 use std::fs::create_dir_all;
-use wgpu::TextureFormat;
+use wgpu::{CompositeAlphaMode, TextureFormat};
+
+// Black on white, the common setup of the tests below.
+fn builder(fonts: Fonts<'static>, width: u32, height: u32) -> Builder<'static> {
+    Builder::default()
+        .with_fallback_fonts(fonts)
+        .with_width_and_height(width, height)
+        .with_bg_color(Color::White)
+        .with_fg_color(Color::Black)
+}
+
+fn headless(builder: Builder<'static>) -> Terminal<WgpuBackend<'static, 'static>> {
+    Terminal::new(futures_lite::future::block_on(builder.build_headless()).unwrap()).unwrap()
+}
+
+// Copies the last frame out of the headless buffer, without the
+// padding of the rows.
+fn readback(backend: &WgpuBackend, width: u32, height: u32) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let bytes_per_row = backend.headless_bytes_per_row();
+    let buffer = backend.map_headless_buffer().expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_fn(width, height, |x, y| {
+        let offset = (y * bytes_per_row + x * 4) as usize;
+        Rgba(buffer[offset..offset + 4].try_into().unwrap())
+    });
+    drop(buffer);
+    backend.unmap_headless_buffer();
+    image
+}
+
+// Saves the image as target/tmp/<name>.png and compares it with the golden.
+fn assert_golden(image: &ImageBuffer<Rgba<u8>, Vec<u8>>, name: &str, golden: &[u8]) {
+    _ = create_dir_all("target/tmp");
+    image::save_buffer(
+        format!("target/tmp/{name}.png"),
+        image.as_flat_samples().samples,
+        image.width(),
+        image.height(),
+        ExtendedColorType::Rgba8,
+    )
+    .expect("save_buffer");
+
+    let pixels = image.pixels().copied().collect::<Vec<_>>();
+    let golden = load_from_memory(golden).unwrap();
+    let golden_pixels = golden.pixels().map(|(_, _, px)| px).collect::<Vec<_>>();
+
+    assert_eq!(pixels, golden_pixels, "{name} differs from golden");
+}
 
 #[test]
 #[serial]
@@ -550,3 +612,4149 @@ fn srgb_conversion() {
     drop(buffer);
     terminal.backend().unmap_headless_buffer();
 }
+
+#[test]
+#[serial]
+fn scale_factor() {
+    let mut backend = futures_lite::future::block_on(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ))
+            .with_width_and_height(480, 96)
+            .build_headless(),
+    )
+    .unwrap();
+
+    backend.set_scale_factor(1.0);
+    let size = backend.size().unwrap();
+
+    backend.set_scale_factor(2.0);
+    let scaled = backend.size().unwrap();
+
+    assert_eq!(scaled.width, size.width / 2);
+    assert_eq!(scaled.height, size.height / 2);
+
+    // new fonts are scaled too.
+    backend.update_fonts(Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    ));
+    assert_eq!(backend.fonts().height_px(), 48);
+    assert_eq!(backend.size().unwrap(), scaled);
+}
+
+#[test]
+#[serial]
+fn font_size_pt() {
+    let mut backend = futures_lite::future::block_on(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                16,
+            ))
+            .with_font_size_pt(12.0, 144.0)
+            .with_width_and_height(480, 96)
+            .build_headless(),
+    )
+    .unwrap();
+
+    assert_eq!(backend.fonts().height_px(), 24);
+    assert_eq!(backend.font_size_pt(), Some((12.0, 144.0)));
+
+    // the scale factor applies on top.
+    backend.set_scale_factor(1.5);
+    assert_eq!(backend.fonts().height_px(), 36);
+    assert_eq!(backend.font_size_pt(), Some((12.0, 144.0)));
+    backend.set_scale_factor(1.0);
+
+    backend.set_font_size_pt(12.0, 96.0);
+    assert_eq!(backend.fonts().height_px(), 16);
+    assert_eq!(backend.font_size_pt(), Some((12.0, 96.0)));
+
+    backend.update_font_size(20);
+    assert_eq!(backend.font_size_pt(), None);
+
+    backend.set_font_size_pt(12.0, 96.0);
+    backend.update_fonts(Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        20,
+    ));
+    assert_eq!(backend.font_size_pt(), None);
+
+    // fonts set after the points bring their own size.
+    let backend = futures_lite::future::block_on(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_font_size_pt(12.0, 144.0)
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                16,
+            ))
+            .with_width_and_height(480, 96)
+            .build_headless(),
+    )
+    .unwrap();
+    assert_eq!(backend.fonts().height_px(), 16);
+    assert_eq!(backend.font_size_pt(), None);
+}
+
+#[test]
+#[serial]
+fn invert_colors() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("TEST"), f.area());
+        })
+        .unwrap();
+
+    let buffer = terminal
+        .backend()
+        .map_headless_buffer()
+        .expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    assert_eq!(*image.get_pixel(0, 71), Rgba([255, 255, 255, 255]));
+    drop(buffer);
+    terminal.backend().unmap_headless_buffer();
+
+    terminal.backend_mut().set_invert_colors(true);
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("TEST"), f.area());
+        })
+        .unwrap();
+
+    let buffer = terminal
+        .backend()
+        .map_headless_buffer()
+        .expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    assert_eq!(*image.get_pixel(0, 71), Rgba([0, 0, 0, 255]));
+    drop(buffer);
+    terminal.backend().unmap_headless_buffer();
+
+    terminal.backend_mut().set_invert_colors(false);
+    terminal.backend_mut().set_high_contrast(true);
+    terminal.backend_mut().set_bg_color(Color::DarkGray);
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("TEST"), f.area());
+        })
+        .unwrap();
+
+    let buffer = terminal
+        .backend()
+        .map_headless_buffer()
+        .expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    assert_eq!(*image.get_pixel(0, 71), Rgba([0, 0, 0, 255]));
+    drop(buffer);
+    terminal.backend().unmap_headless_buffer();
+}
+
+#[test]
+#[serial]
+fn overlay() {
+    // the background would usually be some game scene.
+    // here it's just an empty texture, which is zero-initialized.
+    let overlay = OverlayPostProcessorBuilder::with_source(|device, config| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Background"),
+                size: wgpu::Extent3d {
+                    width: config.width,
+                    height: config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    });
+
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::new_with_postprocessor(overlay)
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 80)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("TEST"), f.area());
+        })
+        .unwrap();
+
+    let buffer = terminal
+        .backend()
+        .map_headless_buffer()
+        .expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 80, &*buffer).unwrap();
+    // text area
+    assert_eq!(*image.get_pixel(0, 71), Rgba([255, 255, 255, 255]));
+    // margin shows the background
+    assert_eq!(*image.get_pixel(0, 79), Rgba([0, 0, 0, 0]));
+    drop(buffer);
+    terminal.backend().unmap_headless_buffer();
+}
+
+#[test]
+#[serial]
+fn redraw() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("TEST"), f.area());
+        })
+        .unwrap();
+
+    // nothing changed, nothing to render.
+    assert!(!terminal.backend().has_pending_changes());
+    terminal.backend_mut().redraw().unwrap();
+
+    let buffer = terminal
+        .backend()
+        .map_headless_buffer()
+        .expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    assert_eq!(*image.get_pixel(0, 71), Rgba([255, 255, 255, 255]));
+    drop(buffer);
+    terminal.backend().unmap_headless_buffer();
+
+    // pending changes are flushed.
+    let mut cell = Cell::new(" ");
+    cell.set_bg(Color::Black);
+    terminal
+        .backend_mut()
+        .draw([(0, 2, &cell)].into_iter())
+        .unwrap();
+    assert!(terminal.backend().has_pending_changes());
+    terminal.backend_mut().redraw().unwrap();
+    assert!(!terminal.backend().has_pending_changes());
+    assert!(terminal.backend().last_render_error().is_none());
+
+    let buffer = terminal
+        .backend()
+        .map_headless_buffer()
+        .expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    assert_eq!(*image.get_pixel(0, 71), Rgba([0, 0, 0, 255]));
+    drop(buffer);
+    terminal.backend().unmap_headless_buffer();
+
+    // a rebuilt surface is pending until the next flush.
+    terminal.backend_mut().update_font_size(20);
+    assert!(terminal.backend().has_pending_changes());
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("TEST"), f.area());
+        })
+        .unwrap();
+    assert!(!terminal.backend().has_pending_changes());
+}
+
+#[test]
+#[serial]
+fn fake_bold_strength() {
+    for (size, goldens) in [
+        (
+            16,
+            [
+                &include_bytes!("goldens/fake_bold_16_0.25.png")[..],
+                &include_bytes!("goldens/fake_bold_16_2.png")[..],
+            ],
+        ),
+        (
+            32,
+            [
+                &include_bytes!("goldens/fake_bold_32_0.25.png")[..],
+                &include_bytes!("goldens/fake_bold_32_2.png")[..],
+            ],
+        ),
+    ] {
+        let mut ink = Vec::new();
+        for (strength, golden) in [0.25, 2.0].into_iter().zip(goldens) {
+            let mut fonts = Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                size,
+            );
+            fonts.set_raster_settings(RasterSettings {
+                fake_bold_strength: strength,
+                ..fonts.raster_settings()
+            });
+
+            let mut terminal = headless(builder(fonts, 512, 64));
+            terminal
+                .draw(|f: &mut ratatui_core::terminal::Frame| {
+                    f.render_widget(Paragraph::new("BOLD".bold()), f.area());
+                })
+                .unwrap();
+
+            let image = readback(terminal.backend(), 512, 64);
+            assert_golden(&image, &format!("fake_bold_{size}_{strength}"), golden);
+
+            ink.push(image.pixels().filter(|px| px[0] < 128).count());
+        }
+
+        assert!(
+            ink[0] < ink[1],
+            "stronger fake bold must be heavier at size {size}"
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn resize_cells() {
+    let mut backend = futures_lite::future::block_on(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ))
+            .with_width_and_height(512, 72)
+            .build_headless(),
+    )
+    .unwrap();
+
+    let cell_box = backend.cell_box();
+    backend.resize_cells(17, 5);
+
+    let size = backend.size().unwrap();
+    assert_eq!((size.width, size.height), (17, 5));
+    let window_size = backend.window_size().unwrap();
+    assert_eq!(
+        (window_size.pixels.width, window_size.pixels.height),
+        (17 * cell_box.width as u16, 5 * cell_box.height as u16)
+    );
+}
+
+#[test]
+#[serial]
+fn margin_color() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 80)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .with_margin_color(Color::Rgb(255, 0, 0))
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("TEST"), f.area());
+        })
+        .unwrap();
+
+    let buffer = terminal
+        .backend()
+        .map_headless_buffer()
+        .expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 80, &*buffer).unwrap();
+    assert_eq!(*image.get_pixel(0, 71), Rgba([255, 255, 255, 255]));
+    assert_eq!(*image.get_pixel(0, 79), Rgba([255, 0, 0, 255]));
+    drop(buffer);
+    terminal.backend().unmap_headless_buffer();
+
+    terminal.backend_mut().set_margin_color(Color::Reset);
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("TEST"), f.area());
+        })
+        .unwrap();
+
+    let buffer = terminal
+        .backend()
+        .map_headless_buffer()
+        .expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 80, &*buffer).unwrap();
+    assert_eq!(*image.get_pixel(0, 79), Rgba([255, 255, 255, 255]));
+    drop(buffer);
+    terminal.backend().unmap_headless_buffer();
+}
+
+#[test]
+#[serial]
+fn base_direction_rtl() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .backend_mut()
+        .set_base_direction(BaseDirection::Rtl);
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("123"), f.area());
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let cols = terminal.backend().size().unwrap().width as u32;
+
+    let buffer = terminal
+        .backend()
+        .map_headless_buffer()
+        .expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+
+    let ink = |col: u32| {
+        (0..cell_box.height)
+            .flat_map(|y| (0..cell_box.width).map(move |x| (col * cell_box.width + x, y)))
+            .filter(|(x, y)| image.get_pixel(*x, *y)[0] < 128)
+            .count()
+    };
+    // digits are aligned to the right.
+    assert_eq!(ink(0), 0);
+    assert_ne!(ink(cols - 1), 0);
+
+    let remap = terminal.backend().visual_remap(0).expect("remap");
+    assert_eq!(remap.len(), cols as usize);
+    assert_eq!(
+        remap[0..3],
+        [cols as u16 - 3, cols as u16 - 2, cols as u16 - 1]
+    );
+    assert_eq!(terminal.backend().visual_remap(3), None);
+
+    drop(buffer);
+    terminal.backend().unmap_headless_buffer();
+}
+
+#[test]
+#[serial]
+fn visual_text() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(
+                Paragraph::new("abc \u{5d0}\u{5d1}\u{5d2} d\n\u{4e16}x"),
+                f.area(),
+            );
+        })
+        .unwrap();
+
+    let logical = terminal.backend().get_text();
+    let visual = terminal.backend().get_visual_text();
+    assert_eq!(logical.lines().count(), visual.lines().count());
+
+    let logical = logical.lines().collect::<Vec<_>>();
+    let visual = visual.lines().collect::<Vec<_>>();
+    assert!(logical[0].starts_with("abc \u{5d0}\u{5d1}\u{5d2} d"));
+    // the hebrew run reads right to left.
+    assert!(visual[0].starts_with("abc \u{5d2}\u{5d1}\u{5d0} d"));
+    // no gap after the wide char.
+    assert!(visual[1].starts_with("\u{4e16}x"));
+    assert_eq!(logical[2], visual[2]);
+
+    // cleared cells stay in their column before the next flush.
+    let backend = terminal.backend_mut();
+    backend.set_cursor_position(Position::new(4, 0)).unwrap();
+    backend.clear_region(ClearType::UntilNewLine).unwrap();
+    let visual = backend.get_visual_text();
+    assert!(visual.starts_with("abc "), "{visual:?}");
+}
+
+#[test]
+#[serial]
+fn italic_symbol() {
+    let mut terminal = headless(builder(
+        Fonts::new_vec(
+            vec![
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                FontData.fallback_symbol_font().expect("symbol font"),
+                FontData.fallback_emoji_font().expect("emoji font"),
+            ],
+            24,
+        ),
+        512,
+        72,
+    ));
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(
+                Paragraph::new(Line::from(vec![
+                    "★".italic(),
+                    " ".into(),
+                    "★".into(),
+                    " ".into(),
+                    "italic 😀 text".italic(),
+                ])),
+                f.area(),
+            );
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(
+        &image,
+        "italic_symbol",
+        include_bytes!("goldens/italic_symbol.png"),
+    );
+
+    let cell = |col: u32| {
+        (0..cell_box.height)
+            .flat_map(|y| (0..cell_box.width).map(move |x| (col * cell_box.width + x, y)))
+            .map(|(x, y)| *image.get_pixel(x, y))
+            .collect::<Vec<_>>()
+    };
+    // no fake italic for the symbol.
+    assert_eq!(cell(0), cell(2));
+}
+
+#[test]
+#[serial]
+fn get_text_region() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("ab中c"), f.area());
+        })
+        .unwrap();
+
+    let backend = terminal.backend();
+    assert_eq!(backend.get_text_trimmed(), "ab中c\n\n\n");
+    assert_eq!(backend.get_text_region(Rect::new(0, 0, 5, 1)), "ab中c\n");
+    assert_eq!(backend.get_text_region(Rect::new(0, 0, 3, 2)), "ab \n   \n");
+    assert_eq!(backend.get_text_region(Rect::new(3, 0, 2, 1)), " c\n");
+    assert_eq!(backend.get_text_region(Rect::new(0, 5, 2, 1)), "");
+}
+
+#[test]
+#[serial]
+fn get_styled_text() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(
+                Paragraph::new(Line::from(vec!["ab".bold(), "中d".red()])),
+                f.area(),
+            );
+        })
+        .unwrap();
+
+    let rows = terminal.backend().get_styled_text();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0][0].0, "ab");
+    assert!(rows[0][0].1.add_modifier.contains(Modifier::BOLD));
+    assert_eq!(rows[0][1].0, "中d");
+    assert_eq!(rows[0][1].1.fg, Some(Color::Red));
+    assert_eq!(rows[1].len(), 1);
+}
+
+#[test]
+#[serial]
+fn build_with_texture() {
+    let instance = wgpu::Instance::default();
+    let adapter = futures_lite::future::block_on(
+        instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+    )
+    .unwrap();
+    let (device, queue) =
+        futures_lite::future::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            required_limits: wgpu::Limits::downlevel_defaults(),
+            ..Default::default()
+        }))
+        .unwrap();
+
+    let target = |width: u32, height: u32| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    };
+    let view = target(512, 72);
+
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_device_and_queue(adapter, device.clone(), queue)
+                .build_with_texture(view, 512, 72, TextureFormat::Rgba8Unorm),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("texture"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend().last_render_error().is_none());
+    assert!(terminal.backend().get_text().starts_with("texture"));
+
+    // the texture belongs to the caller, resize can't change it.
+    terminal.backend_mut().resize(300, 100);
+    assert_eq!(terminal.backend().size().unwrap().height, 3);
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("texture"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend().last_render_error().is_none());
+
+    terminal.backend_mut().set_target(target(256, 48), 256, 48);
+    let size = terminal.backend().size().unwrap();
+    assert_eq!(size.height, 2);
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("texture"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend().last_render_error().is_none());
+}
+
+#[test]
+#[serial]
+fn line_scale_double_width() {
+    let mut terminal = headless(builder(
+        Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            24,
+        ),
+        512,
+        72,
+    ));
+
+    terminal
+        .backend_mut()
+        .set_line_scale(0, LineScale::DoubleWidth);
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("TT Title\nTT"), f.area());
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    assert_eq!(terminal.backend().line_scale(0), LineScale::DoubleWidth);
+    assert_eq!(terminal.backend().line_scale(1), LineScale::Normal);
+
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(
+        &image,
+        "line_scale_double_width",
+        include_bytes!("goldens/line_scale_double_width.png"),
+    );
+
+    let ink = |col: u32, row: u32| {
+        (0..cell_box.height)
+            .flat_map(|y| {
+                (0..cell_box.width)
+                    .map(move |x| (col * cell_box.width + x, row * cell_box.height + y))
+            })
+            .filter(|(x, y)| image.get_pixel(*x, *y)[0] < 128)
+            .count()
+    };
+    // the second T moves to columns 2..4.
+    assert_ne!(ink(2, 0) + ink(3, 0), 0);
+    assert_eq!(ink(2, 1) + ink(3, 1), 0);
+    // and is stretched.
+    assert!(ink(2, 0) + ink(3, 0) > ink(1, 1));
+
+    let w = cell_box.width as i32;
+    let h = cell_box.height as i32;
+    assert_eq!(terminal.backend().pos_to_cell((3 * w, 0)), (1, 0));
+    assert_eq!(terminal.backend().pos_to_cell((3 * w, h)), (3, 1));
+    assert_eq!(terminal.backend().cell_to_pos((1, 0)), (2 * w, 0));
+    assert_eq!(terminal.backend().cell_to_pos((3, 1)), (3 * w, h));
+}
+
+#[test]
+fn fonts_coverage() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+
+    // the fallback font always gets id 0.
+    let coverage = fonts.coverage("a\u{1F600}");
+    assert_eq!(coverage, vec![('a', Some(0)), ('\u{1F600}', None)]);
+}
+
+#[test]
+#[serial]
+fn vertical_align() {
+    let ink_center = |align: VerticalAlign, name: &str, golden: &[u8]| {
+        let mut fonts = Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            24,
+        );
+        fonts.set_raster_settings(RasterSettings {
+            vertical_align: align,
+            ..fonts.raster_settings()
+        });
+
+        let mut terminal = headless(builder(fonts, 512, 72));
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("_┼"), f.area());
+            })
+            .unwrap();
+
+        let cell_box = terminal.backend().cell_box();
+        let image = readback(terminal.backend(), 512, 72);
+        assert_golden(&image, name, golden);
+
+        let ink = (0..cell_box.height)
+            .flat_map(|y| (0..cell_box.width).map(move |x| (x, y)))
+            .filter(|(x, y)| image.get_pixel(*x, *y)[0] < 128)
+            .map(|(_, y)| y)
+            .collect::<Vec<_>>();
+        assert!(!ink.is_empty());
+        let center = ink.iter().sum::<u32>() as f32 / ink.len() as f32;
+
+        (center, cell_box.height as f32)
+    };
+
+    let (baseline, height) = ink_center(
+        VerticalAlign::Baseline,
+        "vertical_align_baseline",
+        include_bytes!("goldens/vertical_align_baseline.png"),
+    );
+    assert!(baseline > height * 0.7);
+    let (centered, height) = ink_center(
+        VerticalAlign::CenterInk,
+        "vertical_align_center_ink",
+        include_bytes!("goldens/vertical_align_center_ink.png"),
+    );
+    assert!((centered - height / 2.0).abs() <= 2.0);
+}
+
+#[test]
+#[serial]
+fn measure() {
+    let backend = futures_lite::future::block_on(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ))
+            .with_width_and_height(512, 72)
+            .build_headless(),
+    )
+    .unwrap();
+
+    assert_eq!(backend.measure("", Style::default()), (0, 0));
+    assert_eq!(backend.measure("ab中", Style::default()), (4, 1));
+    assert_eq!(backend.measure("e\u{301}", Style::default().bold()), (1, 1));
+    assert_eq!(
+        backend.measure("ab\n\u{1F600}xyz\n", Style::default()),
+        (5, 2)
+    );
+}
+
+#[test]
+#[serial]
+fn blink_divisor() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .with_slow_blink(1)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(terminal.backend().slow_blink_divisor(), 1);
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("X".slow_blink()), f.area());
+        })
+        .unwrap();
+
+    let cell_ink = |terminal: &Terminal<_>, col: u32| {
+        let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+        let cell_box = backend.cell_box();
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+        let ink = (0..cell_box.height)
+            .flat_map(|y| (col * cell_box.width..(col + 1) * cell_box.width).map(move |x| (x, y)))
+            .filter(|(x, y)| image.get_pixel(*x, *y)[0] < 128)
+            .count();
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        ink
+    };
+    let ink = |terminal: &Terminal<_>| cell_ink(terminal, 0);
+    assert_ne!(ink(&terminal), 0);
+
+    terminal.backend_mut().blink(Blinking::TEXT);
+    assert_eq!(ink(&terminal), 0);
+
+    // switching off the blink shows the text again.
+    terminal.backend_mut().set_slow_blink(0);
+    assert_eq!(terminal.backend().slow_blink_divisor(), 0);
+    terminal.backend_mut().flush().unwrap();
+    assert_ne!(ink(&terminal), 0);
+
+    terminal.backend_mut().blink(Blinking::TEXT);
+    assert_ne!(ink(&terminal), 0);
+
+    // same for a hidden cursor.
+    terminal.backend_mut().set_cursor_blink(1);
+    terminal
+        .backend_mut()
+        .set_cursor_position(Position::new(2, 0))
+        .unwrap();
+    terminal.backend_mut().show_cursor().unwrap();
+    terminal.backend_mut().flush().unwrap();
+    assert_ne!(cell_ink(&terminal, 2), 0);
+
+    terminal.backend_mut().blink(Blinking::CURSOR);
+    assert_eq!(cell_ink(&terminal, 2), 0);
+
+    terminal.backend_mut().set_cursor_blink(0);
+    terminal.backend_mut().flush().unwrap();
+    assert_ne!(cell_ink(&terminal, 2), 0);
+}
+
+#[test]
+#[serial]
+fn crossed_out_fallback() {
+    let mut terminal = headless(builder(
+        Fonts::new_vec(
+            vec![
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                FontData.fallback_emoji_font().expect("emoji font"),
+            ],
+            24,
+        ),
+        512,
+        72,
+    ));
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("a\u{1F600}b".crossed_out()), f.area());
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(
+        &image,
+        "crossed_out_fallback",
+        include_bytes!("goldens/crossed_out_fallback.png"),
+    );
+
+    // rows that are inked across the complete width of the cells.
+    let strike_rows = |col: u32, width: u32| {
+        (0..cell_box.height)
+            .filter(|y| {
+                (col * cell_box.width..(col + width) * cell_box.width)
+                    .all(|x| image.get_pixel(x, *y)[0] < 128)
+            })
+            .collect::<Vec<_>>()
+    };
+    let regular = strike_rows(0, 1);
+    assert!(!regular.is_empty());
+    assert_eq!(strike_rows(1, 2), regular);
+    assert_eq!(strike_rows(3, 1), regular);
+}
+
+#[test]
+#[serial]
+fn clear_images() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let handle = terminal
+        .backend_mut()
+        .add_image(&[255, 0, 0, 255].repeat(16), 4, 4);
+    let image_frame = terminal.backend().image_frame();
+    let render = |handle: &ImageHandle| {
+        let buffer = image_frame.buffer();
+        let mut buffer = buffer.lock().unwrap();
+        buffer.render(
+            handle,
+            Rect::new(0, 0, 2, 1),
+            ImageArg::new().fit(ImageFit::FitCenter),
+        );
+    };
+    let pixel = |terminal: &Terminal<_>| {
+        let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+        let pixel = *image.get_pixel(12, 12);
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        pixel
+    };
+
+    render(&handle);
+    terminal.draw(|_| {}).unwrap();
+    assert_eq!(pixel(&terminal), Rgba([255, 0, 0, 255]));
+
+    terminal.backend_mut().clear_images();
+    terminal.backend_mut().flush().unwrap();
+    assert_eq!(pixel(&terminal), Rgba([255, 255, 255, 255]));
+
+    // the stale handle is ignored.
+    render(&handle);
+    terminal.draw(|_| {}).unwrap();
+    assert_eq!(pixel(&terminal), Rgba([255, 255, 255, 255]));
+}
+
+#[test]
+#[serial]
+fn image_z_order() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let red = terminal
+        .backend_mut()
+        .add_image(&[255, 0, 0, 255].repeat(16), 4, 4);
+    let green = terminal
+        .backend_mut()
+        .add_image(&[0, 255, 0, 255].repeat(16), 4, 4);
+    let blue = terminal
+        .backend_mut()
+        .add_image(&[0, 0, 255, 255].repeat(16), 4, 4);
+
+    // background, sprite and cursor, added in reverse order.
+    // each one overlaps the previous one by a cell.
+    {
+        let buffer = terminal.backend().image_frame().buffer();
+        let mut buffer = buffer.lock().unwrap();
+        buffer.render(&blue, Rect::new(2, 0, 2, 1), ImageArg::new().z(2));
+        buffer.render(&green, Rect::new(1, 0, 2, 1), ImageArg::new().z(1));
+        buffer.render(&red, Rect::new(0, 0, 2, 1), ImageArg::new().z(-1));
+    }
+    terminal.draw(|_| {}).unwrap();
+
+    let backend = terminal.backend();
+    let cell_box = backend.cell_box();
+    let buffer = backend.map_headless_buffer().expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    let pixel = |col: u32| *image.get_pixel(col * cell_box.width + cell_box.width / 2, 12);
+    assert_eq!(pixel(0), Rgba([255, 0, 0, 255]));
+    assert_eq!(pixel(1), Rgba([0, 255, 0, 255]));
+    assert_eq!(pixel(2), Rgba([0, 0, 255, 255]));
+    assert_eq!(pixel(3), Rgba([0, 0, 255, 255]));
+    drop(buffer);
+    backend.unmap_headless_buffer();
+}
+
+#[test]
+#[serial]
+fn premultiplied_image() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    // the same half-transparent red.
+    let straight = terminal
+        .backend_mut()
+        .add_image(&[255, 0, 0, 128].repeat(16), 4, 4);
+    let premultiplied =
+        terminal
+            .backend_mut()
+            .add_image_premultiplied(&[128, 0, 0, 128].repeat(16), 4, 4);
+    {
+        let buffer = terminal.backend().image_frame().buffer();
+        let mut buffer = buffer.lock().unwrap();
+        buffer.render(&straight, Rect::new(0, 0, 2, 1), ImageArg::new());
+        buffer.render(&premultiplied, Rect::new(2, 0, 2, 1), ImageArg::new());
+    }
+    terminal.draw(|_| {}).unwrap();
+
+    let backend = terminal.backend();
+    let cell_box = backend.cell_box();
+    let buffer = backend.map_headless_buffer().expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    let straight = *image.get_pixel(cell_box.width, 12);
+    let premultiplied = *image.get_pixel(3 * cell_box.width, 12);
+    drop(buffer);
+    backend.unmap_headless_buffer();
+
+    // blended with the white background.
+    assert_eq!(straight[0], 255);
+    assert!(straight[1] < 250, "{straight:?}");
+    assert!(
+        straight.0[..3]
+            .iter()
+            .zip(&premultiplied.0[..3])
+            .all(|(a, b)| a.abs_diff(*b) <= 1),
+        "{straight:?} {premultiplied:?}"
+    );
+}
+
+#[test]
+#[serial]
+fn dropped_image_blink() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .with_slow_blink(1)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let handle = terminal
+        .backend_mut()
+        .add_image(&[255, 0, 0, 255].repeat(16), 4, 4);
+    let image_frame = terminal.backend().image_frame();
+    {
+        let buffer = image_frame.buffer();
+        let mut buffer = buffer.lock().unwrap();
+        buffer.render(
+            &handle,
+            Rect::new(0, 0, 2, 1),
+            ImageArg::new().fit(ImageFit::FitCenter),
+        );
+    }
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("X".slow_blink()), f.area());
+        })
+        .unwrap();
+
+    // the texture is released with the next flush, but the
+    // image is still placed over the blinking cell.
+    // red pixels in the blinking cell.
+    let red = |terminal: &Terminal<_>| {
+        let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+        let cell_box = backend.cell_box();
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+        let red = (0..cell_box.height)
+            .flat_map(|y| (0..cell_box.width).map(move |x| (x, y)))
+            .map(|(x, y)| image.get_pixel(x, y))
+            .filter(|p| p[0] > 200 && p[1] < 50 && p[2] < 50)
+            .count();
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        red
+    };
+    terminal.backend_mut().flush().unwrap();
+    assert_ne!(red(&terminal), 0);
+
+    drop(handle);
+    terminal.backend_mut().flush().unwrap();
+    terminal.backend_mut().blink(Blinking::TEXT);
+    // repainted without the image.
+    assert!(terminal.backend().last_render_error().is_none());
+    assert_eq!(red(&terminal), 0);
+}
+
+#[test]
+#[serial]
+fn cleared_before_draw() {
+    let terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::Blue)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+    let buffer = backend.map_headless_buffer().expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    assert!(image.pixels().all(|p| *p == *image.get_pixel(0, 0)));
+    assert!(image.get_pixel(0, 0)[2] > 128);
+    drop(buffer);
+    backend.unmap_headless_buffer();
+}
+
+#[test]
+#[serial]
+fn dim_over_image() {
+    let mut terminal = headless(builder(
+        Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            24,
+        ),
+        512,
+        72,
+    ));
+
+    let handle = terminal
+        .backend_mut()
+        .add_image(&[255, 0, 0, 255].repeat(16), 4, 4);
+    {
+        let image_frame = terminal.backend().image_frame();
+        let buffer = image_frame.buffer();
+        let mut buffer = buffer.lock().unwrap();
+        buffer.render(
+            &handle,
+            Rect::new(0, 0, 2, 1),
+            ImageArg::new().fit(ImageFit::Fill).below_text(),
+        );
+    }
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("█".dim()), f.area());
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(
+        &image,
+        "dim_over_image",
+        include_bytes!("goldens/dim_over_image.png"),
+    );
+
+    // the glyph is an opaque grey, the image doesn't shine through.
+    let pixel = *image.get_pixel(cell_box.width / 2, cell_box.height / 2);
+    assert_eq!(pixel[0], pixel[1]);
+    assert_eq!(pixel[1], pixel[2]);
+    assert!(pixel[0] > 64 && pixel[0] < 192);
+    // the image is still visible next to it.
+    let pixel = *image.get_pixel(cell_box.width + cell_box.width / 2, cell_box.height / 2);
+    assert_eq!(pixel, Rgba([255, 0, 0, 255]));
+}
+
+#[test]
+fn font_variation() {
+    let mut font =
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file");
+    let advance = font
+        .face()
+        .glyph_hor_advance(font.face().glyph_index('W').unwrap());
+
+    font.set_variation(*b"wght", 600.0);
+    font.set_variation(*b"wght", 700.0);
+    assert_eq!(font.variations().len(), 1);
+    assert_eq!(font.variations()[0].value, 700.0);
+
+    // the static font has no wght axis, nothing changes.
+    assert_eq!(
+        font.face()
+            .glyph_hor_advance(font.face().glyph_index('W').unwrap()),
+        advance
+    );
+}
+
+#[test]
+#[serial]
+fn font_variation_weight() {
+    let render = |weight: Option<f32>| {
+        let mut font = Font::new(include_bytes!("fonts/RatTest.ttf")).expect("Invalid font file");
+        if let Some(weight) = weight {
+            font.set_variation(*b"wght", weight);
+        }
+
+        let mut terminal = Terminal::new(
+            futures_lite::future::block_on(
+                Builder::<DefaultPostProcessorBuilder>::default()
+                    .with_fallback_fonts(Fonts::new(font, 24))
+                    .with_width_and_height(512, 72)
+                    .with_bg_color(Color::White)
+                    .with_fg_color(Color::Black)
+                    .build_headless(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("I I I"), f.area());
+            })
+            .unwrap();
+
+        let backend = terminal.backend();
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+        let ink = image.pixels().filter(|p| p[0] < 128).count();
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        ink
+    };
+
+    let regular = render(None);
+    let bold = render(Some(900.0));
+    assert!(regular > 0);
+    // the stem of 'I' is three times as wide at weight 900.
+    assert!(bold > regular * 2, "{bold} > 2 * {regular}");
+    assert_eq!(render(Some(400.0)), regular);
+}
+
+#[test]
+#[serial]
+fn capture_text_layer() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("█"), f.area());
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let (width, height, pixels) = terminal.backend().capture_text_layer().unwrap();
+    assert_eq!(pixels.len(), (width * height * 4) as usize);
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, pixels).unwrap();
+    assert_eq!(
+        *image.get_pixel(cell_box.width / 2, cell_box.height / 2),
+        Rgba([0, 0, 0, 255])
+    );
+    assert_eq!(
+        *image.get_pixel(cell_box.width + cell_box.width / 2, cell_box.height / 2),
+        Rgba([255, 255, 255, 255])
+    );
+}
+
+#[test]
+#[serial]
+fn image_filter() {
+    let grey_levels = |filter: rat_wgpu::wgpu::FilterMode| {
+        let mut terminal = Terminal::new(
+            futures_lite::future::block_on(
+                Builder::<DefaultPostProcessorBuilder>::default()
+                    .with_fallback_fonts(Fonts::new(
+                        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                            .expect("Invalid font file"),
+                        24,
+                    ))
+                    .with_width_and_height(512, 72)
+                    .with_image_filter(filter)
+                    .build_headless(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        // 2x2 checkerboard
+        let checker = [
+            [0, 0, 0, 255],
+            [255, 255, 255, 255],
+            [255, 255, 255, 255],
+            [0, 0, 0, 255],
+        ]
+        .concat();
+        let handle = terminal.backend_mut().add_image(&checker, 2, 2);
+        {
+            let image_frame = terminal.backend().image_frame();
+            let buffer = image_frame.buffer();
+            let mut buffer = buffer.lock().unwrap();
+            buffer.render(&handle, Rect::new(0, 0, 8, 2), ImageArg::new());
+        }
+        terminal.draw(|_| {}).unwrap();
+
+        let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+        let cell_box = backend.cell_box();
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+        let mut levels = (0..cell_box.height * 2)
+            .flat_map(|y| (0..cell_box.width * 8).map(move |x| (x, y)))
+            .map(|(x, y)| image.get_pixel(x, y)[0])
+            .collect::<Vec<_>>();
+        levels.sort();
+        levels.dedup();
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        levels.len()
+    };
+
+    assert_eq!(grey_levels(rat_wgpu::wgpu::FilterMode::Nearest), 2);
+    assert!(grey_levels(rat_wgpu::wgpu::FilterMode::Linear) > 2);
+}
+
+#[test]
+#[serial]
+fn bloom() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::new_with_postprocessor(BloomPostProcessorBuilder::default())
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::Black)
+                .with_fg_color(Color::White)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new(" █"), f.area());
+        })
+        .unwrap();
+
+    let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+    let cell_box = backend.cell_box();
+    let buffer = backend.map_headless_buffer().expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    // the glow spills over into the neighbouring cells.
+    assert!(image.get_pixel(cell_box.width - 2, cell_box.height / 2)[0] > 0);
+    assert!(image.get_pixel(cell_box.width * 2 + 1, cell_box.height / 2)[0] > 0);
+    // but not far.
+    assert_eq!(
+        image.get_pixel(cell_box.width * 5, cell_box.height / 2)[0],
+        0
+    );
+    drop(buffer);
+    backend.unmap_headless_buffer();
+
+    assert_eq!(backend.pos_to_cell((cell_box.width as i32 + 1, 1)), (1, 0));
+}
+
+#[test]
+#[serial]
+fn cell_to_pos() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_inset(Inset {
+                    top: 3,
+                    right: 0,
+                    bottom: 0,
+                    left: 5,
+                })
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let w = cell_box.width as i32;
+    let h = cell_box.height as i32;
+    assert_eq!(terminal.backend().cell_to_pos((0, 0)), (5, 3));
+    assert_eq!(terminal.backend().cell_to_pos((2, 1)), (5 + 2 * w, 3 + h));
+    assert_eq!(terminal.backend().pos_to_cell((5 + 2 * w, 3 + h)), (2, 1));
+
+    // upside down the top-left corner on the surface is the
+    // bottom-right corner of the cell.
+    terminal.backend_mut().set_rotation(Rotation::Cw180);
+    assert_eq!(
+        terminal.backend().cell_to_pos((0, 0)),
+        (512 - 5 - w, 72 - 3 - h)
+    );
+    assert_eq!(
+        terminal.backend().pos_to_cell((512 - 5 - w, 72 - 3 - h)),
+        (0, 0)
+    );
+    assert_eq!(
+        terminal.backend().cell_to_pos((2, 1)),
+        (512 - 5 - 3 * w, 72 - 3 - 2 * h)
+    );
+
+    // the top of the terminal is at the right side, the cell is
+    // rotated around its top-right corner.
+    terminal.backend_mut().set_rotation(Rotation::Cw90);
+    assert_eq!(terminal.backend().cell_to_pos((0, 0)), (512 - 3 - h, 5));
+    assert_eq!(terminal.backend().pos_to_cell((512 - 3 - h, 5)), (0, 0));
+    assert_eq!(
+        terminal.backend().cell_to_pos((2, 1)),
+        (512 - 3 - 2 * h, 5 + 2 * w)
+    );
+    assert_eq!(
+        terminal.backend().pos_to_cell((512 - 3 - 2 * h, 5 + 2 * w)),
+        (2, 1)
+    );
+
+    // the crt curvature moves the cells, the inset is curved too.
+    for inset in [Inset::default(), Inset::uniform(40)] {
+        let mut terminal = Terminal::new(
+            futures_lite::future::block_on(
+                Builder::new_with_postprocessor(CrtPostProcessorBuilder::default())
+                    .with_fallback_fonts(Fonts::new(
+                        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                            .expect("Invalid font file"),
+                        24,
+                    ))
+                    .with_width_and_height(512, 144)
+                    .with_bg_color(Color::White)
+                    .with_fg_color(Color::Black)
+                    .with_inset(inset)
+                    .build_headless(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let cells = [(0, 0), (3, 1), (10, 0), (20, 1)];
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                for (x, y) in cells {
+                    f.buffer_mut()[(x, y)].set_symbol("█");
+                }
+            })
+            .unwrap();
+
+        let backend = terminal.backend();
+        let (x, y) = backend.cell_to_pos((0, 0));
+        assert!(x > inset.left as i32 && y > inset.top as i32);
+
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 144, &*buffer).unwrap();
+        for cell in cells {
+            let (x, y) = backend.cell_to_pos(cell);
+            assert_eq!(backend.pos_to_cell((x + 2, y + 2)), cell);
+            // the block is drawn there.
+            let ink = image.get_pixel(x as u32 + 2, y as u32 + 2);
+            assert!(ink[0] < 128, "{cell:?} {ink:?}");
+        }
+        drop(buffer);
+        backend.unmap_headless_buffer();
+    }
+}
+
+#[test]
+#[serial]
+fn hdr_format() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::Black)
+                .with_fg_color(Color::White)
+                .with_hdr_text_layer(true)
+                .build_headless_with_format(TextureFormat::Rgba16Float),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("█"), f.area());
+        })
+        .unwrap();
+
+    // f16 per channel, 1.0 is 0x3c00.
+    let red = |pixels: &[u8], width: u32, x: u32, y: u32| {
+        let offset = ((y * width + x) * 8) as usize;
+        u16::from_le_bytes([pixels[offset], pixels[offset + 1]])
+    };
+
+    let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+    let cell_box = backend.cell_box();
+    let buffer = backend.map_headless_buffer().expect("headless buffer");
+    assert_eq!(buffer.len(), 512 * 72 * 8);
+    assert_eq!(
+        red(&buffer, 512, cell_box.width / 2, cell_box.height / 2),
+        0x3c00
+    );
+    assert_eq!(
+        red(&buffer, 512, cell_box.width * 3, cell_box.height / 2),
+        0
+    );
+    drop(buffer);
+    backend.unmap_headless_buffer();
+
+    let (width, height, pixels) = backend.capture_text_layer().unwrap();
+    assert_eq!(pixels.len(), (width * height * 8) as usize);
+    assert_eq!(
+        red(&pixels, width, cell_box.width / 2, cell_box.height / 2),
+        0x3c00
+    );
+}
+
+#[test]
+#[serial]
+fn aa_min_height() {
+    let grey_levels = |size_px: u32, golden: &[u8]| {
+        let mut fonts = Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            size_px,
+        );
+        fonts.set_raster_settings(RasterSettings {
+            aa_min_height_px: 16,
+            ..fonts.raster_settings()
+        });
+
+        let mut terminal = headless(builder(fonts, 512, 72));
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("aSgW@"), f.area());
+            })
+            .unwrap();
+
+        let cell_box = terminal.backend().cell_box();
+        let image = readback(terminal.backend(), 512, 72);
+        assert_golden(&image, &format!("aa_min_height_{size_px}"), golden);
+
+        let mut levels = (0..cell_box.height)
+            .flat_map(|y| (0..cell_box.width * 5).map(move |x| (x, y)))
+            .map(|(x, y)| image.get_pixel(x, y)[0])
+            .collect::<Vec<_>>();
+        levels.sort();
+        levels.dedup();
+        levels.len()
+    };
+
+    assert_eq!(
+        grey_levels(10, include_bytes!("goldens/aa_min_height_10.png")),
+        2
+    );
+    assert!(grey_levels(24, include_bytes!("goldens/aa_min_height_24.png")) > 2);
+}
+
+#[test]
+#[serial]
+fn prewarm() {
+    let render = |prewarm: bool| {
+        let mut terminal = Terminal::new(
+            futures_lite::future::block_on(
+                Builder::<DefaultPostProcessorBuilder>::default()
+                    .with_fallback_fonts(Fonts::new(
+                        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                            .expect("Invalid font file"),
+                        24,
+                    ))
+                    .with_width_and_height(512, 72)
+                    .with_bg_color(Color::White)
+                    .with_fg_color(Color::Black)
+                    .build_headless(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        if prewarm {
+            terminal.backend_mut().prewarm(
+                "Hello, world!\nabc",
+                &[Modifier::empty(), Modifier::BOLD, Modifier::ITALIC],
+            );
+        }
+
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(
+                    Paragraph::new(vec![
+                        Line::from("Hello, world!"),
+                        Line::from("Hello, world!".bold()),
+                        Line::from("abc".italic()),
+                    ]),
+                    f.area(),
+                );
+            })
+            .unwrap();
+
+        let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let pixels = buffer.to_vec();
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        pixels
+    };
+
+    // the glyphs from the atlas are the same as rendered on demand.
+    assert!(render(true) == render(false));
+}
+
+#[test]
+#[serial]
+fn atlas_stats() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let empty = terminal.backend().atlas_stats();
+    assert!(empty.total_area > 0);
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("abc"), f.area());
+        })
+        .unwrap();
+
+    let stats = terminal.backend().atlas_stats();
+    assert!(stats.used_rects >= empty.used_rects + 3);
+    assert!(stats.used_area > empty.used_area);
+    assert_eq!(stats.total_area, empty.total_area);
+
+    let key = terminal.backend().cached_glyphs().next().expect("glyph");
+    assert!(terminal.backend_mut().evict_glyph(&key));
+    assert!(!terminal.backend_mut().evict_glyph(&key));
+    assert_eq!(
+        terminal.backend().atlas_stats().used_rects,
+        stats.used_rects - 1
+    );
+}
+
+#[test]
+#[serial]
+fn underline_overline() {
+    let font =
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file");
+    let declared = font.face().underline_metrics().expect("underline metrics");
+    let expected =
+        ((declared.thickness as f32 * 24.0 / font.face().height() as f32).round() as u32).max(1);
+
+    let mut terminal = headless(builder(Fonts::new(font, 24), 512, 72));
+    // there is no overline modifier, use italic instead.
+    terminal.backend_mut().set_modifier_remap(ModifierRemap {
+        overline: Modifier::ITALIC,
+        ..Default::default()
+    });
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(
+                Paragraph::new(Line::from(vec![" ".underlined(), " ".italic()])),
+                f.area(),
+            );
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(
+        &image,
+        "underline_overline",
+        include_bytes!("goldens/underline_overline.png"),
+    );
+    let ink_rows = |x: u32| {
+        (0..cell_box.height)
+            .filter(|y| image.get_pixel(x, *y)[0] < 128)
+            .collect::<Vec<_>>()
+    };
+
+    // the underline has the thickness declared by the font.
+    let underline = ink_rows(cell_box.width / 2);
+    assert_eq!(underline.len() as u32, expected);
+    assert!(underline[0] >= cell_box.ascender);
+
+    // the overline sits at the top of the cell.
+    let overline = ink_rows(cell_box.width + cell_box.width / 2);
+    assert_eq!(overline, (0..expected).collect::<Vec<_>>());
+}
+
+#[test]
+#[serial]
+fn modifier_remap() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .with_modifier_remap(ModifierRemap {
+                    overline: Modifier::ITALIC,
+                    double_underline: Modifier::BOLD,
+                })
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        terminal.backend().modifier_remap().overline,
+        Modifier::ITALIC
+    );
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(
+                Paragraph::new(Line::from(vec![" ".italic(), " ".underlined(), " ".bold()])),
+                f.area(),
+            );
+        })
+        .unwrap();
+
+    let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+    let cell_box = backend.cell_box();
+    let buffer = backend.map_headless_buffer().expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+    let ink_rows = |x: u32| {
+        (0..cell_box.height)
+            .filter(|y| image.get_pixel(x, *y)[0] < 128)
+            .collect::<Vec<_>>()
+    };
+
+    // the remapped modifier gets a line at the top of the cell.
+    let overline = ink_rows(cell_box.width / 2);
+    assert!(!overline.is_empty());
+    assert_eq!(overline[0], 0);
+    assert!(overline.iter().all(|y| *y < cell_box.ascender));
+
+    // a double underline is the underline and a second line.
+    let underline = ink_rows(cell_box.width + cell_box.width / 2);
+    let double_underline = ink_rows(2 * cell_box.width + cell_box.width / 2);
+    assert!(!underline.is_empty());
+    assert_eq!(double_underline.len(), 2 * underline.len());
+    assert!(underline.iter().all(|y| double_underline.contains(y)));
+
+    drop(buffer);
+    backend.unmap_headless_buffer();
+}
+
+#[test]
+#[serial]
+fn cell_width_px() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_cell_width_px(16)
+                .with_width_and_height(512, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(terminal.backend().cell_box().width, 16);
+    assert_eq!(terminal.backend().size().unwrap().width, 512 / 16);
+
+    terminal.backend_mut().set_cell_width_px(Some(32));
+    assert_eq!(terminal.backend().size().unwrap().width, 512 / 32);
+
+    // scaled like the font-size.
+    terminal.backend_mut().set_scale_factor(2.0);
+    assert_eq!(terminal.backend().cell_box().width, 64);
+    assert_eq!(terminal.backend().size().unwrap().width, 512 / 64);
+    terminal.backend_mut().set_scale_factor(1.0);
+    assert_eq!(terminal.backend().cell_box().width, 32);
+
+    // back to the advance of the font.
+    terminal.backend_mut().set_cell_width_px(None);
+    let width = terminal.backend().cell_box().width;
+    assert_ne!(width, 32);
+    assert_eq!(
+        terminal.backend().size().unwrap().width,
+        (512 / width) as u16
+    );
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("Hello, world!"), f.area());
+        })
+        .unwrap();
+}
+
+#[test]
+#[serial]
+fn selection() {
+    let mut terminal = headless(builder(
+        Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            24,
+        ),
+        512,
+        72,
+    ));
+
+    terminal.backend_mut().set_selection(
+        &[(Position::new(2, 0), Position::new(4, 0))],
+        Color::Rgb(0, 0, 255),
+    );
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("abcdefgh\nabcdefgh"), f.area());
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(&image, "selection", include_bytes!("goldens/selection.png"));
+
+    // top left corner of a cell has no ink.
+    let bg =
+        |x: u16, y: u16| *image.get_pixel(x as u32 * cell_box.width, y as u32 * cell_box.height);
+
+    // blended halfway between white and blue.
+    let selected = |px: Rgba<u8>| px[0] == px[1] && px[0] > 64 && px[0] < 192 && px[2] == 255;
+    let white = Rgba([255, 255, 255, 255]);
+    assert_eq!(bg(1, 0), white);
+    assert!(selected(bg(2, 0)));
+    assert!(selected(bg(3, 0)));
+    assert!(selected(bg(4, 0)));
+    assert_eq!(bg(5, 0), white);
+    assert_eq!(bg(3, 1), white);
+
+    // an empty selection removes the highlight.
+    terminal
+        .backend_mut()
+        .set_selection(&[], Color::Rgb(0, 0, 255));
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("abcdefgh\nabcdefgh"), f.area());
+        })
+        .unwrap();
+
+    let image = readback(terminal.backend(), 512, 72);
+    assert_eq!(*image.get_pixel(3 * cell_box.width, 0), white);
+}
+
+#[test]
+#[serial]
+fn adapter_info() {
+    let backend = futures_lite::future::block_on(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ))
+            .with_width_and_height(512, 72)
+            .build_headless(),
+    )
+    .unwrap();
+
+    let info = backend.adapter_info();
+    assert_ne!(info.backend, rat_wgpu::wgpu::Backend::Noop);
+}
+
+#[test]
+#[serial]
+fn device_queue() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    // own work before and after the frame of the terminal.
+    let backend = terminal.backend();
+    let buffer = backend.device().create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Interop"),
+        size: 16,
+        usage: wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    backend.queue().write_buffer(&buffer, 0, &[1; 16]);
+    backend.queue().submit([]);
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("Hello"), f.area());
+        })
+        .unwrap();
+
+    let backend = terminal.backend();
+    let encoder = backend
+        .device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    backend.queue().submit([encoder.finish()]);
+    backend
+        .device()
+        .poll(wgpu::PollType::Wait {
+            submission_index: None,
+            timeout: None,
+        })
+        .unwrap();
+}
+
+#[test]
+#[serial]
+fn enumerate_adapters() {
+    let adapters = Builder::<DefaultPostProcessorBuilder>::default()
+        .with_backends(rat_wgpu::wgpu::Backends::all())
+        .enumerate_adapters_blocking();
+    assert!(!adapters.is_empty());
+
+    for info in &adapters {
+        let backend = futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_backends(rat_wgpu::wgpu::Backends::all())
+                .with_adapter_info(info.clone())
+                .with_width_and_height(512, 72)
+                .build_headless(),
+        );
+        // adapters below the downlevel limits can't be used.
+        if let Ok(backend) = backend {
+            assert_eq!(backend.adapter_info(), info);
+        }
+    }
+
+    let mut missing = adapters[0].clone();
+    missing.name = "no such adapter".into();
+    let result = futures_lite::future::block_on(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ))
+            .with_adapter_info(missing)
+            .build_headless(),
+    );
+    assert!(matches!(result, Err(rat_wgpu::Error::AdapterNotFound(_))));
+}
+
+#[test]
+#[serial]
+fn with_adapter() {
+    let instance = wgpu::Instance::default();
+    let adapter = futures_lite::future::block_on(
+        instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+    )
+    .unwrap();
+    let info = adapter.get_info();
+
+    let backend = futures_lite::future::block_on(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ))
+            .with_instance(instance)
+            .with_adapter(adapter)
+            .with_width_and_height(512, 72)
+            .build_headless(),
+    )
+    .unwrap();
+
+    assert_eq!(backend.adapter_info(), &info);
+}
+
+#[test]
+#[serial]
+fn power_preference() {
+    let backend = futures_lite::future::block_on(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ))
+            .with_power_preference(rat_wgpu::wgpu::PowerPreference::LowPower)
+            .with_width_and_height(512, 72)
+            .build_headless(),
+    )
+    .unwrap();
+
+    assert_ne!(
+        backend.adapter_info().backend,
+        rat_wgpu::wgpu::Backend::Noop
+    );
+}
+
+#[test]
+#[serial]
+fn bidi_disabled() {
+    let render = |bidi: bool| {
+        let mut terminal = Terminal::new(
+            futures_lite::future::block_on(
+                Builder::<DefaultPostProcessorBuilder>::default()
+                    .with_fallback_fonts(Fonts::new(
+                        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                            .expect("Invalid font file"),
+                        24,
+                    ))
+                    .with_width_and_height(512, 72)
+                    .with_bg_color(Color::White)
+                    .with_fg_color(Color::Black)
+                    .with_bidi(bidi)
+                    .build_headless(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(terminal.backend().bidi_enabled(), bidi);
+
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(
+                    Paragraph::new(vec![
+                        Line::from("fn main() { println!(\"Hello\"); }"),
+                        Line::from(""),
+                        Line::from("  -> 42 == 6 * 7".bold()),
+                    ]),
+                    f.area(),
+                );
+            })
+            .unwrap();
+
+        let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let pixels = buffer.to_vec();
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        pixels
+    };
+
+    // ltr text is the same with or without bidi.
+    assert!(render(true) == render(false));
+}
+
+#[test]
+#[serial]
+fn shaping_cache() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    12,
+                ))
+                .with_width_and_height(1024, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let text = "lorem ipsum dolor sit amet consectetur adipiscing elit sed do eiusmod tempor";
+    let words = text.split(' ').count() as u64;
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new(text), f.area());
+        })
+        .unwrap();
+    let initial = terminal.backend().shaping_stats();
+    assert!(initial.shaped >= words);
+
+    // change one cell in the middle of the row.
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new(text.replace("amet", "amen")), f.area());
+        })
+        .unwrap();
+    let one_cell = terminal.backend().shaping_stats();
+    assert_eq!(one_cell.shaped - initial.shaped, 1);
+
+    // a full repaint without changes only uses the cache.
+    terminal.backend_mut().set_high_contrast(false);
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new(text.replace("amet", "amen")), f.area());
+        })
+        .unwrap();
+    let repaint = terminal.backend().shaping_stats();
+    assert_eq!(repaint.shaped, one_cell.shaped);
+    assert!(repaint.cached - one_cell.cached >= words);
+}
+
+#[test]
+#[serial]
+fn shaping_segments() {
+    let render = |text: &str| {
+        let mut terminal = Terminal::new(
+            futures_lite::future::block_on(
+                Builder::<DefaultPostProcessorBuilder>::default()
+                    .with_fallback_fonts(Fonts::new(
+                        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                            .expect("Invalid font file"),
+                        24,
+                    ))
+                    .with_width_and_height(512, 72)
+                    .with_bg_color(Color::White)
+                    .with_fg_color(Color::Black)
+                    .build_headless(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new(text), f.area());
+            })
+            .unwrap();
+
+        let backend = terminal.backend();
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let pixels = buffer.to_vec();
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        (pixels, backend.shaping_stats().shaped)
+    };
+
+    // a no-break space doesn't end a segment, the row is shaped
+    // in one piece.
+    let text = "a -> b != c |> d == e";
+    let (split, split_runs) = render(text);
+    let (whole, whole_runs) = render(&text.replace(' ', "\u{a0}"));
+    assert!(split_runs > whole_runs, "{split_runs} > {whole_runs}");
+    assert!(split == whole);
+}
+
+#[test]
+#[serial]
+fn cursor_wide_char() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("a世b"), f.area());
+        })
+        .unwrap();
+
+    let backend = terminal.backend_mut();
+
+    // second half of the wide char snaps to the lead cell.
+    backend.set_cursor_position(Position::new(2, 0)).unwrap();
+    assert_eq!(backend.get_cursor_position().unwrap(), Position::new(1, 0));
+
+    backend.set_cursor_position(Position::new(1, 0)).unwrap();
+    assert_eq!(backend.get_cursor_position().unwrap(), Position::new(1, 0));
+    backend.set_cursor_position(Position::new(3, 0)).unwrap();
+    assert_eq!(backend.get_cursor_position().unwrap(), Position::new(3, 0));
+    backend.set_cursor_position(Position::new(2, 1)).unwrap();
+    assert_eq!(backend.get_cursor_position().unwrap(), Position::new(2, 1));
+}
+
+#[test]
+#[serial]
+fn supersample() {
+    let render = |factor: Option<u8>| {
+        let mut fonts = Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            24,
+        );
+        if let Some(factor) = factor {
+            fonts.set_raster_settings(RasterSettings {
+                supersample: factor,
+                ..fonts.raster_settings()
+            });
+        }
+
+        let mut terminal = headless(builder(fonts, 512, 72));
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("aSgW@"), f.area());
+            })
+            .unwrap();
+
+        readback(terminal.backend(), 512, 72)
+    };
+
+    let grey_levels = |image: &ImageBuffer<Rgba<u8>, Vec<u8>>| {
+        let mut levels = image.pixels().map(|px| px[0]).collect::<Vec<_>>();
+        levels.sort();
+        levels.dedup();
+        levels.len()
+    };
+
+    let default = render(None);
+    assert_eq!(render(Some(2)), default);
+
+    // all factors are anti-aliased, but rasterize differently.
+    let x1 = render(Some(1));
+    let x3 = render(Some(3));
+    assert_golden(
+        &x1,
+        "supersample_1",
+        include_bytes!("goldens/supersample_1.png"),
+    );
+    assert_golden(
+        &x3,
+        "supersample_3",
+        include_bytes!("goldens/supersample_3.png"),
+    );
+    assert!(grey_levels(&x1) > 2);
+    assert!(grey_levels(&x3) > 2);
+    assert_ne!(x1, default);
+    assert_ne!(x3, default);
+
+    // clamped to 1..=4
+    assert_eq!(render(Some(0)), x1);
+    assert_eq!(render(Some(9)), render(Some(4)));
+}
+
+#[test]
+#[serial]
+fn headless_resize() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::Rgb(0, 0, 255))
+                .with_margin_color(Color::Rgb(0, 0, 255))
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let capture = |terminal: &mut Terminal<rat_wgpu::WgpuBackend>, width: u32, height: u32| {
+        terminal.backend_mut().resize(width, height);
+        terminal.autoresize().unwrap();
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("Hello"), f.area());
+            })
+            .unwrap();
+
+        let backend = terminal.backend();
+        let bytes_per_row = backend.headless_bytes_per_row();
+        assert_eq!(bytes_per_row, (width * 4).next_multiple_of(256));
+
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        assert_eq!(buffer.len(), (bytes_per_row * height) as usize);
+        let image = ImageBuffer::<Rgba<u8>, _>::from_fn(width, height, |x, y| {
+            let offset = (y * bytes_per_row + x * 4) as usize;
+            Rgba(buffer[offset..offset + 4].try_into().unwrap())
+        });
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        image
+    };
+
+    let image = capture(&mut terminal, 256, 48);
+    assert_eq!(image.dimensions(), (256, 48));
+    assert_eq!(*image.get_pixel(255, 47), Rgba([0, 0, 255, 255]));
+
+    // rows are padded.
+    let image = capture(&mut terminal, 300, 100);
+    assert_eq!(image.dimensions(), (300, 100));
+    assert_eq!(*image.get_pixel(299, 99), Rgba([0, 0, 255, 255]));
+}
+
+#[test]
+#[serial]
+fn fallback_align() {
+    // horizontal center of the ink in the first cell.
+    let ink_center = |align: Option<HorizontalAlign>, golden: Option<(&str, &[u8])>| {
+        let mut fonts = Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            24,
+        );
+        // make the cells wider than the glyphs.
+        fonts.set_cell_width_px(Some(40));
+        if let Some(align) = align {
+            fonts.set_raster_settings(RasterSettings {
+                fallback_align: align,
+                ..fonts.raster_settings()
+            });
+        }
+
+        let mut terminal = headless(builder(fonts, 512, 72));
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("#"), f.area());
+            })
+            .unwrap();
+
+        let cell_box = terminal.backend().cell_box();
+        assert_eq!(cell_box.width, 40);
+        let image = readback(terminal.backend(), 512, 72);
+        if let Some((name, golden)) = golden {
+            assert_golden(&image, name, golden);
+        }
+
+        let ink = (0..cell_box.width)
+            .filter(|x| (0..cell_box.height).any(|y| image.get_pixel(*x, y)[0] < 128))
+            .collect::<Vec<_>>();
+        (ink[0] + ink[ink.len() - 1]) / 2
+    };
+
+    let left = ink_center(
+        Some(HorizontalAlign::Left),
+        Some((
+            "fallback_align_left",
+            include_bytes!("goldens/fallback_align_left.png"),
+        )),
+    );
+    let center = ink_center(
+        Some(HorizontalAlign::Center),
+        Some((
+            "fallback_align_center",
+            include_bytes!("goldens/fallback_align_center.png"),
+        )),
+    );
+    let right = ink_center(
+        Some(HorizontalAlign::Right),
+        Some((
+            "fallback_align_right",
+            include_bytes!("goldens/fallback_align_right.png"),
+        )),
+    );
+    assert_eq!(ink_center(None, None), center);
+    assert!(left < center);
+    assert!(center < right);
+    assert!(center.abs_diff(20) <= 2);
+}
+
+#[test]
+#[serial]
+fn enclosing_marks() {
+    // columns with ink in the first row.
+    let ink_columns = |text: &'static str| {
+        let mut terminal = headless(builder(
+            Fonts::new(
+                Font::new(include_bytes!("fonts/Fairfax.ttf")).expect("Invalid font file"),
+                24,
+            ),
+            256,
+            72,
+        ));
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new(text), f.area());
+            })
+            .unwrap();
+
+        let cell_box = terminal.backend().cell_box();
+        let image = readback(terminal.backend(), 256, 72);
+        let ink = (0..256)
+            .filter(|x| (0..cell_box.height).any(|y| image.get_pixel(*x, y)[0] < 128))
+            .collect::<Vec<_>>();
+        (cell_box, image, ink)
+    };
+
+    let (cell_box, _, digit) = ink_columns("1");
+    for (text, name, golden) in [
+        (
+            "1\u{fe0f}\u{20e3}",
+            "enclosing_keycap",
+            &include_bytes!("goldens/enclosing_keycap.png")[..],
+        ),
+        (
+            "1\u{20dd}",
+            "enclosing_circle",
+            &include_bytes!("goldens/enclosing_circle.png")[..],
+        ),
+    ] {
+        let (_, image, keycap) = ink_columns(text);
+        assert_golden(&image, name, golden);
+        // the mark surrounds the digit and stays in the cell.
+        assert!(keycap.len() > digit.len());
+        assert!(keycap[0] <= digit[0]);
+        assert!(keycap[keycap.len() - 1] >= digit[digit.len() - 1]);
+        assert!(keycap[keycap.len() - 1] < cell_box.width);
+        assert!(keycap.len() as u32 >= cell_box.width * 3 / 4);
+        let center = (keycap[0] + keycap[keycap.len() - 1]) / 2;
+        assert!(center.abs_diff(cell_box.width / 2) <= 2);
+    }
+}
+
+#[test]
+#[serial]
+fn enclosing_mark_key() {
+    let mut terminal = headless(builder(
+        Fonts::new(
+            Font::new(include_bytes!("fonts/Fairfax.ttf")).expect("Invalid font file"),
+            24,
+        ),
+        256,
+        72,
+    ));
+    // the same glyph on its own and around a digit.
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("  1\u{20dd}"), f.area());
+            f.buffer_mut()[(0, 0)].set_symbol("\u{20dd}");
+        })
+        .unwrap();
+
+    let face = Font::new(include_bytes!("fonts/Fairfax.ttf")).expect("Invalid font file");
+    let mark = face.face().glyph_index('\u{20dd}').expect("glyph").0 as u32;
+    let mut keys = terminal
+        .backend()
+        .cached_glyphs()
+        .filter(|key| key.glyph_id() == mark)
+        .map(|key| key.enclosing())
+        .collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, [false, true]);
+}
+
+#[test]
+fn font_metrics() {
+    let font =
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file");
+    let metrics = font.metrics();
+    let face = font.face();
+
+    assert_eq!(metrics.ascender, face.ascender());
+    assert_eq!(metrics.descender, face.descender());
+    assert_eq!(metrics.line_gap, face.line_gap());
+    assert_eq!(metrics.units_per_em as i32, face.units_per_em());
+    assert!(metrics.ascender > 0);
+    assert!(metrics.descender < 0);
+    assert!(metrics.underline_position.is_some_and(|v| v < 0));
+    assert!(metrics.underline_thickness.is_some_and(|v| v > 0));
+
+    // the cell box is derived from the ascender and descender.
+    let fonts = Fonts::new(font, 24);
+    let scale = 24.0 / (metrics.ascender - metrics.descender) as f32;
+    assert_eq!(fonts.ascender(), (metrics.ascender as f32 * scale) as u32);
+}
+
+#[test]
+#[serial]
+fn reversed_decorations() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(builder(fonts, 512, 72).with_cursor_style(CursorStyle::Block));
+
+    let style = Style::new()
+        .fg(Color::Rgb(255, 0, 0))
+        .bg(Color::Rgb(0, 0, 255))
+        .reversed()
+        .underlined()
+        .crossed_out();
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new(Line::styled("    ab", style)), f.area());
+            f.set_cursor_position((2, 0));
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(
+        &image,
+        "reversed_decorations",
+        include_bytes!("goldens/reversed_decorations.png"),
+    );
+
+    let column = |cell: u32| {
+        (0..cell_box.height)
+            .map(|y| *image.get_pixel(cell * cell_box.width + 1, y))
+            .collect::<Vec<_>>()
+    };
+
+    let red = Rgba([255, 0, 0, 255]);
+    let blue = Rgba([0, 0, 255, 255]);
+
+    // the bg is the fg, underline and strikeout use the bg.
+    let plain = column(0);
+    assert_eq!(plain[0], red);
+    assert!(plain.iter().all(|px| *px == red || *px == blue));
+    assert!(plain.iter().filter(|px| **px == blue).count() >= 2);
+
+    // the block cursor has the color of the reversed fg.
+    let cursor = column(2);
+    assert_eq!(cursor[0], blue);
+    assert!(!cursor.contains(&red));
+}
+
+#[test]
+#[serial]
+fn build_headless_blocking() {
+    // no executor needed.
+    let mut terminal = Terminal::new(
+        Builder::<DefaultPostProcessorBuilder>::default()
+            .with_fallback_fonts(Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ))
+            .with_width_and_height(512, 72)
+            .build_headless_blocking()
+            .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("blocking"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend().get_text().starts_with("blocking"));
+}
+
+#[test]
+#[serial]
+fn control_char_display() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(builder(fonts, 512, 72));
+    assert_eq!(
+        terminal.backend().control_char_display(),
+        ControlCharDisplay::Blank
+    );
+
+    let cell_box = terminal.backend().cell_box();
+    // the ratatui buffer filters control chars, they only come in
+    // with cells given to the backend directly.
+    let cells = ["a", "\x01", "\x7f", "b"].map(|symbol| {
+        let mut cell = Cell::default();
+        cell.set_symbol(symbol);
+        cell
+    });
+    let mut ink = |display: ControlCharDisplay| {
+        let backend = terminal.backend_mut();
+        backend.set_control_char_display(display);
+        Backend::draw(
+            backend,
+            cells
+                .iter()
+                .enumerate()
+                .map(|(x, cell)| (x as u16, 0, cell)),
+        )
+        .unwrap();
+        Backend::flush(backend).unwrap();
+
+        let backend = terminal.backend();
+        // the cells keep the control chars.
+        assert!(backend.get_text().starts_with("a\x01\x7fb"));
+
+        let image = readback(backend, 512, 72);
+        // ink in the left and right half of the cells. without the
+        // outer columns, the squeezed glyphs may touch the next cell.
+        (0..4)
+            .map(|col| {
+                let half = |h: u32| {
+                    (0..cell_box.height)
+                        .flat_map(|y| {
+                            (0..cell_box.width / 2 - 1).map(move |x| {
+                                (
+                                    col * cell_box.width + h * (cell_box.width / 2 - 1) + 1 + x,
+                                    y,
+                                )
+                            })
+                        })
+                        .filter(|(x, y)| image.get_pixel(*x, *y)[0] < 128)
+                        .count()
+                };
+                (half(0), half(1))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let blank = ink(ControlCharDisplay::Blank);
+    assert_ne!(blank[0], (0, 0));
+    assert_eq!(blank[1], (0, 0));
+    assert_eq!(blank[2], (0, 0));
+    assert_ne!(blank[3], (0, 0));
+
+    // ␁ and ␡
+    let pictures = ink(ControlCharDisplay::Pictures);
+    assert_ne!(pictures[1], (0, 0));
+    assert_ne!(pictures[2], (0, 0));
+    assert_ne!(pictures[1], pictures[2]);
+
+    // ^A and ^?, one glyph in each half.
+    let caret = ink(ControlCharDisplay::Caret);
+    assert!(caret[1].0 > 0 && caret[1].1 > 0);
+    assert!(caret[2].0 > 0 && caret[2].1 > 0);
+    assert_ne!(caret[1], caret[2]);
+    // the caret is the same.
+    assert_eq!(caret[1].0, caret[2].0);
+    assert_eq!(caret[0], blank[0]);
+}
+
+#[test]
+#[serial]
+fn builtin_box_drawing() {
+    let mut terminal = headless(
+        builder(
+            Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ),
+            512,
+            72,
+        )
+        .with_builtin_box_drawing(true),
+    );
+    assert!(terminal.backend().builtin_box_drawing());
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Block::bordered(), f.area());
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let area = terminal.backend().size().unwrap();
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(
+        &image,
+        "builtin_box_drawing",
+        include_bytes!("goldens/builtin_box_drawing.png"),
+    );
+    let ink = |x: u32, y: u32| image.get_pixel(x, y)[0] < 128;
+
+    // a border line runs through every pixel, from corner to corner.
+    let top = (0..cell_box.height)
+        .find(|y| ink(cell_box.width * 2, *y))
+        .expect("top border");
+    let left = (0..cell_box.width)
+        .find(|x| ink(*x, cell_box.height))
+        .expect("left border");
+    // the same offset in the last column and row.
+    let right = (area.width as u32 - 1) * cell_box.width + left;
+    let bottom = (area.height as u32 - 1) * cell_box.height + top;
+    assert!((left..=right).all(|x| ink(x, top)));
+    assert!((top..=bottom).all(|y| ink(left, y)));
+    assert!((left..=right).all(|x| ink(x, bottom)));
+    assert!((top..=bottom).all(|y| ink(right, y)));
+
+    // nothing outside the corners.
+    assert!(!ink(0, 0));
+}
+
+#[test]
+#[serial]
+fn braille_ramp() {
+    let mut terminal = headless(
+        builder(
+            Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ),
+            512,
+            72,
+        )
+        .with_builtin_box_drawing(true),
+    );
+
+    let ramp = "⠀⠁⠃⠇⡇⡏⡟⡿⣿";
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new(ramp), f.area());
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(
+        &image,
+        "braille_ramp",
+        include_bytes!("goldens/braille_ramp.png"),
+    );
+
+    // each pattern adds one dot.
+    let ink = (0..ramp.chars().count() as u32)
+        .map(|col| {
+            let mut count = 0;
+            for y in 0..cell_box.height {
+                for x in col * cell_box.width..(col + 1) * cell_box.width {
+                    if image.get_pixel(x, y)[0] < 128 {
+                        count += 1;
+                    }
+                }
+            }
+            count
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(ink[0], 0);
+    assert!(ink.windows(2).all(|w| w[0] < w[1]), "{ink:?}");
+    // all dots have the same size.
+    assert!(
+        ink.iter()
+            .enumerate()
+            .all(|(n, count)| *count == n * ink[1]),
+        "{ink:?}"
+    );
+}
+
+#[test]
+#[serial]
+fn window_opacity() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 80)
+                .with_bg_color(Color::White)
+                .with_margin_color(Color::Rgb(0, 0, 255))
+                .with_alpha_mode(CompositeAlphaMode::PreMultiplied)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(terminal.backend().window_opacity(), 1.0);
+
+    terminal.backend_mut().set_window_opacity(0.5);
+    assert_eq!(terminal.backend().window_opacity(), 0.5);
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new(""), f.area());
+        })
+        .unwrap();
+
+    let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+    let cell_box = backend.cell_box();
+    let area = backend.size().unwrap();
+    assert!(area.height as u32 * cell_box.height < 80);
+
+    let buffer = backend.map_headless_buffer().expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 80, &*buffer).unwrap();
+    let close = |a: Rgba<u8>, b: [u8; 4]| a.0.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= 1);
+
+    // white text layer and blue margin, both at half alpha and premultiplied.
+    let text = *image.get_pixel(0, 0);
+    assert!(close(text, [128, 128, 128, 128]), "{text:?}");
+    let margin = *image.get_pixel(511, 79);
+    assert!(close(margin, [0, 0, 128, 128]), "{margin:?}");
+
+    drop(buffer);
+    backend.unmap_headless_buffer();
+}
+
+#[test]
+#[serial]
+fn window_opacity_post_processors() {
+    // a pixel in the middle of the text layer at half opacity.
+    fn pixel<P: PostProcessorBuilder + Default>(post_processor: P) -> Rgba<u8> {
+        let mut terminal = Terminal::new(
+            futures_lite::future::block_on(
+                Builder::new_with_postprocessor(post_processor)
+                    .with_fallback_fonts(Fonts::new(
+                        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                            .expect("Invalid font file"),
+                        24,
+                    ))
+                    .with_width_and_height(512, 80)
+                    .with_bg_color(Color::White)
+                    .with_alpha_mode(CompositeAlphaMode::PreMultiplied)
+                    .build_headless(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        terminal.backend_mut().set_window_opacity(0.5);
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new(""), f.area());
+            })
+            .unwrap();
+
+        let backend = terminal.backend();
+        let buffer = backend.map_headless_buffer().expect("headless buffer");
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 80, &*buffer).unwrap();
+        let pixel = *image.get_pixel(256, 36);
+        drop(buffer);
+        backend.unmap_headless_buffer();
+        pixel
+    }
+
+    let overlay = OverlayPostProcessorBuilder::with_source(|device, config| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Background"),
+                size: wgpu::Extent3d {
+                    width: config.width,
+                    height: config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    });
+
+    for pixel in [
+        pixel(BloomPostProcessorBuilder::default()),
+        pixel(CrtPostProcessorBuilder::default()),
+        pixel(overlay),
+    ] {
+        // half alpha, and premultiplied.
+        assert!(pixel[3].abs_diff(128) <= 1, "{pixel:?}");
+        assert!(pixel.0[..3].iter().all(|c| *c <= pixel[3]), "{pixel:?}");
+    }
+}
+
+#[test]
+#[serial]
+fn remove_font() {
+    let cascadia = include_bytes!("fonts/CascadiaMono-Regular.ttf");
+    let fairfax = include_bytes!("fonts/Fairfax.ttf");
+
+    let mut fonts = Fonts::new(Font::new(cascadia).expect("Invalid font file"), 24);
+    fonts.add_regular_fonts([
+        Font::new(fairfax).expect("Invalid font file"),
+        Font::new(cascadia).expect("Invalid font file"),
+        Font::new(cascadia).expect("Invalid font file"),
+    ]);
+    // 'ƀ' is only in Fairfax, '⬥' only in Cascadia.
+    assert_eq!(fonts.coverage("ƀ⬥"), vec![('ƀ', Some(1)), ('⬥', Some(2))]);
+
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(fonts)
+                .with_width_and_height(512, 72)
+                .with_bg_color(Color::White)
+                .with_fg_color(Color::Black)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    // the only fallback font stays.
+    assert!(!terminal.backend_mut().remove_font(0));
+    assert!(terminal.backend_mut().remove_font(2));
+    assert!(!terminal.backend_mut().remove_font(2));
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("ƀ⬥"), f.area());
+        })
+        .unwrap();
+
+    let backend: &rat_wgpu::WgpuBackend = terminal.backend();
+    let cell_box = backend.cell_box();
+    let buffer = backend.map_headless_buffer().expect("headless buffer");
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*buffer).unwrap();
+
+    // both glyphs are still there, from font 1 and 3.
+    for col in 0..2 {
+        let ink = (0..cell_box.height)
+            .flat_map(|y| (col * cell_box.width..(col + 1) * cell_box.width).map(move |x| (x, y)))
+            .any(|(x, y)| image.get_pixel(x, y)[0] < 128);
+        assert!(ink, "cell {col}");
+    }
+
+    drop(buffer);
+    backend.unmap_headless_buffer();
+
+    let mut fonts = Fonts::new(Font::new(cascadia).expect("Invalid font file"), 24);
+    fonts.add_regular_fonts([
+        Font::new(fairfax).expect("Invalid font file"),
+        Font::new(cascadia).expect("Invalid font file"),
+        Font::new(cascadia).expect("Invalid font file"),
+    ]);
+    assert!(fonts.remove_font(2));
+    assert_eq!(fonts.coverage("ƀ⬥"), vec![('ƀ', Some(1)), ('⬥', Some(3))]);
+}
+
+#[test]
+#[serial]
+fn fonts_accessor() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_regular_fonts([
+                    Font::new(include_bytes!("fonts/Fairfax.ttf")).expect("Invalid font file")
+                ])
+                .with_width_and_height(512, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let backend = terminal.backend();
+    let fonts = backend.fonts();
+    assert_eq!(fonts.count(), 2);
+    assert_eq!(fonts.cell_box().width, backend.cell_box().width);
+    assert_eq!(fonts.height_px(), backend.cell_box().height);
+    // 'ƀ' is only in Fairfax, '⬥' only in Cascadia.
+    assert_eq!(fonts.coverage("ƀ⬥"), vec![('ƀ', Some(1)), ('⬥', Some(0))]);
+
+    terminal.backend_mut().update_font_size(32);
+    assert_eq!(terminal.backend().fonts().height_px(), 32);
+}
+
+#[test]
+#[serial]
+fn notdef_style() {
+    // U+E000 is not in the font, it's followed by a real '?'.
+    let render = |style: NotdefStyle, name: &str, golden: &[u8]| {
+        let mut fonts = Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            24,
+        );
+        fonts.set_raster_settings(RasterSettings {
+            notdef_style: style,
+            ..fonts.raster_settings()
+        });
+        assert_eq!(fonts.raster_settings().notdef_style, style);
+
+        let mut terminal = headless(builder(fonts, 512, 72));
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("\u{E000}?"), f.area());
+            })
+            .unwrap();
+
+        let cell_box = terminal.backend().cell_box();
+        let image = readback(terminal.backend(), 512, 72);
+        assert_golden(&image, name, golden);
+
+        [0, 1].map(|col| {
+            image
+                .view(col * cell_box.width, 0, cell_box.width, cell_box.height)
+                .to_image()
+        })
+    };
+    let ink = |cell: &ImageBuffer<Rgba<u8>, Vec<u8>>| cell.pixels().filter(|p| p[0] < 128).count();
+    let center = |cell: &ImageBuffer<Rgba<u8>, Vec<u8>>| {
+        cell.get_pixel(cell.width() / 2, cell.height() / 2)[0] < 128
+    };
+
+    let [hollow, _] = render(
+        NotdefStyle::HollowBox,
+        "notdef_hollow_box",
+        include_bytes!("goldens/notdef_hollow_box.png"),
+    );
+    assert!(ink(&hollow) > 0);
+    assert!(!center(&hollow));
+
+    let [filled, _] = render(
+        NotdefStyle::FilledBox,
+        "notdef_filled_box",
+        include_bytes!("goldens/notdef_filled_box.png"),
+    );
+    assert!(ink(&filled) > ink(&hollow));
+    assert!(center(&filled));
+
+    let [question, real] = render(
+        NotdefStyle::QuestionMark,
+        "notdef_question_mark",
+        include_bytes!("goldens/notdef_question_mark.png"),
+    );
+    assert!(ink(&question) > 0);
+    assert_eq!(question, real);
+
+    let [blank, _] = render(
+        NotdefStyle::Blank,
+        "notdef_blank",
+        include_bytes!("goldens/notdef_blank.png"),
+    );
+    assert_eq!(ink(&blank), 0);
+}
+
+#[test]
+#[serial]
+fn cluster_kerning() {
+    // leftmost ink below the baseline, that's the macron below.
+    let mark_x = |preserve: bool, name: &str, golden: &[u8]| {
+        let mut fonts = Fonts::new(
+            Font::new(include_bytes!("fonts/Fairfax.ttf")).expect("Invalid font file"),
+            24,
+        );
+        fonts.set_raster_settings(RasterSettings {
+            preserve_cluster_kerning: preserve,
+            ..fonts.raster_settings()
+        });
+        assert_eq!(fonts.raster_settings().preserve_cluster_kerning, preserve);
+
+        let mut terminal = headless(builder(fonts, 512, 72));
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("o\u{332}"), f.area());
+            })
+            .unwrap();
+
+        let cell_box = terminal.backend().cell_box();
+        let image = readback(terminal.backend(), 512, 72);
+        assert_golden(&image, name, golden);
+
+        let x = (cell_box.ascender + 1..cell_box.height)
+            .flat_map(|y| (0..512).map(move |x| (x, y)))
+            .filter(|(x, y)| image.get_pixel(*x, *y)[0] < 128)
+            .map(|(x, _)| x)
+            .min()
+            .expect("mark");
+        (x, cell_box.width)
+    };
+
+    // the mark has no advance, it's placed after the 'o' when kept
+    // as shaped. otherwise it's moved back to the start of the cell.
+    let (grid, cell_width) = mark_x(
+        false,
+        "cluster_kerning_grid",
+        include_bytes!("goldens/cluster_kerning_grid.png"),
+    );
+    let (shaped, _) = mark_x(
+        true,
+        "cluster_kerning_shaped",
+        include_bytes!("goldens/cluster_kerning_shaped.png"),
+    );
+    assert!(shaped.abs_diff(grid + cell_width) <= 1, "{grid} {shaped}");
+}
+
+#[test]
+#[serial]
+fn invalidate() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    12,
+                ))
+                .with_width_and_height(1024, 72)
+                .build_headless(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let text = "lorem ipsum dolor sit amet";
+    let words = text.split(' ').count() as u64;
+    let draw = |terminal: &mut Terminal<rat_wgpu::WgpuBackend>| {
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new(text), f.area());
+            })
+            .unwrap();
+        terminal.backend().shaping_stats()
+    };
+
+    let initial = draw(&mut terminal);
+
+    // nothing changed, nothing to do.
+    let unchanged = draw(&mut terminal);
+    assert_eq!(unchanged, initial);
+
+    // all rows are painted again, from the cache.
+    terminal.backend_mut().invalidate();
+    let repaint = draw(&mut terminal);
+    assert_eq!(repaint.shaped, initial.shaped);
+    assert!(repaint.cached - initial.cached >= words);
+}
+
+#[test]
+#[serial]
+fn pipelined_capture() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(builder(fonts, 512, 72));
+
+    terminal.backend_mut().begin_capture();
+
+    // more frames than buffers in flight.
+    let mut frames = Vec::new();
+    for n in 0..5 {
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new(format!("frame {n}")), f.area());
+            })
+            .unwrap();
+        frames.extend(terminal.backend_mut().poll_capture());
+    }
+    terminal.backend_mut().end_capture().unwrap();
+    while let Some(frame) = terminal.backend_mut().poll_capture() {
+        frames.push(frame);
+    }
+    assert_eq!(frames.len(), 5);
+    assert!(frames.iter().all(|frame| frame.len() == 512 * 72 * 4));
+    assert!(frames.windows(2).all(|w| w[0] != w[1]));
+
+    // the last frame is what's on the surface now.
+    assert!(readback(terminal.backend(), 512, 72).into_raw() == frames[4]);
+
+    // nothing more after the end.
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("done"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend_mut().poll_capture().is_none());
+}
+
+#[test]
+#[serial]
+fn subpixel_phases() {
+    let render = |count: u8, golden: Option<&[u8]>| {
+        let mut fonts = Fonts::new(
+            Font::new(include_bytes!("fonts/Fairfax.ttf")).expect("Invalid font file"),
+            30,
+        );
+        fonts.set_raster_settings(RasterSettings {
+            subpixel_phases: count,
+            ..fonts.raster_settings()
+        });
+        assert_eq!(fonts.raster_settings().subpixel_phases, count.clamp(1, 4));
+
+        let mut terminal = headless(builder(fonts, 512, 72));
+        // the macron below is offset by 1.25px at this size.
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("o\u{332}"), f.area());
+            })
+            .unwrap();
+
+        let backend = terminal.backend();
+        let phases = backend
+            .cached_glyphs()
+            .map(|key| key.phase())
+            .collect::<Vec<_>>();
+        let image = readback(backend, 512, 72);
+        if let Some(golden) = golden {
+            assert_golden(&image, &format!("subpixel_phases_{count}"), golden);
+        }
+        (phases, image)
+    };
+
+    let (whole, whole_image) = render(1, Some(include_bytes!("goldens/subpixel_phases_1.png")));
+    assert!(whole.iter().all(|phase| *phase == 0));
+
+    // the mark lands on the phase nearest to 1/4px.
+    let (thirds, thirds_image) = render(3, Some(include_bytes!("goldens/subpixel_phases_3.png")));
+    assert!(thirds.contains(&1), "{thirds:?}");
+    assert!(thirds.iter().all(|phase| *phase < 3));
+    assert_ne!(whole_image, thirds_image);
+
+    assert_eq!(render(9, None).0.len(), thirds.len());
+}
+
+#[test]
+#[serial]
+fn grid_fit() {
+    let render = |grid_fit: bool, name: &str, golden: &[u8]| {
+        let mut fonts = Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            12,
+        );
+        // grid-fitting skips the fallback fonts.
+        fonts
+            .add_regular_fonts([Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                .expect("Invalid font file")]);
+        assert!(!fonts.raster_settings().grid_fit);
+        fonts.set_raster_settings(RasterSettings {
+            grid_fit,
+            ..fonts.raster_settings()
+        });
+        assert_eq!(fonts.raster_settings().grid_fit, grid_fit);
+
+        let mut terminal = headless(builder(fonts, 512, 72));
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("l|Im"), f.area());
+            })
+            .unwrap();
+
+        let image = readback(terminal.backend(), 512, 72);
+        assert_golden(&image, name, golden);
+
+        // how far the pixels are from plain black or white.
+        let gray = image
+            .pixels()
+            .map(|p| p[0].min(255 - p[0]) as u32)
+            .sum::<u32>();
+        let ink = image.pixels().filter(|p| p[0] < 128).count();
+        (gray, ink)
+    };
+
+    let (blurry, blurry_ink) = render(
+        false,
+        "grid_fit_off",
+        include_bytes!("goldens/grid_fit_off.png"),
+    );
+    let (crisp, crisp_ink) = render(
+        true,
+        "grid_fit_on",
+        include_bytes!("goldens/grid_fit_on.png"),
+    );
+    assert!(blurry_ink > 0 && crisp_ink > 0);
+    // the stems cover whole pixels.
+    assert!(crisp < blurry, "{crisp} < {blurry}");
+}
+
+#[test]
+#[serial]
+fn clear_every_frame() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(builder(fonts, 512, 72));
+
+    // nothing to keep before the first draw.
+    terminal.clear().unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("Hello\nWorld"), f.area());
+        })
+        .unwrap();
+    let hello = readback(terminal.backend(), 512, 72);
+
+    for _ in 0..3 {
+        terminal.clear().unwrap();
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("Hello\nWorld"), f.area());
+            })
+            .unwrap();
+        assert_eq!(readback(terminal.backend(), 512, 72), hello);
+    }
+
+    // nothing drawn after the clear.
+    terminal.clear().unwrap();
+    terminal
+        .draw(|_f: &mut ratatui_core::terminal::Frame| {})
+        .unwrap();
+    assert_eq!(terminal.backend().get_text_trimmed().trim(), "");
+    assert!(
+        readback(terminal.backend(), 512, 72)
+            .pixels()
+            .all(|p| p.0[..3] == [255, 255, 255])
+    );
+}
+
+#[test]
+#[serial]
+fn empty_cell() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(builder(fonts, 512, 72).with_empty_cell(Cell::new("\u{b7}")));
+    assert_eq!(terminal.backend().empty_cell().symbol(), "\u{b7}");
+
+    let ink = |terminal: &Terminal<_>, col: u32| {
+        let backend: &WgpuBackend = terminal.backend();
+        let cell_box = backend.cell_box();
+        let image = readback(backend, 512, 72);
+        (0..cell_box.height)
+            .flat_map(|y| (0..cell_box.width).map(move |x| (col * cell_box.width + x, y)))
+            .filter(|(x, y)| image.get_pixel(*x, *y)[0] < 128)
+            .count()
+    };
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("a"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend().get_text().starts_with("a\u{b7}\u{b7}"));
+    assert!(ink(&terminal, 1) > 0);
+
+    // cleared cells get the fill too.
+    terminal.clear().unwrap();
+    terminal.draw(|_| {}).unwrap();
+    assert!(terminal.backend().get_text().starts_with("\u{b7}\u{b7}"));
+    assert_eq!(ink(&terminal, 0), ink(&terminal, 1));
+    assert!(ink(&terminal, 0) > 0);
+
+    // wide symbols are rejected.
+    terminal.backend_mut().set_empty_cell(Cell::new("\u{3042}"));
+    assert_eq!(terminal.backend().empty_cell().symbol(), "\u{b7}");
+    terminal.backend_mut().set_empty_cell(Cell::EMPTY);
+    terminal.clear().unwrap();
+    terminal.draw(|_| {}).unwrap();
+    assert_eq!(ink(&terminal, 0), 0);
+}
+
+#[test]
+#[serial]
+fn text_outline() {
+    let mut terminal = headless(
+        builder(
+            Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ),
+            512,
+            72,
+        )
+        .with_bg_color(Color::Rgb(128, 128, 128))
+        .with_fg_color(Color::White),
+    );
+    assert_eq!(terminal.backend().text_outline(), (0.0, Color::Reset));
+
+    // darkest and brightest pixel of the text.
+    let render = |terminal: &mut Terminal<_>, golden: Option<&[u8]>| {
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new("Hello"), f.area());
+            })
+            .unwrap();
+        let image = readback(terminal.backend(), 512, 72);
+        if let Some(golden) = golden {
+            assert_golden(&image, "text_outline", golden);
+        }
+        let luma = image.pixels().map(|p| p[1]).collect::<Vec<_>>();
+        (*luma.iter().min().unwrap(), *luma.iter().max().unwrap())
+    };
+
+    let (dark, bright) = render(&mut terminal, None);
+    assert!(dark >= 120, "{dark}");
+    assert!(bright > 240, "{bright}");
+
+    terminal.backend_mut().set_text_outline(1.5, Color::Black);
+    assert_eq!(terminal.backend().text_outline(), (1.5, Color::Black));
+    let (dark, bright) = render(
+        &mut terminal,
+        Some(include_bytes!("goldens/text_outline.png")),
+    );
+    assert!(dark < 40, "{dark}");
+    // the fill is still white.
+    assert!(bright > 240, "{bright}");
+
+    terminal.backend_mut().set_text_outline(0.0, Color::Black);
+    let (dark, _) = render(&mut terminal, None);
+    assert!(dark >= 120, "{dark}");
+}
+
+#[test]
+#[serial]
+fn last_flush_presented() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(builder(fonts, 512, 72));
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("Hello"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend().last_flush_presented());
+
+    // nothing changed.
+    assert!(!terminal.backend().has_pending_changes());
+    terminal.backend_mut().flush().unwrap();
+    assert!(!terminal.backend().last_flush_presented());
+    terminal.backend_mut().redraw().unwrap();
+    assert!(!terminal.backend().last_flush_presented());
+
+    terminal.backend_mut().invalidate();
+    terminal.backend_mut().flush().unwrap();
+    assert!(terminal.backend().last_flush_presented());
+}
+
+#[test]
+#[serial]
+fn inset() {
+    let inset = Inset {
+        top: 10,
+        right: 13,
+        bottom: 6,
+        left: 7,
+    };
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(
+        builder(fonts, 512, 72)
+            .with_bg_color(Color::Black)
+            .with_margin_color(Color::Red)
+            .with_inset(inset),
+    );
+
+    let cell_box = terminal.backend().cell_box();
+    assert_eq!(terminal.backend().inset(), inset);
+
+    // the grid shrinks by the inset.
+    let size = terminal.backend().size().unwrap();
+    assert_eq!(size.width as u32, (512 - 7 - 13) / cell_box.width);
+    assert_eq!(size.height as u32, (72 - 10 - 6) / cell_box.height);
+    let window_size = terminal.backend_mut().window_size().unwrap();
+    assert_eq!(window_size.columns_rows, size);
+    assert_eq!(window_size.pixels.width, 512 - 7 - 13);
+    assert_eq!(window_size.pixels.height, 72 - 10 - 6);
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Block::new().bg(Color::Blue), f.area());
+        })
+        .unwrap();
+
+    let image = readback(terminal.backend(), 512, 72);
+    let red = |x: u32, y: u32| {
+        let p = image.get_pixel(x, y);
+        p[0] > 128 && p[2] < 128
+    };
+    let blue = |x: u32, y: u32| {
+        let p = image.get_pixel(x, y);
+        p[0] < 128 && p[2] > 128
+    };
+
+    // the cells start after the inset, all around is margin.
+    assert!(red(0, 0));
+    assert!(red(6, 20));
+    assert!(red(20, 9));
+    assert!(blue(7, 10));
+    let right = 7 + size.width as u32 * cell_box.width;
+    let bottom = 10 + size.height as u32 * cell_box.height;
+    assert!(blue(right - 1, bottom - 1));
+    assert!(red(right, 20));
+    assert!(red(20, bottom));
+    assert!(red(511, 71));
+
+    // without the inset the grid covers the surface again.
+    terminal.backend_mut().set_inset(Inset::default());
+    let size = terminal.backend().size().unwrap();
+    assert_eq!(size.width as u32, 512 / cell_box.width);
+    assert_eq!(size.height as u32, 72 / cell_box.height);
+
+    terminal.backend_mut().set_inset(Inset::uniform(4));
+    terminal.backend_mut().resize_cells(10, 2);
+    assert_eq!(
+        terminal.backend().size().unwrap(),
+        ratatui_core::layout::Size::new(10, 2)
+    );
+}
+
+#[test]
+#[serial]
+fn rotation() {
+    // a portrait display, the terminal is rotated to landscape.
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(
+        builder(fonts, 72, 512)
+            .with_bg_color(Color::Blue)
+            .with_rotation(Rotation::Cw90),
+    );
+
+    let cell_box = terminal.backend().cell_box();
+    assert_eq!(terminal.backend().rotation(), Rotation::Cw90);
+
+    // the grid is laid out for the rotated surface.
+    let size = terminal.backend().size().unwrap();
+    assert_eq!(size.width as u32, 512 / cell_box.width);
+    assert_eq!(size.height as u32, 72 / cell_box.height);
+    let window_size = terminal.backend_mut().window_size().unwrap();
+    assert_eq!(window_size.pixels, ratatui_core::layout::Size::new(512, 72));
+
+    // the top-left of the terminal is at the top-right of the surface.
+    assert_eq!(terminal.backend().pos_to_cell((71, 0)), (0, 0));
+    assert_eq!(
+        terminal.backend().pos_to_cell((71, cell_box.width as i32)),
+        (1, 0)
+    );
+    assert_eq!(
+        terminal
+            .backend()
+            .pos_to_cell((71 - cell_box.height as i32, 0)),
+        (0, 1)
+    );
+    assert_eq!(
+        terminal.backend().pos_to_cell((0, 511)),
+        ((511 / cell_box.width) as u16, (71 / cell_box.height) as u16)
+    );
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Block::new().bg(Color::Blue), f.area());
+            f.render_widget(Block::new().bg(Color::Red), Rect::new(0, 0, 1, 1));
+        })
+        .unwrap();
+
+    let image = readback(terminal.backend(), 72, 512);
+    let red = |x: u32, y: u32| {
+        let p = image.get_pixel(x, y);
+        p[0] > 128 && p[2] < 128
+    };
+
+    assert!(red(71, 0));
+    assert!(red(72 - cell_box.height, cell_box.width - 1));
+    assert!(!red(71 - cell_box.height, 0));
+    assert!(!red(71, cell_box.width));
+    assert!(!red(0, 0));
+    assert!(!red(0, 511));
+
+    terminal.backend_mut().set_rotation(Rotation::None);
+    let size = terminal.backend().size().unwrap();
+    assert_eq!(size.width as u32, 72 / cell_box.width);
+    assert_eq!(size.height as u32, 512 / cell_box.height);
+    assert_eq!(terminal.backend().pos_to_cell((0, 0)), (0, 0));
+}
+
+#[test]
+#[serial]
+fn image_cursor() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(builder(fonts, 512, 72));
+
+    let handle = terminal
+        .backend_mut()
+        .add_image(&[0, 255, 0, 255].repeat(16), 4, 4);
+    terminal.backend_mut().set_cursor_style(CursorStyle::Image);
+    terminal
+        .backend_mut()
+        .set_cursor_image(Some(handle.clone()));
+    assert_eq!(terminal.backend().cursor_style(), CursorStyle::Image);
+    assert_eq!(terminal.backend().cursor_image(), Some(&handle));
+
+    let green = Rgba([0, 255, 0, 255]);
+    let cells = |terminal: &Terminal<WgpuBackend>| {
+        let cell_box = terminal.backend().cell_box();
+        let image = readback(terminal.backend(), 512, 72);
+        (0..4)
+            .map(|cell| {
+                (0..cell_box.height)
+                    .flat_map(|y| (0..cell_box.width).map(move |x| (x, y)))
+                    .filter(|(x, y)| *image.get_pixel(cell * cell_box.width + x, *y) == green)
+                    .count() as u32
+                    == cell_box.width * cell_box.height
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // the image covers the cursor cell, text included.
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("abcd"), f.area());
+            f.set_cursor_position((2, 0));
+        })
+        .unwrap();
+    assert_eq!(cells(&terminal), vec![false, false, true, false]);
+
+    // and is gone with the cursor.
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("abcd"), f.area());
+        })
+        .unwrap();
+    assert_eq!(cells(&terminal), vec![false, false, false, false]);
+}
+
+#[test]
+#[serial]
+fn draw_buffer() {
+    let build = || {
+        headless(builder(
+            Fonts::new(
+                Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                    .expect("Invalid font file"),
+                24,
+            ),
+            512,
+            72,
+        ))
+    };
+    let text = || {
+        Paragraph::new(vec![
+            Line::from(vec!["Hello ".red(), "World".on_blue().underlined()]),
+            Line::from("wide ＡＢ chars"),
+        ])
+    };
+
+    let mut terminal = build();
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(text(), f.area());
+        })
+        .unwrap();
+    let expected = readback(terminal.backend(), 512, 72);
+
+    // larger than the backend, the rest is ignored.
+    let mut terminal = build();
+    let mut buffer = ratatui_core::buffer::Buffer::empty(Rect::new(0, 0, 100, 10));
+    ratatui_core::widgets::Widget::render(text(), buffer.area, &mut buffer);
+    terminal.backend_mut().draw_buffer(&buffer);
+    // draw hides the cursor without a position.
+    terminal.backend_mut().hide_cursor().unwrap();
+    terminal.backend_mut().flush().unwrap();
+    assert!(readback(terminal.backend(), 512, 72) == expected);
+}
+
+#[test]
+#[serial]
+fn text_layer_load_clear() {
+    let instance = wgpu::Instance::default();
+    let adapter = futures_lite::future::block_on(
+        instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+    )
+    .unwrap();
+    let (device, queue) =
+        futures_lite::future::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            required_limits: wgpu::Limits::downlevel_defaults(),
+            ..Default::default()
+        }))
+        .unwrap();
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 72,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_bg_color(Color::Black)
+                .with_fg_color(Color::White)
+                .with_text_layer_load(rat_wgpu::wgpu::LoadOp::Clear(
+                    rat_wgpu::wgpu::Color::TRANSPARENT,
+                ))
+                .with_device_and_queue(adapter, device.clone(), queue.clone())
+                .build_with_texture(view, 512, 72, TextureFormat::Rgba8Unorm),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    // ink in the given row of cells.
+    let ink = |backend: &WgpuBackend, row: u32| {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 512 * 4 * 72,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(512 * 4),
+                    rows_per_image: Some(72),
+                },
+            },
+            texture.size(),
+        );
+        queue.submit([encoder.finish()]);
+        buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+
+        let cell_box = backend.cell_box();
+        let data = buffer.slice(..).get_mapped_range();
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, &*data).unwrap();
+        (row * cell_box.height..(row + 1) * cell_box.height)
+            .flat_map(|y| (0..4 * cell_box.width).map(move |x| (x, y)))
+            .filter(|(x, y)| image.get_pixel(*x, *y).0[0] > 128)
+            .count()
+    };
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("abcd\nefgh"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend().last_render_error().is_none());
+    let second_row = ink(terminal.backend(), 1);
+    assert!(second_row > 0);
+
+    // only the first row changes, the second must be painted again.
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("xbcd\nefgh"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend().get_text().contains("efgh"));
+    assert_eq!(ink(terminal.backend(), 1), second_row);
+
+    // a blinking cell on the third row.
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("xbcd\nefgh"), f.area());
+            f.buffer_mut()[(0, 2)]
+                .set_char('z')
+                .set_style(Modifier::SLOW_BLINK);
+        })
+        .unwrap();
+
+    // the full repaint already covers the blinking cells.
+    for _ in 0..4 {
+        let fg_quads = terminal.backend().render_stats().fg_quads;
+        terminal
+            .backend_mut()
+            .blink(Blinking::TEXT | Blinking::CURSOR);
+        assert_eq!(ink(terminal.backend(), 1), second_row);
+        let blink_quads = terminal.backend().render_stats().fg_quads - fg_quads;
+
+        let fg_quads = terminal.backend().render_stats().fg_quads;
+        terminal.backend_mut().redraw().unwrap();
+        assert_eq!(ink(terminal.backend(), 1), second_row);
+        assert_eq!(
+            blink_quads,
+            terminal.backend().render_stats().fg_quads - fg_quads
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn continuous_underline() {
+    let mut terminal = headless(builder(
+        Fonts::new(
+            Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+            24,
+        ),
+        512,
+        72,
+    ));
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(
+                Paragraph::new(Line::from(vec![
+                    "a b c".underlined(),
+                    " ".into(),
+                    "a b c".crossed_out(),
+                ])),
+                f.area(),
+            );
+        })
+        .unwrap();
+
+    let cell_box = terminal.backend().cell_box();
+    let image = readback(terminal.backend(), 512, 72);
+    assert_golden(
+        &image,
+        "continuous_underline",
+        include_bytes!("goldens/continuous_underline.png"),
+    );
+
+    // rows that are ink from the first to the last pixel of the run.
+    let unbroken = |first_cell: u32| {
+        let xs = first_cell * cell_box.width..(first_cell + 5) * cell_box.width;
+        (0..cell_box.height)
+            .filter(|y| xs.clone().all(|x| image.get_pixel(x, *y)[0] < 128))
+            .collect::<Vec<_>>()
+    };
+
+    let underline = unbroken(0);
+    assert!(!underline.is_empty());
+    assert!(underline[0] >= cell_box.ascender);
+
+    let strikeout = unbroken(6);
+    assert!(!strikeout.is_empty());
+    assert!(strikeout[0] < cell_box.ascender);
+
+    // nothing between the runs.
+    assert!(unbroken(5).is_empty());
+}
+
+#[test]
+#[serial]
+fn render_stats() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(builder(fonts, 512, 72));
+
+    let draw = |terminal: &mut Terminal<WgpuBackend>, text: &'static str| {
+        terminal
+            .draw(|f: &mut ratatui_core::terminal::Frame| {
+                f.render_widget(Paragraph::new(text), f.area());
+            })
+            .unwrap();
+        terminal.backend().render_stats()
+    };
+
+    let first = draw(&mut terminal, "Hello");
+    assert!(first.glyphs_rasterized > 0);
+    assert!(first.fg_quads > 0);
+    assert!(first.bg_quads >= first.fg_quads);
+    assert!(first.dirty_rows > 0);
+
+    draw(&mut terminal, "World");
+    let before = terminal.backend().render_stats();
+
+    // the same text again comes from the atlas.
+    let again = draw(&mut terminal, "Hello");
+    assert!(again.glyphs_cached_hits > before.glyphs_cached_hits);
+    assert_eq!(again.glyphs_rasterized, before.glyphs_rasterized);
+}
+
+#[test]
+#[serial]
+fn headless_texture_only() {
+    let mut terminal = Terminal::new(
+        futures_lite::future::block_on(
+            Builder::<DefaultPostProcessorBuilder>::default()
+                .with_fallback_fonts(Fonts::new(
+                    Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf"))
+                        .expect("Invalid font file"),
+                    24,
+                ))
+                .with_width_and_height(512, 72)
+                .build_headless_texture_only(),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("texture"), f.area());
+        })
+        .unwrap();
+    assert!(terminal.backend().last_render_error().is_none());
+
+    let texture = terminal.backend().headless_texture();
+    assert_eq!((texture.width(), texture.height()), (512, 72));
+    assert!(
+        texture
+            .usage()
+            .contains(wgpu::TextureUsages::TEXTURE_BINDING)
+    );
+
+    assert!(matches!(
+        terminal.backend().map_headless_buffer(),
+        Err(rat_wgpu::Error::HeadlessReadbackDisabled)
+    ));
+}
+
+#[test]
+#[serial]
+fn render_into() {
+    let fonts = Fonts::new(
+        Font::new(include_bytes!("fonts/CascadiaMono-Regular.ttf")).expect("Invalid font file"),
+        24,
+    );
+    let mut terminal = headless(builder(fonts, 512, 72));
+
+    terminal
+        .draw(|f: &mut ratatui_core::terminal::Frame| {
+            f.render_widget(Paragraph::new("host frame"), f.area());
+        })
+        .unwrap();
+
+    let backend = terminal.backend_mut();
+    let device = backend.device().clone();
+    let queue = backend.queue().clone();
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: 512,
+            height: 72,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: backend.surface_format(),
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 512 * 4 * 72,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    // the host owns the encoder and submits it.
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    backend.render_into(&mut encoder, &view);
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(512 * 4),
+                rows_per_image: Some(72),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit([encoder.finish()]);
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+
+    // same frame as the one presented by flush.
+    let data = buffer.slice(..).get_mapped_range();
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(512, 72, data.to_vec()).unwrap();
+    assert!(image.pixels().any(|p| p[0] < 128));
+    assert!(image == readback(terminal.backend(), 512, 72));
+}